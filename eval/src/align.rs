@@ -0,0 +1,168 @@
+//! Levenshtein alignment between a reference and a hypothesis, counting the edits between them.
+
+use serde::Serialize;
+use std::ops::AddAssign;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditOp {
+    /// The reference and hypothesis agree
+    Hit,
+    /// The hypothesis has a different item in place of the reference one
+    Substitution,
+    /// The hypothesis has an item the reference does not
+    Insertion,
+    /// The hypothesis is missing an item of the reference
+    Deletion,
+}
+
+/// One step of an alignment, holding the indices of the items it covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edit {
+    pub op: EditOp,
+    /// Index into the reference, `None` for insertions
+    pub reference: Option<usize>,
+    /// Index into the hypothesis, `None` for deletions
+    pub hypothesis: Option<usize>,
+}
+
+/// Find a minimal sequence of edits turning `reference` into `hypothesis`.
+///
+/// When several alignments have the same cost, substitutions are preferred over a
+/// deletion/insertion pair, which keeps diffs of similar transcripts lined up.
+pub fn align<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> Vec<Edit> {
+    let (n, m) = (reference.len(), hypothesis.len());
+    // only two rows of costs are kept, but every cell's best move is needed for the backtrace
+    let mut moves = vec![EditOp::Hit; (n + 1) * (m + 1)];
+    let mut previous: Vec<usize> = (0..=m).collect();
+    let mut current = vec![0; m + 1];
+    moves[1..=m].fill(EditOp::Insertion);
+
+    for i in 1..=n {
+        current[0] = i;
+        moves[i * (m + 1)] = EditOp::Deletion;
+        for j in 1..=m {
+            let (diagonal, op) = if reference[i - 1] == hypothesis[j - 1] {
+                (previous[j - 1], EditOp::Hit)
+            } else {
+                (previous[j - 1] + 1, EditOp::Substitution)
+            };
+            let (cost, op) = [
+                (diagonal, op),
+                (previous[j] + 1, EditOp::Deletion),
+                (current[j - 1] + 1, EditOp::Insertion),
+            ]
+            .into_iter()
+            .min_by_key(|(cost, _)| *cost)
+            .expect("there are always three candidates");
+            current[j] = cost;
+            moves[i * (m + 1) + j] = op;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let mut edits = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let op = moves[i * (m + 1) + j];
+        let edit = match op {
+            EditOp::Hit | EditOp::Substitution => {
+                i -= 1;
+                j -= 1;
+                Edit {
+                    op,
+                    reference: Some(i),
+                    hypothesis: Some(j),
+                }
+            }
+            EditOp::Deletion => {
+                i -= 1;
+                Edit {
+                    op,
+                    reference: Some(i),
+                    hypothesis: None,
+                }
+            }
+            EditOp::Insertion => {
+                j -= 1;
+                Edit {
+                    op,
+                    reference: None,
+                    hypothesis: Some(j),
+                }
+            }
+        };
+        edits.push(edit);
+    }
+    edits.reverse();
+    edits
+}
+
+/// Edit counts of one or more alignments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorCounts {
+    /// Number of items in the reference
+    pub reference_len: usize,
+    pub hits: usize,
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl ErrorCounts {
+    pub fn from_edits(edits: &[Edit]) -> Self {
+        let mut counts = Self::default();
+        for edit in edits {
+            match edit.op {
+                EditOp::Hit => counts.hits += 1,
+                EditOp::Substitution => counts.substitutions += 1,
+                EditOp::Insertion => counts.insertions += 1,
+                EditOp::Deletion => counts.deletions += 1,
+            }
+        }
+        counts.reference_len = counts.hits + counts.substitutions + counts.deletions;
+        counts
+    }
+
+    pub fn errors(&self) -> usize {
+        self.substitutions + self.insertions + self.deletions
+    }
+
+    /// Errors per reference item. With an empty reference, every insertion counts as a
+    /// whole error rather than the rate becoming infinite.
+    pub fn rate(&self) -> f64 {
+        self.errors() as f64 / self.reference_len.max(1) as f64
+    }
+}
+
+impl AddAssign for ErrorCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.reference_len += other.reference_len;
+        self.hits += other.hits;
+        self.substitutions += other.substitutions;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+    }
+}
+
+/// [`ErrorCounts`] as they appear in reports, together with the error rate.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Score {
+    pub rate: f64,
+    pub reference_len: usize,
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl From<ErrorCounts> for Score {
+    fn from(counts: ErrorCounts) -> Self {
+        Self {
+            rate: counts.rate(),
+            reference_len: counts.reference_len,
+            substitutions: counts.substitutions,
+            insertions: counts.insertions,
+            deletions: counts.deletions,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use april_asr_eval::{compare, Manifest, Normalizer};
+use std::path::PathBuf;
+
+fn main() {
+    let usage = format!(
+        "usage: april-compare [--json] {} <manifest.jsonl> <baseline.april> <model.april>...",
+        Normalizer::FLAGS
+    );
+    let mut normalizer = Normalizer::default();
+    let mut json = false;
+    let mut positional = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            _ if normalizer.apply_flag(&arg) => {}
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", usage);
+                return;
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown option {}\n{}", arg, usage);
+                std::process::exit(2);
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let [manifest_path, models @ ..] = positional.as_slice() else {
+        eprintln!("{}", usage);
+        std::process::exit(2);
+    };
+    if models.len() < 2 {
+        eprintln!("at least two models are needed for a comparison\n{}", usage);
+        std::process::exit(2);
+    }
+    let models: Vec<PathBuf> = models.iter().map(PathBuf::from).collect();
+
+    let manifest = Manifest::load(manifest_path)
+        .unwrap_or_else(|e| panic!("failed to load manifest {}: {}", manifest_path, e));
+    let total = manifest.entries.len();
+    let mut done = 0;
+    let comparison = compare(&models, &manifest, &normalizer, |model, file| {
+        done = done % total + 1;
+        eprintln!(
+            "{} [{}/{}] {}: WER {:.2}%",
+            model.display(),
+            done,
+            total,
+            file.audio.display(),
+            file.wer.rate * 100.0
+        );
+    })
+    .unwrap_or_else(|e| panic!("comparison failed: {}", e));
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&comparison).expect("comparisons always serialize")
+        );
+    } else {
+        print!("{}", comparison);
+    }
+}
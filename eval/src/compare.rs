@@ -0,0 +1,195 @@
+//! Side-by-side comparison of several models over the same corpus, for deciding whether a
+//! new model is an improvement over the one it would replace.
+
+use crate::align::{align, EditOp, Score};
+use crate::error::Result;
+use crate::manifest::Manifest;
+use crate::normalize::Normalizer;
+use crate::report::{evaluate, FileReport, Report};
+use april_asr_rs::AprilModel;
+use serde::Serialize;
+use std::fmt::Formatter;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSummary {
+    pub path: PathBuf,
+    pub name: String,
+    pub wer: Score,
+    pub cer: Score,
+    /// Wall-clock realtime speedup, as sessions are run synchronously
+    pub realtime_speedup: f64,
+    pub mean_first_partial_seconds: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hypothesis {
+    pub text: String,
+    pub wer: f64,
+    /// Word diff against the first model's hypothesis, in `[-removed-] {+added+}` notation.
+    /// `None` for the first model itself.
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileComparison {
+    pub audio: PathBuf,
+    pub reference: String,
+    /// Whether all models produced the same transcript after normalization
+    pub agree: bool,
+    /// One hypothesis per model, in the order the models were given
+    pub hypotheses: Vec<Hypothesis>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub normalizer: Normalizer,
+    pub models: Vec<ModelSummary>,
+    pub files: Vec<FileComparison>,
+}
+
+/// Evaluate each model over `manifest` in turn, then line up their results file by file.
+///
+/// Models are loaded one at a time, so only one is in memory at once. The first model is the
+/// baseline the others are diffed against.
+pub fn compare(
+    models: &[PathBuf],
+    manifest: &Manifest,
+    normalizer: &Normalizer,
+    mut progress: impl FnMut(&Path, &FileReport),
+) -> Result<Comparison> {
+    let mut reports = Vec::with_capacity(models.len());
+    for path in models {
+        let model = AprilModel::new(path.as_os_str().as_encoded_bytes())?;
+        reports.push(evaluate(&model, manifest, normalizer, |file| {
+            progress(path, file)
+        })?);
+    }
+    Ok(Comparison::from_reports(models, &reports, normalizer))
+}
+
+impl Comparison {
+    /// Combine reports of the same corpus, one per model in `paths`.
+    pub fn from_reports(paths: &[PathBuf], reports: &[Report], normalizer: &Normalizer) -> Self {
+        let models = paths
+            .iter()
+            .zip(reports)
+            .map(|(path, report)| ModelSummary {
+                path: path.clone(),
+                name: report.model.clone(),
+                wer: report.wer,
+                cer: report.cer,
+                realtime_speedup: report.realtime_speedup(),
+                mean_first_partial_seconds: report.mean_first_partial_seconds(),
+            })
+            .collect();
+
+        let file_count = reports.first().map_or(0, |report| report.files.len());
+        let files = (0..file_count)
+            .map(|index| {
+                let files: Vec<&FileReport> =
+                    reports.iter().map(|report| &report.files[index]).collect();
+                let words: Vec<Vec<String>> = files
+                    .iter()
+                    .map(|file| normalizer.words(&file.hypothesis))
+                    .collect();
+                let hypotheses = files
+                    .iter()
+                    .zip(&words)
+                    .enumerate()
+                    .map(|(i, (file, words_i))| Hypothesis {
+                        text: file.hypothesis.clone(),
+                        wer: file.wer.rate,
+                        diff: (i > 0).then(|| word_diff(&words[0], words_i)),
+                    })
+                    .collect();
+                FileComparison {
+                    audio: files[0].audio.clone(),
+                    reference: files[0].reference.clone(),
+                    agree: words.windows(2).all(|pair| pair[0] == pair[1]),
+                    hypotheses,
+                }
+            })
+            .collect();
+
+        Self {
+            normalizer: normalizer.clone(),
+            models,
+            files,
+        }
+    }
+}
+
+/// Render the word-level differences from `from` to `to`, with unchanged words as they are.
+pub fn word_diff(from: &[String], to: &[String]) -> String {
+    let mut parts = Vec::new();
+    for edit in align(from, to) {
+        let removed = edit.reference.map(|i| &from[i]);
+        let added = edit.hypothesis.map(|i| &to[i]);
+        match (edit.op, removed, added) {
+            (EditOp::Hit, Some(word), _) => parts.push(word.clone()),
+            (EditOp::Substitution, Some(removed), Some(added)) => {
+                parts.push(format!("[-{}-] {{+{}+}}", removed, added))
+            }
+            (EditOp::Deletion, Some(removed), _) => parts.push(format!("[-{}-]", removed)),
+            (EditOp::Insertion, _, Some(added)) => parts.push(format!("{{+{}+}}", added)),
+            _ => unreachable!("alignments always index the sides their edit covers"),
+        }
+    }
+    parts.join(" ")
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<4} {:<24} {:>8} {:>8} {:>10} {:>14}",
+            "", "model", "WER", "CER", "speedup", "first partial"
+        )?;
+        for (i, model) in self.models.iter().enumerate() {
+            let first_partial = model
+                .mean_first_partial_seconds
+                .map_or("-".to_owned(), |s| format!("{:.0}ms", s * 1000.0));
+            writeln!(
+                f,
+                "{:<4} {:<24} {:>7.2}% {:>7.2}% {:>9.1}x {:>14}",
+                format!("[{}]", i),
+                model.name,
+                model.wer.rate * 100.0,
+                model.cer.rate * 100.0,
+                model.realtime_speedup,
+                first_partial
+            )?;
+        }
+        for (i, model) in self.models.iter().enumerate() {
+            writeln!(f, "[{}] {}", i, model.path.display())?;
+        }
+
+        let disagreements: Vec<&FileComparison> =
+            self.files.iter().filter(|file| !file.agree).collect();
+        writeln!(
+            f,
+            "\nmodels disagree on {} of {} files",
+            disagreements.len(),
+            self.files.len()
+        )?;
+        for file in disagreements {
+            writeln!(f, "\n{}", file.audio.display())?;
+            writeln!(f, "  ref      {}", file.reference)?;
+            for (i, hypothesis) in file.hypotheses.iter().enumerate() {
+                let label = format!("[{}]", i);
+                writeln!(
+                    f,
+                    "  {:<4} {:>3.0}% {}",
+                    label,
+                    hypothesis.wer * 100.0,
+                    hypothesis.text
+                )?;
+                if let Some(diff) = &hypothesis.diff {
+                    writeln!(f, "  {:<4} diff {}", "", diff)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
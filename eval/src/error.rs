@@ -0,0 +1,40 @@
+use std::fmt::Formatter;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read the manifest or an audio file
+    Io(PathBuf, std::io::Error),
+    /// A manifest line is not a valid entry
+    Manifest {
+        line: usize,
+        source: serde_json::Error,
+    },
+    /// An audio file could not be decoded
+    Audio(PathBuf, &'static str),
+    /// April failed to load the model or to create a session
+    April(april_asr_rs::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Error::Manifest { line, source } => {
+                write!(f, "invalid manifest entry on line {}: {}", line, source)
+            }
+            Error::Audio(path, e) => write!(f, "failed to decode {}: {}", path.display(), e),
+            Error::April(e) => write!(f, "april error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<april_asr_rs::Error> for Error {
+    fn from(err: april_asr_rs::Error) -> Self {
+        Self::April(err)
+    }
+}
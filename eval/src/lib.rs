@@ -0,0 +1,15 @@
+mod align;
+mod compare;
+mod error;
+mod manifest;
+mod normalize;
+mod report;
+mod transcribe;
+
+pub use align::{align, Edit, EditOp, ErrorCounts, Score};
+pub use compare::{compare, word_diff, Comparison, FileComparison, Hypothesis, ModelSummary};
+pub use error::{Error, Result};
+pub use manifest::{Entry, Manifest};
+pub use normalize::Normalizer;
+pub use report::{evaluate, FileReport, Report, Scores};
+pub use transcribe::{transcribe, Transcription};
@@ -0,0 +1,59 @@
+use april_asr_eval::{evaluate, Manifest, Normalizer};
+use april_asr_rs::AprilModel;
+
+fn main() {
+    let usage = format!(
+        "usage: april-eval {} <model.april> <manifest.jsonl>",
+        Normalizer::FLAGS
+    );
+    let mut normalizer = Normalizer::default();
+    let mut positional = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            _ if normalizer.apply_flag(&arg) => {}
+            "-h" | "--help" => {
+                println!("{}", usage);
+                return;
+            }
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown option {}\n{}", arg, usage);
+                std::process::exit(2);
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let [model_path, manifest_path] = positional.as_slice() else {
+        eprintln!("{}", usage);
+        std::process::exit(2);
+    };
+
+    let manifest = Manifest::load(manifest_path)
+        .unwrap_or_else(|e| panic!("failed to load manifest {}: {}", manifest_path, e));
+    let model = AprilModel::new(model_path.as_str())
+        .unwrap_or_else(|e| panic!("failed to load model {}: {}", model_path, e));
+
+    let total = manifest.entries.len();
+    let mut done = 0;
+    let report = evaluate(&model, &manifest, &normalizer, |file| {
+        done += 1;
+        eprintln!(
+            "[{}/{}] {}: WER {:.2}%",
+            done,
+            total,
+            file.audio.display(),
+            file.wer.rate * 100.0
+        );
+    })
+    .unwrap_or_else(|e| panic!("evaluation failed: {}", e));
+
+    eprintln!(
+        "WER {:.2}%, CER {:.2}% over {} files",
+        report.wer.rate * 100.0,
+        report.cer.rate * 100.0,
+        total
+    );
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("reports always serialize")
+    );
+}
@@ -0,0 +1,86 @@
+//! Evaluation corpora, listed one recording per line as JSON:
+//!
+//! ```json
+//! {"audio": "clips/0001.wav", "reference": "the quick brown fox"}
+//! {"audio": "clips/0002.raw", "reference": "jumps over the lazy dog", "sample_rate": 8000}
+//! ```
+//!
+//! Relative audio paths are resolved against the directory holding the manifest. Audio is
+//! either a pcm16 WAV file, or headerless mono pcm16 at `sample_rate` (the model's rate if
+//! omitted). Blank lines and lines starting with `#` are skipped.
+
+use crate::error::{Error, Result};
+use april_asr_rs::codec::{wav, Resampler};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    pub audio: PathBuf,
+    pub reference: String,
+    /// Sample rate of headerless audio, ignored for WAV files
+    #[serde(default)]
+    pub sample_rate: Option<usize>,
+}
+
+impl Entry {
+    /// Read the audio and convert it to mono pcm16 at `sample_rate`.
+    pub fn load_audio(&self, sample_rate: usize) -> Result<Vec<i16>> {
+        let bytes = std::fs::read(&self.audio).map_err(|e| Error::Io(self.audio.clone(), e))?;
+        let (rate, samples) = if bytes.starts_with(b"RIFF") {
+            let wav = wav::parse(&bytes).map_err(|e| Error::Audio(self.audio.clone(), e))?;
+            (wav.sample_rate as usize, wav.samples)
+        } else {
+            if !bytes.len().is_multiple_of(2) {
+                return Err(Error::Audio(
+                    self.audio.clone(),
+                    "raw pcm16 audio has an odd number of bytes",
+                ));
+            }
+            (
+                self.sample_rate.unwrap_or(sample_rate),
+                wav::downmix(&bytes, 1),
+            )
+        };
+        if rate == 0 {
+            return Err(Error::Audio(self.audio.clone(), "sample rate is zero"));
+        }
+        Ok(Resampler::new(rate, sample_rate).process(&samples))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    pub entries: Vec<Entry>,
+}
+
+impl Manifest {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| Error::Io(path.to_owned(), e))?;
+        let mut manifest = Self::parse(&text)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for entry in &mut manifest.entries {
+            entry.audio = base.join(&entry.audio);
+        }
+        Ok(manifest)
+    }
+
+    /// Parse manifest lines, leaving audio paths as they are.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = serde_json::from_str(line).map_err(|source| Error::Manifest {
+                line: index + 1,
+                source,
+            })?;
+            entries.push(entry);
+        }
+        Ok(Self { entries })
+    }
+}
@@ -0,0 +1,238 @@
+//! Text normalization applied to both sides before scoring, so that formatting differences
+//! between reference transcripts and model output are not counted as errors.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Normalizer {
+    /// Compare case-insensitively
+    pub lowercase: bool,
+    /// Drop punctuation, keeping apostrophes inside words ("don't")
+    pub strip_punctuation: bool,
+    /// Spell out numbers written with digits ("42" becomes "forty two")
+    pub spell_numbers: bool,
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            strip_punctuation: true,
+            spell_numbers: true,
+        }
+    }
+}
+
+impl Normalizer {
+    /// Command line flags turning off each normalization, as accepted by [`Self::apply_flag`].
+    pub const FLAGS: &'static str = "[--keep-case] [--keep-punctuation] [--keep-numbers]";
+
+    /// Apply a command line flag from [`Self::FLAGS`], returning whether `flag` is one.
+    pub fn apply_flag(&mut self, flag: &str) -> bool {
+        match flag {
+            "--keep-case" => self.lowercase = false,
+            "--keep-punctuation" => self.strip_punctuation = false,
+            "--keep-numbers" => self.spell_numbers = false,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Normalize `text` into single-space separated words.
+    pub fn normalize(&self, text: &str) -> String {
+        self.words(text).join(" ")
+    }
+
+    pub fn words(&self, text: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        for word in text.split_whitespace() {
+            let mut word = if self.lowercase {
+                word.to_lowercase()
+            } else {
+                word.to_owned()
+            };
+            if self.spell_numbers {
+                if let Some(spelled) = spell_number(&word) {
+                    words.extend(spelled.split(' ').map(str::to_owned));
+                    continue;
+                }
+            }
+            if self.strip_punctuation {
+                word = strip_punctuation(&word);
+            }
+            words.extend(word.split_whitespace().map(str::to_owned));
+        }
+        words
+    }
+}
+
+fn strip_punctuation(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let inner_apostrophe = c == '\''
+                && i > 0
+                && chars[i - 1].is_alphanumeric()
+                && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric());
+            if c.is_alphanumeric() || inner_apostrophe {
+                c
+            } else {
+                // hyphenated and slashed words become separate words
+                ' '
+            }
+        })
+        .collect()
+}
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+const SCALES: [(u64, &str); 6] = [
+    (1_000_000_000_000_000_000, "quintillion"),
+    (1_000_000_000_000_000, "quadrillion"),
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+/// Currency symbols read out after the amount, with the names of the unit and of its
+/// hundredth, singular and plural.
+const CURRENCIES: [(char, [&str; 2], [&str; 2]); 3] = [
+    ('$', ["dollar", "dollars"], ["cent", "cents"]),
+    ('€', ["euro", "euros"], ["cent", "cents"]),
+    ('£', ["pound", "pounds"], ["penny", "pence"]),
+];
+
+/// Spell out a word that is a number such as "42", "1,000", "3.5" or "-7", possibly wrapped in
+/// punctuation. Amounts of money are read out the way they are said, so "$20" becomes
+/// "twenty dollars" and "£2.50" becomes "two pounds fifty pence". Returns `None` for anything
+/// else, including mixed words like "4th".
+fn spell_number(word: &str) -> Option<String> {
+    let word = word
+        .trim_start_matches(|c: char| c.is_ascii_punctuation() && c != '-' && c != '$')
+        .trim_end_matches(|c: char| c.is_ascii_punctuation());
+    let (negative, word) = match word.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, word),
+    };
+    let (currency, word) = match CURRENCIES
+        .iter()
+        .find(|(symbol, ..)| word.starts_with(*symbol))
+    {
+        Some(currency) => (Some(currency), &word[currency.0.len_utf8()..]),
+        None => (None, word),
+    };
+    let (integer, fraction) = match word.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (word, None),
+    };
+    let digits: String = integer.chars().filter(|&c| c != ',').collect();
+    if digits.is_empty()
+        || !digits.chars().all(|c| c.is_ascii_digit())
+        || fraction.is_some_and(|f| f.is_empty() || !f.chars().all(|c| c.is_ascii_digit()))
+    {
+        return None;
+    }
+
+    let mut words = Vec::new();
+    if negative {
+        words.push("minus".to_owned());
+    }
+    let value = digits.parse::<u64>();
+    match value {
+        Ok(value) => spell_integer(value, &mut words),
+        // too long to be a quantity, so read it digit by digit
+        Err(_) => words.extend(digits.bytes().map(|d| ONES[(d - b'0') as usize].to_owned())),
+    }
+    let plural = |value: u64| usize::from(value != 1);
+    match (currency, fraction) {
+        // "$3.50" is three dollars fifty cents
+        (Some((_, unit, hundredth)), Some(cents)) if cents.len() == 2 => {
+            words.push(unit[value.map_or(1, plural)].to_owned());
+            let cents: u64 = cents.parse().expect("fraction digits were checked");
+            if cents > 0 {
+                spell_integer(cents, &mut words);
+                words.push(hundredth[plural(cents)].to_owned());
+            }
+        }
+        (currency, fraction) => {
+            if let Some(fraction) = fraction {
+                words.push("point".to_owned());
+                words.extend(
+                    fraction
+                        .bytes()
+                        .map(|d| ONES[(d - b'0') as usize].to_owned()),
+                );
+            }
+            if let Some((_, unit, _)) = currency {
+                // "1.5 dollars" is plural like any other fraction
+                let form = if fraction.is_some() {
+                    1
+                } else {
+                    value.map_or(1, plural)
+                };
+                words.push(unit[form].to_owned());
+            }
+        }
+    }
+    Some(words.join(" "))
+}
+
+fn spell_integer(mut value: u64, words: &mut Vec<String>) {
+    if value == 0 {
+        words.push(ONES[0].to_owned());
+        return;
+    }
+    for (scale, name) in SCALES {
+        if value >= scale {
+            spell_below_thousand(value / scale, words);
+            words.push(name.to_owned());
+            value %= scale;
+        }
+    }
+    if value > 0 {
+        spell_below_thousand(value, words);
+    }
+}
+
+fn spell_below_thousand(value: u64, words: &mut Vec<String>) {
+    let (hundreds, rest) = (value / 100, value % 100);
+    if hundreds > 0 {
+        words.push(ONES[hundreds as usize].to_owned());
+        words.push("hundred".to_owned());
+    }
+    if rest >= 20 {
+        words.push(TENS[(rest / 10) as usize].to_owned());
+        if rest % 10 > 0 {
+            words.push(ONES[(rest % 10) as usize].to_owned());
+        }
+    } else if rest > 0 {
+        words.push(ONES[rest as usize].to_owned());
+    }
+}
@@ -0,0 +1,123 @@
+use crate::align::{align, ErrorCounts, Score};
+use crate::error::Result;
+use crate::manifest::{Entry, Manifest};
+use crate::normalize::Normalizer;
+use crate::transcribe::{transcribe, Transcription};
+use april_asr_rs::AprilModel;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Word and character error counts of one hypothesis against its reference.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Scores {
+    pub words: ErrorCounts,
+    pub chars: ErrorCounts,
+}
+
+impl Scores {
+    /// Score `hypothesis` against `reference`, after normalizing both. Characters are compared
+    /// on the normalized text, including the single spaces between words.
+    pub fn compute(normalizer: &Normalizer, reference: &str, hypothesis: &str) -> Self {
+        let reference = normalizer.words(reference);
+        let hypothesis = normalizer.words(hypothesis);
+        let reference_chars: Vec<char> = reference.join(" ").chars().collect();
+        let hypothesis_chars: Vec<char> = hypothesis.join(" ").chars().collect();
+        Self {
+            words: ErrorCounts::from_edits(&align(&reference, &hypothesis)),
+            chars: ErrorCounts::from_edits(&align(&reference_chars, &hypothesis_chars)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub audio: PathBuf,
+    pub reference: String,
+    pub hypothesis: String,
+    pub wer: Score,
+    pub cer: Score,
+    pub audio_seconds: f64,
+    pub processing_seconds: f64,
+    /// Seconds of audio fed before the first recognition result, if there was one
+    pub first_partial_seconds: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub model: String,
+    pub normalizer: Normalizer,
+    /// Word error rate over the whole corpus, so longer files weigh more
+    pub wer: Score,
+    /// Character error rate over the whole corpus
+    pub cer: Score,
+    pub audio_seconds: f64,
+    pub processing_seconds: f64,
+    pub files: Vec<FileReport>,
+}
+
+impl FileReport {
+    pub fn new(entry: &Entry, transcription: &Transcription, scores: Scores) -> Self {
+        Self {
+            audio: entry.audio.clone(),
+            reference: entry.reference.clone(),
+            hypothesis: transcription.text.clone(),
+            wer: scores.words.into(),
+            cer: scores.chars.into(),
+            audio_seconds: transcription.audio.as_secs_f64(),
+            processing_seconds: transcription.elapsed.as_secs_f64(),
+            first_partial_seconds: transcription.first_partial.map(|d| d.as_secs_f64()),
+        }
+    }
+}
+
+/// Transcribe every entry of `manifest` with `model` and score the results.
+///
+/// `progress` is called after each file, e.g. to log how far along a long run is.
+pub fn evaluate(
+    model: &AprilModel,
+    manifest: &Manifest,
+    normalizer: &Normalizer,
+    mut progress: impl FnMut(&FileReport),
+) -> Result<Report> {
+    let mut words = ErrorCounts::default();
+    let mut chars = ErrorCounts::default();
+    let mut files = Vec::with_capacity(manifest.entries.len());
+    for entry in &manifest.entries {
+        let samples = entry.load_audio(model.get_sample_rate())?;
+        let transcription = transcribe(model, &samples)?;
+        let scores = Scores::compute(normalizer, &entry.reference, &transcription.text);
+        words += scores.words;
+        chars += scores.chars;
+
+        let file = FileReport::new(entry, &transcription, scores);
+        progress(&file);
+        files.push(file);
+    }
+
+    Ok(Report {
+        model: model.get_model_name()?.to_owned(),
+        normalizer: normalizer.clone(),
+        wer: words.into(),
+        cer: chars.into(),
+        audio_seconds: files.iter().map(|file| file.audio_seconds).sum(),
+        processing_seconds: files.iter().map(|file| file.processing_seconds).sum(),
+        files,
+    })
+}
+
+impl Report {
+    /// Seconds of audio transcribed per second of processing, over the whole corpus.
+    pub fn realtime_speedup(&self) -> f64 {
+        self.audio_seconds / self.processing_seconds.max(f64::EPSILON)
+    }
+
+    /// Mean first partial latency of the files that produced any result.
+    pub fn mean_first_partial_seconds(&self) -> Option<f64> {
+        let latencies: Vec<f64> = self
+            .files
+            .iter()
+            .filter_map(|file| file.first_partial_seconds)
+            .collect();
+        (!latencies.is_empty()).then(|| latencies.iter().sum::<f64>() / latencies.len() as f64)
+    }
+}
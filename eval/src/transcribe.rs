@@ -0,0 +1,83 @@
+use crate::error::Result;
+use april_asr_rs::{AprilConfig, AprilModel, AprilResultType};
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant};
+
+/// How much audio is fed to the session at once, in milliseconds. This bounds how precisely
+/// the first partial result can be timed.
+const CHUNK_MS: usize = 100;
+
+/// The transcript of one recording, and how long it took to produce.
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    pub text: String,
+    pub audio: Duration,
+    /// Wall-clock time spent in april
+    pub elapsed: Duration,
+    /// How much audio had been fed when the first partial or final result arrived
+    pub first_partial: Option<Duration>,
+}
+
+impl Transcription {
+    /// Seconds of audio transcribed per second of processing.
+    pub fn realtime_speedup(&self) -> f64 {
+        self.audio.as_secs_f64() / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Run `samples`, at the model's sample rate, through a new synchronous session.
+pub fn transcribe(model: &AprilModel, samples: &[i16]) -> Result<Transcription> {
+    let (tx, results) = mpsc::channel();
+    let mut config = AprilConfig::default();
+    config.set_handler_fn(
+        |tx: &Sender<(AprilResultType, String)>, result, tokens| {
+            // the receiving end only goes away together with the session
+            let _ = tx.send((result, tokens.to_string()));
+        },
+        tx,
+    );
+    let mut session = model.create_session(config)?;
+
+    let sample_rate = model.get_sample_rate();
+    let mut text = String::new();
+    let mut first_partial = None;
+    let mut fed = 0;
+    let mut collect = |fed: usize| {
+        for (result, chunk) in results.try_iter() {
+            let recognized = matches!(
+                result,
+                AprilResultType::RecognitionPartial | AprilResultType::RecognitionFinal
+            );
+            if recognized && !chunk.trim().is_empty() && first_partial.is_none() {
+                first_partial = Some(samples_to_duration(fed, sample_rate));
+            }
+            if result == AprilResultType::RecognitionFinal {
+                text.push_str(&chunk);
+            }
+        }
+    };
+
+    let start = Instant::now();
+    let mut chunk = Vec::with_capacity(sample_rate * CHUNK_MS / 1000);
+    for samples in samples.chunks((sample_rate * CHUNK_MS / 1000).max(1)) {
+        chunk.clear();
+        chunk.extend_from_slice(samples);
+        session.feed_pcm16(&mut chunk);
+        fed += samples.len();
+        collect(fed);
+    }
+    session.flush();
+    collect(fed);
+    let elapsed = start.elapsed();
+
+    Ok(Transcription {
+        text: text.trim().to_owned(),
+        audio: samples_to_duration(samples.len(), sample_rate),
+        elapsed,
+        first_partial,
+    })
+}
+
+fn samples_to_duration(samples: usize, sample_rate: usize) -> Duration {
+    Duration::from_secs_f64(samples as f64 / sample_rate.max(1) as f64)
+}
@@ -0,0 +1,80 @@
+use april_asr_eval::{align, word_diff, EditOp, ErrorCounts, Normalizer, Scores};
+
+#[test]
+fn counts_each_kind_of_edit() {
+    let reference = ["the", "cat", "sat", "on", "the", "mat"];
+    let hypothesis = ["the", "bat", "sat", "the", "mat", "today"];
+    let edits = align(&reference, &hypothesis);
+    let ops: Vec<EditOp> = edits.iter().map(|edit| edit.op).collect();
+    assert_eq!(
+        ops,
+        [
+            EditOp::Hit,
+            EditOp::Substitution,
+            EditOp::Hit,
+            EditOp::Deletion,
+            EditOp::Hit,
+            EditOp::Hit,
+            EditOp::Insertion,
+        ]
+    );
+    let counts = ErrorCounts::from_edits(&edits);
+    assert_eq!(counts.reference_len, 6);
+    assert_eq!(
+        (counts.substitutions, counts.insertions, counts.deletions),
+        (1, 1, 1)
+    );
+    assert_eq!(counts.rate(), 0.5);
+}
+
+#[test]
+fn empty_sides() {
+    let empty: [&str; 0] = [];
+    assert_eq!(ErrorCounts::from_edits(&align(&empty, &empty)).rate(), 0.0);
+    let counts = ErrorCounts::from_edits(&align(&empty, &["hello"]));
+    assert_eq!((counts.insertions, counts.rate()), (1, 1.0));
+    let counts = ErrorCounts::from_edits(&align(&["hello"], &empty));
+    assert_eq!((counts.deletions, counts.rate()), (1, 1.0));
+}
+
+#[test]
+fn normalizes_before_scoring() {
+    let normalizer = Normalizer::default();
+    assert_eq!(
+        normalizer.normalize("Don't panic: it's 1,042 (or -3.5) well-known \"facts\"!"),
+        "don't panic it's one thousand forty two or minus three point five well known facts"
+    );
+    assert_eq!(normalizer.normalize("the 4th"), "the 4th");
+
+    assert_eq!(
+        normalizer.normalize("$1 €2.5 £3.05 $4.00 (-$20)"),
+        "one dollar two point five euros three pounds five pence four dollars minus twenty dollars"
+    );
+
+    let scores = Scores::compute(&normalizer, "It costs $20.", "IT COSTS TWENTY DOLLARS");
+    assert_eq!(scores.words.errors(), 0);
+    assert_eq!(scores.chars.errors(), 0);
+    // the currency is part of what was said
+    let scores = Scores::compute(&normalizer, "It costs $20.", "IT COSTS TWENTY");
+    assert_eq!((scores.words.errors(), scores.words.deletions), (1, 1));
+
+    let strict = Normalizer {
+        lowercase: false,
+        strip_punctuation: false,
+        spell_numbers: false,
+    };
+    let scores = Scores::compute(&strict, "It costs $20.", "IT COSTS TWENTY");
+    assert_eq!(scores.words.errors(), 3);
+}
+
+#[test]
+fn diffs_words() {
+    let words = |text: &str| Normalizer::default().words(text);
+    assert_eq!(
+        word_diff(
+            &words("the cat sat on the mat"),
+            &words("the bat sat the mat today")
+        ),
+        "the [-cat-] {+bat+} sat [-on-] the mat {+today+}"
+    );
+}
@@ -0,0 +1,181 @@
+use crate::error::Result;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Server configuration, loaded from a TOML file.
+///
+/// ```toml
+/// model = "/path/to/model.april"
+///
+/// [limits]
+/// max_sessions = 4
+///
+/// [websocket]
+/// listen = "127.0.0.1:2700"
+///
+/// [http]
+/// listen = "127.0.0.1:2701"
+///
+/// # optional, only started if present
+/// [vosk]
+/// listen = "127.0.0.1:2702"
+///
+/// # optional, only started if present
+/// [wyoming]
+/// listen = "127.0.0.1:10300"
+///
+/// # optional, only started if present
+/// [rtp]
+/// listen = "0.0.0.0:5004"
+/// jitter_packets = 4
+/// idle_timeout_ms = 2000
+///
+/// # optional, replaces phrases in final results, see `april_asr_server::VocabularyFile`
+/// [vocabulary]
+/// path = "/path/to/vocabulary.toml"
+/// reload_interval_ms = 2000
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path to the `.april` model file every session is created from.
+    pub model: PathBuf,
+    #[serde(default)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub websocket: WebSocketConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    pub vosk: Option<VoskServerConfig>,
+    pub wyoming: Option<WyomingServerConfig>,
+    pub rtp: Option<RtpConfig>,
+    pub vocabulary: Option<VocabularyConfig>,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_toml(&contents)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Maximum number of sessions running at once, across all connections.
+    /// Connections over this limit are refused.
+    pub max_sessions: usize,
+    /// Largest request body accepted by `POST /transcribe`, in bytes.
+    pub max_body_bytes: usize,
+    /// Longest recording accepted by `POST /transcribe`, in seconds.
+    pub max_audio_seconds: f64,
+    /// Maximum number of HTTP requests handled at once. Further requests wait for a free
+    /// handler.
+    pub max_http_requests: usize,
+    /// Largest header, data or payload of a Wyoming event, in bytes. Clients sending larger
+    /// ones are disconnected.
+    pub max_event_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_sessions: 4,
+            max_body_bytes: 32 * 1024 * 1024,
+            max_audio_seconds: 600.0,
+            max_http_requests: 8,
+            max_event_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebSocketConfig {
+    /// Address the WebSocket streaming server listens on.
+    pub listen: SocketAddr,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 2700)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Address the HTTP transcription endpoint listens on.
+    pub listen: SocketAddr,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 2701)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VoskServerConfig {
+    /// Address the Vosk-compatible WebSocket server listens on.
+    pub listen: SocketAddr,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WyomingServerConfig {
+    /// Address the Wyoming speech-to-text server listens on.
+    pub listen: SocketAddr,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RtpConfig {
+    /// UDP address RTP streams are sent to.
+    pub listen: SocketAddr,
+    /// Number of packets buffered per stream to put reordered packets back in place.
+    /// Higher values tolerate more jitter, at the cost of latency.
+    #[serde(default = "RtpConfig::default_jitter_packets")]
+    pub jitter_packets: usize,
+    /// A stream that sends no packets for this long is flushed and closed.
+    #[serde(default = "RtpConfig::default_idle_timeout_ms")]
+    pub idle_timeout_ms: u64,
+}
+
+impl RtpConfig {
+    fn default_jitter_packets() -> usize {
+        4
+    }
+
+    fn default_idle_timeout_ms() -> u64 {
+        2000
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VocabularyConfig {
+    /// Vocabulary file applied to the final results of every session, as TOML, or as JSON if
+    /// its name ends in `.json`.
+    pub path: PathBuf,
+    /// How often to check the file for changes, reloading it when it changed. 0 disables
+    /// reloading.
+    #[serde(default = "VocabularyConfig::default_reload_interval_ms")]
+    pub reload_interval_ms: u64,
+}
+
+impl VocabularyConfig {
+    fn default_reload_interval_ms() -> u64 {
+        2000
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt::Formatter;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to read the config file, or to bind/accept on a socket
+    Io(std::io::Error),
+    /// The config file is not valid TOML, or does not match the expected layout
+    Config(toml::de::Error),
+    /// April failed to load the model or to create a session
+    April(april_asr_rs::Error),
+    /// The WebSocket connection failed
+    WebSocket(Box<tungstenite::Error>),
+    /// The HTTP server failed to start
+    Http(Box<dyn std::error::Error + Send + Sync>),
+    /// A vocabulary file is not valid TOML or JSON, or does not match the expected layout
+    Vocabulary { path: PathBuf, reason: String },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Config(e) => write!(f, "invalid config: {}", e),
+            Error::April(e) => write!(f, "april error: {}", e),
+            Error::WebSocket(e) => write!(f, "websocket error: {}", e),
+            Error::Http(e) => write!(f, "HTTP error: {}", e),
+            Error::Vocabulary { path, reason } => {
+                write!(f, "invalid vocabulary {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Config(err)
+    }
+}
+
+impl From<april_asr_rs::Error> for Error {
+    fn from(err: april_asr_rs::Error) -> Self {
+        Self::April(err)
+    }
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(err: tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+}
@@ -0,0 +1,230 @@
+//! One-shot transcription over HTTP.
+//!
+//! * `POST /transcribe` takes a WAV file, or raw little-endian pcm16 described by the
+//!   `sample_rate` and `channels` query parameters, and returns the final [`Transcript`].
+//! * `GET /models` returns the metadata of the loaded model.
+//! * `GET /metrics` returns the metrics of all sessions, see [`crate::ServerMetrics`].
+//!
+//! Errors are returned as `{"error": ...}` with a matching status code.
+
+use crate::config::Limits;
+use crate::error::{Error, Result};
+use crate::limits::SessionLimiter;
+use crate::transcript::{self, Transcript};
+use april_asr_rs::codec::wav;
+use april_asr_rs::AprilModel;
+use serde::Serialize;
+use std::io::Read;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub struct HttpServer {
+    server: Server,
+    model: Arc<AprilModel>,
+    limiter: SessionLimiter,
+    limits: Limits,
+}
+
+impl HttpServer {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        model: Arc<AprilModel>,
+        limiter: SessionLimiter,
+        limits: Limits,
+    ) -> Result<Self> {
+        Ok(Self {
+            server: Server::http(addr).map_err(Error::Http)?,
+            model,
+            limiter,
+            limits,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.server.server_addr().to_ip().ok_or_else(|| {
+            Error::Io(std::io::Error::other(
+                "HTTP server is not bound to an IP address",
+            ))
+        })
+    }
+
+    /// Handle requests forever, on [`Limits::max_http_requests`] threads. Further requests
+    /// wait until one of them is free.
+    pub fn run(self) -> Result<()> {
+        let this = Arc::new(self);
+        let workers: Vec<_> = (0..this.limits.max_http_requests.max(1))
+            .map(|_| {
+                let this = Arc::clone(&this);
+                std::thread::spawn(move || {
+                    for request in this.server.incoming_requests() {
+                        if let Err(e) = this.handle(request) {
+                            eprintln!("failed to send HTTP response: {}", e);
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            let _ = worker.join();
+        }
+        Ok(())
+    }
+
+    fn handle(&self, mut request: Request) -> std::io::Result<()> {
+        let url = request.url().to_owned();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let result = match (request.method().clone(), path) {
+            (Method::Get, "/models") => Ok(json_response(200, &self.models())),
+            (Method::Get, "/metrics") => Ok(text_response(
+                "text/plain; version=0.0.4",
+                self.limiter
+                    .metrics()
+                    .render_prometheus(self.limiter.active()),
+            )),
+            (Method::Post, "/transcribe") => self
+                .transcribe(&mut request, query)
+                .map(|transcript| json_response(200, &transcript)),
+            (_, "/models" | "/metrics" | "/transcribe") => {
+                Err(HttpError(405, "method not allowed".into()))
+            }
+            _ => Err(HttpError(404, "not found".into())),
+        };
+        let response = result
+            .unwrap_or_else(|HttpError(status, error)| json_response(status, &ErrorBody { error }));
+        request.respond(response)
+    }
+
+    fn models(&self) -> ModelList {
+        let model = &self.model;
+        ModelList {
+            models: vec![ModelInfo {
+                name: model.get_model_name().unwrap_or_default().to_owned(),
+                description: model.get_model_description().unwrap_or_default().to_owned(),
+                language: model.get_model_language().unwrap_or_default().to_owned(),
+                sample_rate: model.get_sample_rate(),
+            }],
+        }
+    }
+
+    fn transcribe(
+        &self,
+        request: &mut Request,
+        query: &str,
+    ) -> std::result::Result<Transcript, HttpError> {
+        let max_body = self.limits.max_body_bytes;
+        if request.body_length().is_some_and(|len| len > max_body) {
+            return Err(HttpError(413, "request body too large".into()));
+        }
+        // refuse before reading the body, so busy servers don't buffer it for nothing
+        let Some(_permit) = self.limiter.try_acquire() else {
+            return Err(HttpError(503, "too many active sessions".into()));
+        };
+        let mut body = Vec::new();
+        request
+            .as_reader()
+            .take(max_body as u64 + 1)
+            .read_to_end(&mut body)
+            .map_err(|e| HttpError(400, format!("failed to read request body: {}", e)))?;
+        if body.len() > max_body {
+            return Err(HttpError(413, "request body too large".into()));
+        }
+
+        let is_wav = body.starts_with(b"RIFF")
+            || request.headers().iter().any(|h| {
+                h.field.equiv("Content-Type")
+                    && matches!(h.value.as_str(), "audio/wav" | "audio/x-wav" | "audio/wave")
+            });
+        let (sample_rate, mut samples) = if is_wav {
+            let wav = wav::parse(&body).map_err(|e| HttpError(400, e.into()))?;
+            (wav.sample_rate as usize, wav.samples)
+        } else {
+            let sample_rate =
+                query_param(query, "sample_rate")?.unwrap_or_else(|| self.model.get_sample_rate());
+            let channels = query_param(query, "channels")?.unwrap_or(1);
+            if channels == 0 || !body.len().is_multiple_of(2 * channels) {
+                return Err(HttpError(
+                    400,
+                    "raw audio must contain whole pcm16 frames".into(),
+                ));
+            }
+            (sample_rate, wav::downmix(&body, channels))
+        };
+
+        let model_rate = self.model.get_sample_rate();
+        if sample_rate != model_rate {
+            return Err(HttpError(
+                422,
+                format!(
+                    "sample rate {} does not match the model's {}",
+                    sample_rate, model_rate
+                ),
+            ));
+        }
+        if samples.len() as f64 / sample_rate as f64 > self.limits.max_audio_seconds {
+            return Err(HttpError(
+                413,
+                format!(
+                    "audio is longer than {} seconds",
+                    self.limits.max_audio_seconds
+                ),
+            ));
+        }
+
+        let (mut session, results) =
+            transcript::create_session(&self.model, self.limiter.vocabulary())
+                .map_err(|e| HttpError(500, format!("transcription failed: {}", e)))?;
+        self.limiter.metrics().track(session.metrics_handle());
+        session.feed_pcm16(&mut samples);
+        session.flush();
+        Ok(Transcript::from_events(results.try_iter()))
+    }
+}
+
+struct HttpError(u16, String);
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct ModelList {
+    models: Vec<ModelInfo>,
+}
+
+#[derive(Serialize)]
+struct ModelInfo {
+    name: String,
+    description: String,
+    language: String,
+    sample_rate: usize,
+}
+
+fn query_param(query: &str, name: &str) -> std::result::Result<Option<usize>, HttpError> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| {
+            value
+                .parse()
+                .map_err(|_| HttpError(400, format!("invalid value for {}: {}", name, value)))
+        })
+        .transpose()
+}
+
+fn json_response(status: u16, body: &impl Serialize) -> Response<std::io::Cursor<Vec<u8>>> {
+    let json = serde_json::to_vec(body).expect("HTTP responses always serialize");
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+    Response::from_data(json)
+        .with_status_code(status)
+        .with_header(content_type)
+}
+
+fn text_response(content_type: &str, body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let content_type =
+        Header::from_bytes("Content-Type", content_type).expect("static header is valid");
+    Response::from_string(body).with_header(content_type)
+}
@@ -0,0 +1,33 @@
+mod config;
+mod error;
+mod http;
+mod limits;
+mod metrics;
+mod rtp;
+mod transcript;
+mod vocabulary;
+mod vosk;
+mod websocket;
+mod wyoming;
+
+pub use config::{
+    Config, HttpConfig, Limits, RtpConfig, VocabularyConfig, VoskServerConfig, WebSocketConfig,
+    WyomingServerConfig,
+};
+pub use error::{Error, Result};
+pub use http::HttpServer;
+pub use limits::{SessionLimiter, SessionPermit};
+pub use metrics::ServerMetrics;
+pub use rtp::{
+    JitterBuffer, JitterOutput, JitterStats, RtpIngest, RtpPacket, RtpTranscript,
+    PAYLOAD_TYPE_PCMA, PAYLOAD_TYPE_PCMU,
+};
+pub use transcript::{
+    create_session, transcribe, Event, EventSink, Replacement, Token, Transcript, Word,
+};
+pub use vocabulary::{MatchKind, ReplaceEntry, SharedVocabulary, VocabularyFile};
+pub use vosk::{
+    SessionAction, VoskAdapter, VoskClientMessage, VoskConfig, VoskReply, VoskServer, VoskWord,
+};
+pub use websocket::WebSocketServer;
+pub use wyoming::{error_event, info_event, WyomingEvent, WyomingServer, WYOMING_VERSION};
@@ -0,0 +1,73 @@
+use crate::metrics::ServerMetrics;
+use crate::vocabulary::SharedVocabulary;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts running sessions and refuses new ones once `max` are active. It also holds the
+/// metrics and vocabulary of all sessions, since it is shared by every server.
+///
+/// Cheap to clone: all clones share the same counter.
+#[derive(Debug, Clone)]
+pub struct SessionLimiter {
+    active: Arc<AtomicUsize>,
+    max: usize,
+    metrics: ServerMetrics,
+    vocabulary: SharedVocabulary,
+}
+
+impl SessionLimiter {
+    pub fn new(max: usize) -> Self {
+        Self {
+            active: Arc::new(AtomicUsize::new(0)),
+            max,
+            metrics: ServerMetrics::new(),
+            vocabulary: SharedVocabulary::default(),
+        }
+    }
+
+    /// Apply `vocabulary` to the final results of every session, instead of no vocabulary.
+    pub fn with_vocabulary(mut self, vocabulary: SharedVocabulary) -> Self {
+        self.vocabulary = vocabulary;
+        self
+    }
+
+    /// Metrics of the sessions started under this limiter. Servers
+    /// [`ServerMetrics::track`] every session they create.
+    pub fn metrics(&self) -> &ServerMetrics {
+        &self.metrics
+    }
+
+    /// Vocabulary servers pass to [`crate::create_session`].
+    pub fn vocabulary(&self) -> &SharedVocabulary {
+        &self.vocabulary
+    }
+
+    /// Reserve a session slot, or return `None` if all slots are taken.
+    /// The slot is released when the returned permit is dropped.
+    pub fn try_acquire(&self) -> Option<SessionPermit> {
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < self.max).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| SessionPermit {
+                active: Arc::clone(&self.active),
+            })
+    }
+
+    /// Number of sessions currently holding a permit.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Debug)]
+pub struct SessionPermit {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for SessionPermit {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,124 @@
+use april_asr_rs::AprilModel;
+use april_asr_server::{
+    Config, HttpServer, RtpIngest, SessionLimiter, SharedVocabulary, VoskServer, WebSocketServer,
+    WyomingServer,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn main() {
+    let config_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "april-asr-server.toml".to_owned());
+    let config = Config::load(&config_path)
+        .unwrap_or_else(|e| panic!("failed to load config from {}: {}", config_path, e));
+
+    let model = AprilModel::new(config.model.as_os_str().as_encoded_bytes())
+        .unwrap_or_else(|e| panic!("failed to load model {}: {}", config.model.display(), e));
+    let model = Arc::new(model);
+    // sessions are counted across all servers, since they all share the same machine
+    let mut limiter = SessionLimiter::new(config.limits.max_sessions);
+    if let Some(vocabulary) = &config.vocabulary {
+        let shared = SharedVocabulary::load_file(&vocabulary.path).unwrap_or_else(|e| {
+            panic!(
+                "failed to load vocabulary from {}: {}",
+                vocabulary.path.display(),
+                e
+            )
+        });
+        eprintln!(
+            "loaded {} vocabulary entries from {}",
+            shared.current().len(),
+            vocabulary.path.display()
+        );
+        if vocabulary.reload_interval_ms > 0 {
+            shared.watch(
+                vocabulary.path.clone(),
+                Duration::from_millis(vocabulary.reload_interval_ms),
+            );
+        }
+        limiter = limiter.with_vocabulary(shared);
+    }
+    let mut servers = Vec::new();
+
+    let websocket = WebSocketServer::bind(config.websocket.listen, model.clone(), limiter.clone())
+        .expect("failed to bind websocket listener");
+    eprintln!(
+        "listening for websocket connections on {}",
+        websocket
+            .local_addr()
+            .expect("failed to get listening address")
+    );
+    servers.push(std::thread::spawn(move || {
+        websocket.run().expect("websocket server failed")
+    }));
+
+    let http = HttpServer::bind(
+        config.http.listen,
+        model.clone(),
+        limiter.clone(),
+        config.limits.clone(),
+    )
+    .expect("failed to bind HTTP listener");
+    eprintln!(
+        "listening for HTTP requests on {}",
+        http.local_addr().expect("failed to get listening address")
+    );
+    servers.push(std::thread::spawn(move || {
+        http.run().expect("HTTP server failed")
+    }));
+
+    if let Some(vosk) = &config.vosk {
+        let vosk = VoskServer::bind(vosk.listen, model.clone(), limiter.clone())
+            .expect("failed to bind vosk listener");
+        eprintln!(
+            "listening for vosk connections on {}",
+            vosk.local_addr().expect("failed to get listening address")
+        );
+        servers.push(std::thread::spawn(move || {
+            vosk.run().expect("vosk server failed")
+        }));
+    }
+
+    if let Some(wyoming) = &config.wyoming {
+        let wyoming = WyomingServer::bind(
+            wyoming.listen,
+            model.clone(),
+            limiter.clone(),
+            config.limits.clone(),
+        )
+        .expect("failed to bind wyoming listener");
+        eprintln!(
+            "listening for wyoming connections on {}",
+            wyoming
+                .local_addr()
+                .expect("failed to get listening address")
+        );
+        servers.push(std::thread::spawn(move || {
+            wyoming.run().expect("wyoming server failed")
+        }));
+    }
+
+    if let Some(rtp) = &config.rtp {
+        let rtp = RtpIngest::bind(rtp.clone(), model.clone(), limiter.clone())
+            .expect("failed to bind RTP socket");
+        eprintln!(
+            "listening for RTP streams on {}",
+            rtp.local_addr().expect("failed to get listening address")
+        );
+        servers.push(std::thread::spawn(move || {
+            // RTP has no way to send results back, so they are written to stdout as JSON lines
+            rtp.run(|transcript| {
+                println!(
+                    "{}",
+                    serde_json::to_string(&transcript).expect("transcripts always serialize")
+                )
+            })
+            .expect("RTP ingest failed")
+        }));
+    }
+
+    for server in servers {
+        let _ = server.join();
+    }
+}
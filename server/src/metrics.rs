@@ -0,0 +1,295 @@
+//! Metrics of every session run by the servers, exported in the Prometheus text format by
+//! `GET /metrics` on the HTTP server.
+
+use april_asr_rs::{KeepUpStats, LatencyStats, MetricsHandle, SessionMetrics};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Totals of all sessions, finished or still running.
+///
+/// Cheap to clone: all clones share the same totals.
+#[derive(Debug, Clone, Default)]
+pub struct ServerMetrics {
+    registry: Arc<Mutex<Registry>>,
+    keep_up: KeepUpStats,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    sessions_total: u64,
+    finished: SessionMetrics,
+    running: Vec<MetricsHandle>,
+}
+
+impl ServerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a new session, and include its metrics in the totals from now on.
+    pub fn track(&self, session: MetricsHandle) {
+        let mut registry = self.lock();
+        // without scrapes, finished sessions would otherwise pile up here
+        registry.prune();
+        registry.sessions_total += 1;
+        registry.running.push(session);
+    }
+
+    /// Counters for sessions run with a [`april_asr_rs::KeepUpSession`] to count into, which
+    /// are exported with the other metrics. The servers' own sessions are synchronous, so they
+    /// never fall behind and don't count into them.
+    pub fn keep_up_stats(&self) -> KeepUpStats {
+        self.keep_up.clone()
+    }
+
+    /// Number of sessions started, and the sum of their metrics so far.
+    pub fn totals(&self) -> (u64, SessionMetrics) {
+        let mut registry = self.lock();
+        let mut totals = registry.prune();
+        totals += &registry.finished;
+        (registry.sessions_total, totals)
+    }
+
+    /// Render the totals in the Prometheus text exposition format.
+    pub fn render_prometheus(&self, active_sessions: usize) -> String {
+        let (sessions_total, totals) = self.totals();
+        let mut out = String::new();
+        metric(
+            &mut out,
+            "april_sessions_active",
+            "gauge",
+            "Sessions currently running.",
+            &[("", active_sessions as f64)],
+        );
+        metric(
+            &mut out,
+            "april_sessions_total",
+            "counter",
+            "Sessions started.",
+            &[("", sessions_total as f64)],
+        );
+        metric(
+            &mut out,
+            "april_audio_seconds_total",
+            "counter",
+            "Seconds of audio fed to sessions.",
+            &[("", totals.audio_seconds)],
+        );
+        metric(
+            &mut out,
+            "april_processing_seconds_total",
+            "counter",
+            "Wall time spent feeding and flushing sessions. Divide by audio seconds for the \
+             realtime factor.",
+            &[("", totals.processing_seconds)],
+        );
+        metric(
+            &mut out,
+            "april_results_total",
+            "counter",
+            "Recognition results emitted.",
+            &[
+                ("{type=\"partial\"}", totals.partial_results as f64),
+                ("{type=\"final\"}", totals.final_results as f64),
+            ],
+        );
+        metric(
+            &mut out,
+            "april_cant_keep_up_total",
+            "counter",
+            "Times april dropped audio because it could not keep up.",
+            &[("", totals.cant_keep_up as f64)],
+        );
+        let keep_up = self.keep_up.counts();
+        metric(
+            &mut out,
+            "april_keep_up_resyncs_total",
+            "counter",
+            "Sessions flushed to catch up after falling behind.",
+            &[("", keep_up.resyncs as f64)],
+        );
+        metric(
+            &mut out,
+            "april_keep_up_realtime_switches_total",
+            "counter",
+            "Sessions switched to realtime mode after falling behind.",
+            &[("", keep_up.switched_to_realtime as f64)],
+        );
+        metric(
+            &mut out,
+            "april_keep_up_backpressure_seconds_total",
+            "counter",
+            "Time audio producers were blocked to slow them down to realtime.",
+            &[("", keep_up.paced_seconds)],
+        );
+        metric(
+            &mut out,
+            "april_keep_up_failures_total",
+            "counter",
+            "Sessions failed because they fell behind.",
+            &[("", keep_up.failures as f64)],
+        );
+        summary(
+            &mut out,
+            "april_first_partial_latency_seconds",
+            "Time from feeding audio to the first result covering it.",
+            &totals.first_partial_latency,
+        );
+        summary(
+            &mut out,
+            "april_partial_to_final_latency_seconds",
+            "Time from the first partial result of a sentence to its final result.",
+            &totals.partial_to_final_latency,
+        );
+        out
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Registry {
+    /// Fold finished sessions into the totals, so that only running ones are kept, and
+    /// return the sum of the running ones.
+    fn prune(&mut self) -> SessionMetrics {
+        let mut running = SessionMetrics::default();
+        self.running.retain(|session| {
+            let active = session.is_active();
+            let metrics = session.snapshot();
+            if active {
+                running += &metrics;
+            } else {
+                self.finished += &metrics;
+            }
+            active
+        });
+        running
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, f64)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+fn summary(out: &mut String, name: &str, help: &str, latency: &LatencyStats) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} summary", name);
+    let _ = writeln!(out, "{}_sum {}", name, latency.total_seconds);
+    let _ = writeln!(out, "{}_count {}", name, latency.count);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The value of the sample `name`, which must be in `text` exactly once.
+    fn sample(text: &str, name: &str) -> f64 {
+        let values: Vec<f64> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix(name)?.strip_prefix(' ')?.parse().ok())
+            .collect();
+        assert_eq!(values.len(), 1, "{} in {}", name, text);
+        values[0]
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let text = ServerMetrics::new().render_prometheus(2);
+        // every sample follows the type of its metric, and is a number
+        let mut declared = "";
+        for line in text.lines() {
+            if let Some(kind) = line.strip_prefix("# TYPE ") {
+                declared = kind.split(' ').next().unwrap();
+            } else if !line.starts_with("# HELP april_") {
+                let (name, value) = line.rsplit_once(' ').unwrap();
+                assert!(name.starts_with(declared), "{} is not a {}", name, declared);
+                value.parse::<f64>().unwrap();
+            }
+        }
+        assert!(text.contains("# TYPE april_sessions_active gauge\n"));
+        assert!(text.contains("# TYPE april_first_partial_latency_seconds summary\n"));
+        assert_eq!(sample(&text, "april_sessions_active"), 2.0);
+        assert_eq!(sample(&text, "april_sessions_total"), 0.0);
+        assert_eq!(sample(&text, "april_results_total{type=\"partial\"}"), 0.0);
+        assert_eq!(
+            sample(&text, "april_first_partial_latency_seconds_count"),
+            0.0
+        );
+        assert_eq!(sample(&text, "april_keep_up_failures_total"), 0.0);
+    }
+
+    #[test]
+    fn exports_keep_up_counters() {
+        use april_asr_rs::{
+            AprilConfigFlags, AprilResultType, KeepUpPolicy, KeepUpSession, ScriptedRecognizer,
+            Trigger,
+        };
+
+        let recognizer = ScriptedRecognizer::new(16000).then(
+            Trigger::Audio { ms: 100 },
+            AprilResultType::ErrorCantKeepUp,
+            "",
+        );
+        let metrics = ServerMetrics::new();
+        let mut session = KeepUpSession::new(
+            &recognizer,
+            AprilConfigFlags::ASYNC_NO_RT,
+            KeepUpPolicy::DropAndResync,
+            Box::new(|_, _| {}),
+            metrics.keep_up_stats(),
+        )
+        .unwrap();
+        session.feed_pcm16(&mut [0; 1600]).unwrap();
+        session.feed_pcm16(&mut [0; 1600]).unwrap();
+
+        let text = metrics.render_prometheus(0);
+        assert_eq!(sample(&text, "april_keep_up_resyncs_total"), 1.0);
+        assert_eq!(sample(&text, "april_keep_up_realtime_switches_total"), 0.0);
+    }
+
+    #[cfg(feature = "stub")]
+    #[test]
+    fn totals_running_and_finished_sessions() {
+        use crate::transcript::create_session;
+        use crate::SharedVocabulary;
+        use april_asr_rs::AprilModel;
+
+        // the stub loads any readable file as a model, and hears a word in every 500ms of sound
+        let model = AprilModel::new(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).unwrap();
+        let metrics = ServerMetrics::new();
+        let vocabulary = SharedVocabulary::default();
+        for _ in 0..3 {
+            let (mut session, _results) = create_session(&model, &vocabulary).unwrap();
+            metrics.track(session.metrics_handle());
+            session.feed_pcm16(&mut [1000; 16000]);
+            session.flush();
+        }
+        // the last session is still running
+        let (mut session, _results) = create_session(&model, &vocabulary).unwrap();
+        metrics.track(session.metrics_handle());
+        session.feed_pcm16(&mut [1000; 8000]);
+        assert_eq!(metrics.lock().running.len(), 1);
+
+        let text = metrics.render_prometheus(1);
+        assert_eq!(sample(&text, "april_sessions_total"), 4.0);
+        assert_eq!(sample(&text, "april_audio_seconds_total"), 3.5);
+        assert_eq!(sample(&text, "april_results_total{type=\"partial\"}"), 7.0);
+        assert_eq!(sample(&text, "april_results_total{type=\"final\"}"), 3.0);
+        // one per chunk fed, as the flush found every chunk covered already
+        assert_eq!(
+            sample(&text, "april_first_partial_latency_seconds_count"),
+            4.0
+        );
+        assert_eq!(
+            sample(&text, "april_partial_to_final_latency_seconds_count"),
+            3.0
+        );
+        drop(session);
+        assert_eq!(metrics.totals().1.audio_seconds, 3.5);
+    }
+}
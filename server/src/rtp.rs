@@ -0,0 +1,635 @@
+//! Transcription of RTP audio streams received over UDP, as forwarded by telephony gear.
+//!
+//! Every SSRC of every source address gets its own session. Packets go through a small jitter
+//! buffer that puts them back in sequence order and fills lost packets with silence, then the
+//! G.711 payload is decoded, resampled to the model's sample rate and fed to the session. Streams
+//! that stop sending packets for [`RtpConfig::idle_timeout_ms`] are flushed and closed.
+
+use crate::config::RtpConfig;
+use crate::error::Result;
+use crate::limits::{SessionLimiter, SessionPermit};
+use crate::transcript::{self, Event, EventSink};
+use april_asr_rs::codec::{self, Resampler};
+use april_asr_rs::{AprilModel, AprilSession};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Static RTP payload type for G.711 µ-law (PCMU)
+pub const PAYLOAD_TYPE_PCMU: u8 = 0;
+/// Static RTP payload type for G.711 A-law (PCMA)
+pub const PAYLOAD_TYPE_PCMA: u8 = 8;
+/// Both G.711 variants are sampled at 8kHz
+const G711_SAMPLE_RATE: usize = 8000;
+
+/// A parsed RTP packet, borrowing its payload from the received datagram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtpPacket<'a> {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub payload: &'a [u8],
+}
+
+impl<'a> RtpPacket<'a> {
+    /// Parse an RTP packet as described in RFC 3550, skipping any CSRCs, header extension and padding.
+    pub fn parse(datagram: &'a [u8]) -> std::result::Result<Self, &'static str> {
+        if datagram.len() < 12 {
+            return Err("packet shorter than an RTP header");
+        }
+        if datagram[0] >> 6 != 2 {
+            return Err("not an RTP version 2 packet");
+        }
+        let padding = datagram[0] & 0x20 != 0;
+        let extension = datagram[0] & 0x10 != 0;
+        let csrc_count = (datagram[0] & 0x0f) as usize;
+
+        let mut start = 12 + 4 * csrc_count;
+        if extension {
+            let header = datagram
+                .get(start..start + 4)
+                .ok_or("truncated RTP header extension")?;
+            start += 4 + 4 * u16::from_be_bytes([header[2], header[3]]) as usize;
+        }
+        let mut end = datagram.len();
+        if padding {
+            let padding_len = *datagram.last().expect("length checked above") as usize;
+            end = end
+                .checked_sub(padding_len)
+                .ok_or("RTP padding longer than the packet")?;
+        }
+        let payload = datagram
+            .get(start..end)
+            .ok_or("RTP header longer than the packet")?;
+
+        Ok(Self {
+            marker: datagram[1] & 0x80 != 0,
+            payload_type: datagram[1] & 0x7f,
+            sequence: u16::from_be_bytes([datagram[2], datagram[3]]),
+            timestamp: u32::from_be_bytes([datagram[4], datagram[5], datagram[6], datagram[7]]),
+            ssrc: u32::from_be_bytes([datagram[8], datagram[9], datagram[10], datagram[11]]),
+            payload,
+        })
+    }
+}
+
+/// Reorders packets of a single stream and detects losses.
+///
+/// Packets are held back until `depth` of them are buffered, giving late packets a chance to
+/// arrive. Packets arriving after their slot was already released are dropped.
+///
+/// Lost audio is measured with RTP timestamps, assuming one byte of payload per sample as with G.711.
+/// Gaps longer than [`JitterBuffer::MAX_GAP`] are taken as the sender resetting its timestamps,
+/// and are not filled in.
+#[derive(Debug)]
+pub struct JitterBuffer {
+    depth: usize,
+    /// Packets waiting to be released, by extended sequence number
+    pending: BTreeMap<u64, BufferedPacket>,
+    /// Extended sequence number of the next packet to release
+    next: Option<u64>,
+    /// Highest extended sequence number seen, used to extend 16-bit sequence numbers
+    highest: Option<u64>,
+    /// RTP timestamp right after the last released packet
+    next_timestamp: Option<u32>,
+    pub stats: JitterStats,
+}
+
+#[derive(Debug, Clone)]
+struct BufferedPacket {
+    timestamp: u32,
+    payload_type: u8,
+    payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct JitterStats {
+    pub received: u64,
+    /// Packets that arrived out of order, but in time to be put back in place
+    pub reordered: u64,
+    /// Packets that arrived after their slot was released, or twice
+    pub dropped: u64,
+    /// Packets that never arrived
+    pub lost: u64,
+}
+
+/// A chunk of audio released by the [`JitterBuffer`], in sequence order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JitterOutput {
+    Packet {
+        payload_type: u8,
+        payload: Vec<u8>,
+    },
+    /// Packets were lost; stands in for this many samples of audio
+    Gap {
+        samples: u32,
+    },
+}
+
+impl JitterBuffer {
+    /// Longest gap filled with silence, one second of G.711.
+    pub const MAX_GAP: u32 = G711_SAMPLE_RATE as u32;
+
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth: depth.max(1),
+            pending: BTreeMap::new(),
+            next: None,
+            highest: None,
+            next_timestamp: None,
+            stats: JitterStats::default(),
+        }
+    }
+
+    /// Map a 16-bit sequence number onto the 64-bit sequence closest to the highest one seen.
+    fn extend(&mut self, sequence: u16) -> u64 {
+        let Some(highest) = self.highest else {
+            // leave room below the first packet for ones that were reordered before it
+            let extended = (1 << 16) + sequence as u64;
+            self.highest = Some(extended);
+            return extended;
+        };
+        let delta = sequence.wrapping_sub(highest as u16) as i16 as i64;
+        let extended = (highest as i64 + delta) as u64;
+        self.highest = Some(highest.max(extended));
+        extended
+    }
+
+    pub fn push(&mut self, packet: &RtpPacket) {
+        self.stats.received += 1;
+        let sequence = self.extend(packet.sequence);
+        if self.next.is_some_and(|next| sequence < next) || self.pending.contains_key(&sequence) {
+            self.stats.dropped += 1;
+            return;
+        }
+        if self.highest.is_some_and(|highest| sequence < highest) {
+            self.stats.reordered += 1;
+        }
+        self.pending.insert(
+            sequence,
+            BufferedPacket {
+                timestamp: packet.timestamp,
+                payload_type: packet.payload_type,
+                payload: packet.payload.to_vec(),
+            },
+        );
+    }
+
+    /// Release packets that are ready, in order.
+    /// With `drain` set, releases everything still buffered, such as when the stream ends.
+    pub fn pop(&mut self, drain: bool) -> Option<JitterOutput> {
+        let (&sequence, _) = self.pending.first_key_value()?;
+        let in_order = self.next == Some(sequence);
+        if !in_order && !drain && self.pending.len() < self.depth {
+            // the next packet may still arrive; at the start of a stream, an earlier one might
+            return None;
+        }
+        let next = *self.next.get_or_insert(sequence);
+
+        let packet = self.pending.get(&sequence).expect("key taken from the map");
+        if sequence != next {
+            // give up on the missing packets, and stand in for them with silence
+            let expected = self.next_timestamp.unwrap_or(packet.timestamp);
+            self.stats.lost += sequence - next;
+            self.next = Some(sequence);
+            let samples = packet.timestamp.wrapping_sub(expected);
+            // a longer gap is more likely a timestamp jump than a real loss, so resync instead
+            if samples > 0 && samples <= Self::MAX_GAP {
+                self.next_timestamp = Some(packet.timestamp);
+                return Some(JitterOutput::Gap { samples });
+            }
+        }
+
+        let packet = self
+            .pending
+            .remove(&sequence)
+            .expect("key taken from the map");
+        self.next = Some(sequence + 1);
+        self.next_timestamp = Some(packet.timestamp.wrapping_add(packet.payload.len() as u32));
+        Some(JitterOutput::Packet {
+            payload_type: packet.payload_type,
+            payload: packet.payload,
+        })
+    }
+}
+
+/// A result from one RTP stream.
+#[derive(Debug, Clone, Serialize)]
+pub struct RtpTranscript {
+    pub ssrc: u32,
+    pub source: SocketAddr,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+struct RtpStream<'m> {
+    _permit: SessionPermit,
+    source: SocketAddr,
+    jitter: JitterBuffer,
+    resampler: Resampler,
+    session: AprilSession<'m, EventSink>,
+    results: Receiver<Event>,
+    last_packet: Instant,
+}
+
+impl RtpStream<'_> {
+    /// Decode and feed everything the jitter buffer is ready to release.
+    fn feed(&mut self, drain: bool) {
+        let mut samples = Vec::new();
+        while let Some(output) = self.jitter.pop(drain) {
+            let decoded = match output {
+                JitterOutput::Packet {
+                    payload_type: PAYLOAD_TYPE_PCMU,
+                    payload,
+                } => codec::decode_ulaw(&payload),
+                JitterOutput::Packet {
+                    payload_type: PAYLOAD_TYPE_PCMA,
+                    payload,
+                } => codec::decode_alaw(&payload),
+                // unsupported codecs are filtered out before they reach the jitter buffer
+                JitterOutput::Packet { .. } => continue,
+                JitterOutput::Gap { samples } => vec![0; samples as usize],
+            };
+            self.resampler.process_into(&decoded, &mut samples);
+        }
+        self.session.feed_pcm16(&mut samples);
+    }
+}
+
+/// Streams turned away, so that each is only reported once while it keeps sending. Like idle
+/// streams, they are forgotten once they stop sending, and as anyone can send packets with made
+/// up SSRCs, at most [`RefusedStreams::CAPACITY`] are remembered.
+#[derive(Default)]
+struct RefusedStreams {
+    streams: HashMap<(SocketAddr, u32), Refusal>,
+}
+
+struct Refusal {
+    last_packet: Instant,
+    /// Whether creating its session failed, which is not retried until the stream is forgotten.
+    /// Streams refused for lack of a session slot get one as soon as a slot is free.
+    session_failed: bool,
+}
+
+impl RefusedStreams {
+    const CAPACITY: usize = 1024;
+
+    /// Record a packet of a refused stream, returning whether the stream is new and should be
+    /// reported. Once full, new streams are neither remembered nor reported.
+    fn refuse(&mut self, key: (SocketAddr, u32), session_failed: bool, now: Instant) -> bool {
+        if let Some(refusal) = self.streams.get_mut(&key) {
+            refusal.last_packet = now;
+            refusal.session_failed |= session_failed;
+            return false;
+        }
+        if self.streams.len() >= Self::CAPACITY {
+            return false;
+        }
+        self.streams.insert(
+            key,
+            Refusal {
+                last_packet: now,
+                session_failed,
+            },
+        );
+        true
+    }
+
+    fn session_failed(&self, key: &(SocketAddr, u32)) -> bool {
+        self.streams
+            .get(key)
+            .is_some_and(|refusal| refusal.session_failed)
+    }
+
+    fn remove(&mut self, key: &(SocketAddr, u32)) {
+        self.streams.remove(key);
+    }
+
+    /// Forget streams that sent nothing for `idle_timeout`.
+    fn expire(&mut self, now: Instant, idle_timeout: Duration) {
+        self.streams
+            .retain(|_, refusal| now.duration_since(refusal.last_packet) < idle_timeout);
+    }
+}
+
+pub struct RtpIngest {
+    socket: UdpSocket,
+    model: Arc<AprilModel>,
+    limiter: SessionLimiter,
+    config: RtpConfig,
+}
+
+impl RtpIngest {
+    pub fn bind(
+        config: RtpConfig,
+        model: Arc<AprilModel>,
+        limiter: SessionLimiter,
+    ) -> Result<Self> {
+        let socket = UdpSocket::bind(config.listen)?;
+        // wake up regularly even when nothing arrives, to close idle streams
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+        Ok(Self {
+            socket,
+            model,
+            limiter,
+            config,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Receive packets forever, passing every result to `emit`.
+    pub fn run(self, mut emit: impl FnMut(RtpTranscript)) -> Result<()> {
+        let idle_timeout = Duration::from_millis(self.config.idle_timeout_ms);
+        let model_rate = self.model.get_sample_rate();
+        // SSRCs are only unique per sender, so two senders picking the same one stay apart
+        let mut streams: HashMap<(SocketAddr, u32), RtpStream> = HashMap::new();
+        let mut refused = RefusedStreams::default();
+        let mut buf = [0; 65536];
+
+        loop {
+            let received = match self.socket.recv_from(&mut buf) {
+                Ok(received) => Some(received),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    None
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            if let Some((len, source)) = received {
+                let packet = match RtpPacket::parse(&buf[..len]) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        eprintln!("dropping invalid RTP packet from {}: {}", source, e);
+                        continue;
+                    }
+                };
+                if !matches!(packet.payload_type, PAYLOAD_TYPE_PCMU | PAYLOAD_TYPE_PCMA) {
+                    continue;
+                }
+
+                let key = (source, packet.ssrc);
+                let stream = match streams.entry(key) {
+                    std::collections::hash_map::Entry::Occupied(stream) => stream.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(slot) => {
+                        if refused.session_failed(&key) {
+                            refused.refuse(key, true, Instant::now());
+                            continue;
+                        }
+                        let Some(permit) = self.limiter.try_acquire() else {
+                            if refused.refuse(key, false, Instant::now()) {
+                                eprintln!(
+                                    "ignoring RTP stream {:#010x} from {}: too many active sessions",
+                                    packet.ssrc, source
+                                );
+                            }
+                            continue;
+                        };
+                        // the other streams keep going without this one
+                        let (session, results) = match transcript::create_session(
+                            &self.model,
+                            self.limiter.vocabulary(),
+                        ) {
+                            Ok(created) => created,
+                            Err(e) => {
+                                if refused.refuse(key, true, Instant::now()) {
+                                    eprintln!(
+                                        "ignoring RTP stream {:#010x} from {}: {}",
+                                        packet.ssrc, source, e
+                                    );
+                                }
+                                continue;
+                            }
+                        };
+                        refused.remove(&key);
+                        self.limiter.metrics().track(session.metrics_handle());
+                        slot.insert(RtpStream {
+                            _permit: permit,
+                            source,
+                            jitter: JitterBuffer::new(self.config.jitter_packets),
+                            resampler: Resampler::new(G711_SAMPLE_RATE, model_rate),
+                            session,
+                            results,
+                            last_packet: Instant::now(),
+                        })
+                    }
+                };
+                stream.last_packet = Instant::now();
+                stream.jitter.push(&packet);
+                stream.feed(false);
+                emit_results(packet.ssrc, stream, &mut emit);
+            }
+
+            let now = Instant::now();
+            refused.expire(now, idle_timeout);
+            streams.retain(|(_, ssrc), stream| {
+                if now.duration_since(stream.last_packet) < idle_timeout {
+                    return true;
+                }
+                stream.feed(true);
+                stream.session.flush();
+                emit_results(*ssrc, stream, &mut emit);
+                eprintln!(
+                    "RTP stream {:#010x} from {} went idle: {:?}",
+                    ssrc, stream.source, stream.jitter.stats
+                );
+                false
+            });
+        }
+    }
+}
+
+fn emit_results(ssrc: u32, stream: &RtpStream, emit: &mut impl FnMut(RtpTranscript)) {
+    for event in stream.results.try_iter() {
+        emit(RtpTranscript {
+            ssrc,
+            source: stream.source,
+            event,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An RTP header with the given first byte, and the rest of the packet after it.
+    fn packet(first: u8, sequence: u16, timestamp: u32, rest: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![first, 0x80 | PAYLOAD_TYPE_PCMA];
+        bytes.extend(sequence.to_be_bytes());
+        bytes.extend(timestamp.to_be_bytes());
+        bytes.extend(0x1234_5678u32.to_be_bytes());
+        bytes.extend(rest);
+        bytes
+    }
+
+    #[test]
+    fn parses_headers() {
+        let plain = packet(0x80, 7, 160, &[1, 2, 3]);
+        assert_eq!(
+            RtpPacket::parse(&plain).unwrap(),
+            RtpPacket {
+                marker: true,
+                payload_type: PAYLOAD_TYPE_PCMA,
+                sequence: 7,
+                timestamp: 160,
+                ssrc: 0x1234_5678,
+                payload: &[1, 2, 3],
+            }
+        );
+
+        // two CSRCs, an extension of one word and two bytes of padding
+        let mut rest = vec![0xaa; 8];
+        rest.extend([0xbe, 0xde, 0, 1, 0xcc, 0xcc, 0xcc, 0xcc]);
+        rest.extend([1, 2, 3, 0, 2]);
+        let full = packet(0x80 | 0x20 | 0x10 | 2, 7, 160, &rest);
+        assert_eq!(RtpPacket::parse(&full).unwrap().payload, &[1, 2, 3]);
+
+        assert_eq!(
+            RtpPacket::parse(&plain[..11]).unwrap_err(),
+            "packet shorter than an RTP header"
+        );
+        assert_eq!(
+            RtpPacket::parse(&packet(0x40, 7, 160, &[])).unwrap_err(),
+            "not an RTP version 2 packet"
+        );
+        assert_eq!(
+            RtpPacket::parse(&packet(0x90, 7, 160, &[0xbe, 0xde])).unwrap_err(),
+            "truncated RTP header extension"
+        );
+        assert_eq!(
+            RtpPacket::parse(&packet(
+                0x80 | 0x10,
+                7,
+                160,
+                &[0xbe, 0xde, 0, 2, 0, 0, 0, 0]
+            ))
+            .unwrap_err(),
+            "RTP header longer than the packet"
+        );
+        assert_eq!(
+            RtpPacket::parse(&packet(0xa0, 7, 160, &[1, 20])).unwrap_err(),
+            "RTP padding longer than the packet"
+        );
+    }
+
+    /// Push packets of 160 samples, numbered from `sequence`, and pop everything that's ready.
+    fn run(jitter: &mut JitterBuffer, packets: &[(u16, u32)], drain: bool) -> Vec<JitterOutput> {
+        for &(sequence, timestamp) in packets {
+            let bytes = packet(0x80, sequence, timestamp, &[sequence as u8; 160]);
+            jitter.push(&RtpPacket::parse(&bytes).unwrap());
+        }
+        std::iter::from_fn(|| jitter.pop(drain)).collect()
+    }
+
+    fn sequences(outputs: &[JitterOutput]) -> Vec<Option<u8>> {
+        outputs
+            .iter()
+            .map(|output| match output {
+                JitterOutput::Packet { payload, .. } => Some(payload[0]),
+                JitterOutput::Gap { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn jitter_buffer_reorders() {
+        let mut jitter = JitterBuffer::new(3);
+        // held back until the buffer is full, as an earlier packet might still arrive
+        assert!(run(&mut jitter, &[(11, 160), (10, 0)], false).is_empty());
+        let out = run(&mut jitter, &[(13, 480)], false);
+        assert_eq!(sequences(&out), [Some(10), Some(11)]);
+        let out = run(&mut jitter, &[(12, 320)], false);
+        assert_eq!(sequences(&out), [Some(12), Some(13)]);
+        // too late, its slot was released already
+        assert!(run(&mut jitter, &[(11, 160)], false).is_empty());
+        assert_eq!(
+            jitter.stats,
+            JitterStats {
+                received: 5,
+                reordered: 2,
+                dropped: 1,
+                lost: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn jitter_buffer_fills_losses() {
+        let mut jitter = JitterBuffer::new(2);
+        let out = run(&mut jitter, &[(1, 0), (2, 160), (5, 640), (6, 800)], false);
+        assert_eq!(sequences(&out), [Some(1), Some(2), None, Some(5), Some(6)]);
+        assert_eq!(out[2], JitterOutput::Gap { samples: 320 });
+        assert_eq!(jitter.stats.lost, 2);
+
+        // a timestamp jumping further than a second is a resync, not a second of loss
+        let out = run(&mut jitter, &[(8, 5_000_000), (9, 5_000_160)], false);
+        assert_eq!(sequences(&out), [Some(8), Some(9)]);
+        assert_eq!(jitter.stats.lost, 3);
+        // up to a second is filled in
+        let out = run(
+            &mut jitter,
+            &[(11, 5_000_320 + JitterBuffer::MAX_GAP)],
+            true,
+        );
+        assert_eq!(sequences(&out), [None, Some(11)]);
+        assert_eq!(
+            out[0],
+            JitterOutput::Gap {
+                samples: JitterBuffer::MAX_GAP
+            }
+        );
+    }
+
+    #[test]
+    fn jitter_buffer_wraps_sequence_numbers() {
+        let mut jitter = JitterBuffer::new(2);
+        let packets = [(65534, 0), (0, 320), (65535, 160), (1, 480)];
+        let out = run(&mut jitter, &packets, true);
+        assert_eq!(sequences(&out), [Some(254), Some(255), Some(0), Some(1)]);
+        assert_eq!(jitter.stats.lost, 0);
+        assert_eq!(jitter.stats.reordered, 1);
+    }
+
+    #[test]
+    fn refused_streams_are_reported_once_and_forgotten() {
+        let mut refused = RefusedStreams::default();
+        let start = Instant::now();
+        let source: SocketAddr = "127.0.0.1:5004".parse().unwrap();
+        assert!(refused.refuse((source, 1), false, start));
+        assert!(!refused.refuse((source, 1), false, start));
+        assert!(!refused.session_failed(&(source, 1)));
+        assert!(refused.refuse((source, 2), true, start));
+        assert!(refused.session_failed(&(source, 2)));
+
+        // stream 1 keeps sending, stream 2 went quiet
+        let later = start + Duration::from_secs(2);
+        refused.refuse((source, 1), false, later);
+        refused.expire(later, Duration::from_secs(1));
+        assert!(!refused.refuse((source, 1), false, later));
+        assert!(!refused.session_failed(&(source, 2)));
+        assert!(refused.refuse((source, 2), false, later));
+
+        // made up SSRCs can't grow it without bound
+        for ssrc in 3.. {
+            if !refused.refuse((source, ssrc), false, later) {
+                break;
+            }
+        }
+        assert_eq!(refused.streams.len(), RefusedStreams::CAPACITY);
+        refused.remove(&(source, 1));
+        assert!(refused.refuse((source, u32::MAX), false, later));
+    }
+}
@@ -0,0 +1,213 @@
+use crate::error::Result;
+use crate::vocabulary::SharedVocabulary;
+use april_asr_rs::{
+    AprilConfig, AprilModel, AprilResultType, AprilSession, AprilTokenFlags, AprilTokens,
+    AprilWord, Vocabulary,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// An owned copy of an april result, as sent back to clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Partial {
+        text: String,
+        words: Vec<Word>,
+        #[serde(default)]
+        tokens: Vec<Token>,
+    },
+    /// `text` and `words` have the vocabulary applied, while `tokens` are as recognized.
+    Final {
+        text: String,
+        words: Vec<Word>,
+        #[serde(default)]
+        tokens: Vec<Token>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        replacements: Vec<Replacement>,
+    },
+    Silence,
+    CantKeepUp,
+}
+
+impl Event {
+    /// Convert a result passed to the session callback. Result types the server does not
+    /// know how to forward return `None`.
+    pub fn from_result(result: AprilResultType, tokens: AprilTokens) -> Option<Self> {
+        Self::from_result_replacing(result, tokens, &Vocabulary::new())
+    }
+
+    /// Like [`Event::from_result`], but replaces `vocabulary` in final results.
+    pub fn from_result_replacing(
+        result: AprilResultType,
+        tokens: AprilTokens,
+        vocabulary: &Vocabulary,
+    ) -> Option<Self> {
+        if result == AprilResultType::RecognitionFinal && !vocabulary.is_empty() {
+            let replaced = vocabulary.apply(&tokens);
+            let mut text = tokens.to_string();
+            if !replaced.replacements.is_empty() {
+                // keep the space april puts before every word, so texts can be concatenated
+                let leading = text.len() - text.trim_start().len();
+                text.truncate(leading);
+                text.push_str(&replaced.to_string());
+            }
+            return Some(Self::Final {
+                text,
+                words: replaced.words.into_iter().map(Word::from).collect(),
+                tokens: tokens.0.iter().map(Token::from).collect(),
+                replacements: replaced
+                    .replacements
+                    .into_iter()
+                    .map(Replacement::from)
+                    .collect(),
+            });
+        }
+
+        let text = tokens.to_string();
+        let words = tokens.words().into_iter().map(Word::from).collect();
+        let tokens = tokens.0.iter().map(Token::from).collect();
+        match result {
+            AprilResultType::RecognitionPartial => Some(Self::Partial {
+                text,
+                words,
+                tokens,
+            }),
+            AprilResultType::RecognitionFinal => Some(Self::Final {
+                text,
+                words,
+                tokens,
+                replacements: Vec::new(),
+            }),
+            AprilResultType::Silence => Some(Self::Silence),
+            AprilResultType::ErrorCantKeepUp => Some(Self::CantKeepUp),
+            AprilResultType::Unknown | AprilResultType::Other(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub text: String,
+    pub logprob: f32,
+    pub time_ms: usize,
+    pub word_boundary: bool,
+    pub sentence_end: bool,
+}
+
+impl From<&april_asr_rs::AprilToken<'_>> for Token {
+    fn from(token: &april_asr_rs::AprilToken<'_>) -> Self {
+        Self {
+            text: token.token.to_string(),
+            logprob: token.logprob,
+            time_ms: token.time_ms,
+            word_boundary: token.flag_bits.contains(AprilTokenFlags::WORD_BOUNDARY),
+            sentence_end: token.flag_bits.contains(AprilTokenFlags::SENTENCE_END),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Word {
+    pub word: String,
+    pub start_ms: usize,
+    pub end_ms: usize,
+    pub logprob: f32,
+}
+
+impl From<AprilWord> for Word {
+    fn from(word: AprilWord) -> Self {
+        Self {
+            word: word.text,
+            start_ms: word.start_ms,
+            end_ms: word.end_ms,
+            logprob: word.logprob,
+        }
+    }
+}
+
+/// A vocabulary replacement in a final result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replacement {
+    /// Index of the replacing word in the words of the result
+    pub word: usize,
+    /// The replaced words, as recognized
+    pub original: Vec<Word>,
+}
+
+impl From<april_asr_rs::Replacement> for Replacement {
+    fn from(replacement: april_asr_rs::Replacement) -> Self {
+        Self {
+            word: replacement.word,
+            original: replacement.original.into_iter().map(Word::from).collect(),
+        }
+    }
+}
+
+/// The final transcript of a whole recording.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Transcript {
+    pub text: String,
+    pub words: Vec<Word>,
+}
+
+impl Transcript {
+    /// Join the final results among `events`, ignoring everything else.
+    pub fn from_events(events: impl IntoIterator<Item = Event>) -> Self {
+        let mut transcript = Self::default();
+        for event in events {
+            if let Event::Final { text, words, .. } = event {
+                transcript.text.push_str(&text);
+                transcript.words.extend(words);
+            }
+        }
+        transcript.text = transcript.text.trim().to_owned();
+        transcript
+    }
+}
+
+/// Run a whole recording through a new session and collect its final results.
+pub fn transcribe(
+    model: &AprilModel,
+    vocabulary: &SharedVocabulary,
+    samples: &mut [i16],
+) -> Result<Transcript> {
+    let (mut session, results) = create_session(model, vocabulary)?;
+    session.feed_pcm16(samples);
+    session.flush();
+    Ok(Transcript::from_events(results.try_iter()))
+}
+
+/// Where a session created by [`create_session`] sends its results.
+#[derive(Debug)]
+pub struct EventSink {
+    tx: Sender<Event>,
+    vocabulary: SharedVocabulary,
+}
+
+/// Create a synchronous session whose results are sent to the returned receiver, with
+/// `vocabulary` applied to final results.
+///
+/// Since the session is synchronous, all results for a chunk of audio are in the receiver
+/// by the time [`AprilSession::feed_pcm16`] or [`AprilSession::flush`] returns.
+pub fn create_session<'m>(
+    model: &'m AprilModel,
+    vocabulary: &SharedVocabulary,
+) -> Result<(AprilSession<'m, EventSink>, Receiver<Event>)> {
+    let (tx, rx) = mpsc::channel();
+    let mut config = AprilConfig::default();
+    config.set_handler_fn(
+        |sink: &EventSink, result, tokens| {
+            let vocabulary = sink.vocabulary.current();
+            if let Some(event) = Event::from_result_replacing(result, tokens, &vocabulary) {
+                // the receiving end only goes away together with the session
+                let _ = sink.tx.send(event);
+            }
+        },
+        EventSink {
+            tx,
+            vocabulary: vocabulary.clone(),
+        },
+    );
+    Ok((model.create_session(config)?, rx))
+}
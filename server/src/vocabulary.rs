@@ -0,0 +1,178 @@
+//! Vocabulary files, replacing phrases in final results, and reloading them while the server
+//! runs.
+//!
+//! A vocabulary file is TOML, or JSON if its name ends in `.json`, with one entry per phrase:
+//!
+//! ```toml
+//! [[replace]]
+//! from = "cube control"
+//! to = "kubectl"
+//!
+//! [[replace]]
+//! from = "kubernetes"
+//! to = "Kubernetes"
+//! # "exact" by default, or "fuzzy" or "phonetic"
+//! match = "phonetic"
+//! # true by default
+//! preserve_case = false
+//! ```
+use crate::error::{Error, Result};
+use april_asr_rs::{MatchMode, Vocabulary, VocabularyEntry};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VocabularyFile {
+    #[serde(default)]
+    pub replace: Vec<ReplaceEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplaceEntry {
+    /// The phrase as the model recognizes it.
+    pub from: String,
+    /// What to write instead.
+    pub to: String,
+    #[serde(default, rename = "match")]
+    pub mode: MatchKind,
+    #[serde(default = "ReplaceEntry::default_preserve_case")]
+    pub preserve_case: bool,
+}
+
+impl ReplaceEntry {
+    fn default_preserve_case() -> bool {
+        true
+    }
+}
+
+/// How [`ReplaceEntry::from`] is matched, see [`MatchMode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    #[default]
+    Exact,
+    Fuzzy,
+    Phonetic,
+}
+
+impl From<MatchKind> for MatchMode {
+    fn from(kind: MatchKind) -> Self {
+        match kind {
+            MatchKind::Exact => MatchMode::Exact,
+            MatchKind::Fuzzy => MatchMode::Fuzzy,
+            MatchKind::Phonetic => MatchMode::Phonetic,
+        }
+    }
+}
+
+impl VocabularyFile {
+    /// Load a vocabulary file, as JSON if its name ends in `.json` and as TOML otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let parsed = if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&contents)
+        } else {
+            Self::from_toml(&contents)
+        };
+        parsed.map_err(|reason| Error::Vocabulary {
+            path: path.to_owned(),
+            reason,
+        })
+    }
+
+    pub fn from_toml(contents: &str) -> std::result::Result<Self, String> {
+        toml::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    pub fn from_json(contents: &str) -> std::result::Result<Self, String> {
+        serde_json::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    pub fn to_vocabulary(&self) -> Vocabulary {
+        let mut vocabulary = Vocabulary::new();
+        for entry in &self.replace {
+            vocabulary.add(VocabularyEntry {
+                preserve_case: entry.preserve_case,
+                ..VocabularyEntry::new(&entry.from, &entry.to, entry.mode.into())
+            });
+        }
+        vocabulary
+    }
+}
+
+/// The vocabulary every session applies to its final results, which can be replaced while
+/// sessions run. Sessions pick up a new vocabulary with their next final result.
+///
+/// Cheap to clone: all clones share the same vocabulary.
+#[derive(Debug, Clone, Default)]
+pub struct SharedVocabulary {
+    current: Arc<RwLock<Arc<Vocabulary>>>,
+}
+
+impl SharedVocabulary {
+    pub fn new(vocabulary: Vocabulary) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(vocabulary))),
+        }
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(VocabularyFile::load(path)?.to_vocabulary()))
+    }
+
+    pub fn current(&self) -> Arc<Vocabulary> {
+        Arc::clone(&self.current.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn replace(&self, vocabulary: Vocabulary) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(vocabulary);
+    }
+
+    /// Reload the vocabulary from `path` whenever the file changes, checking every `interval`.
+    /// If the file can't be loaded, the error is printed and the previous vocabulary is kept.
+    ///
+    /// The thread stops once every clone of this vocabulary is dropped.
+    pub fn watch(
+        &self,
+        path: impl Into<PathBuf>,
+        interval: Duration,
+    ) -> std::thread::JoinHandle<()> {
+        let path = path.into();
+        let current: Weak<_> = Arc::downgrade(&self.current);
+        let mut loaded = modified(&path);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            let Some(current) = current.upgrade() else {
+                return;
+            };
+            let changed = modified(&path);
+            if changed == loaded {
+                continue;
+            }
+            loaded = changed;
+            match VocabularyFile::load(&path) {
+                Ok(file) => {
+                    let vocabulary = file.to_vocabulary();
+                    eprintln!(
+                        "reloaded {} vocabulary entries from {}",
+                        vocabulary.len(),
+                        path.display()
+                    );
+                    SharedVocabulary { current }.replace(vocabulary);
+                }
+                Err(e) => eprintln!("keeping the previous vocabulary: {}", e),
+            }
+        })
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
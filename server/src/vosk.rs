@@ -0,0 +1,340 @@
+//! Adapter for the WebSocket protocol spoken by `vosk-server`, so existing Vosk clients can
+//! switch to april models unchanged.
+//!
+//! A client optionally sends `{"config": {"sample_rate": ..., "words": ...}}`, then binary frames
+//! of little-endian pcm16 audio, and finally `{"eof" : 1}`. The server answers every audio frame
+//! with exactly one message: `{"partial": ...}` while a sentence is still being recognized, or
+//! `{"text": ..., "result": [...]}` once april finalizes it. `{"eof" : 1}` is answered with the
+//! final result of whatever audio is left. `{"reset" : 1}` drops that audio instead, and is
+//! answered with an empty result.
+//!
+//! [`VoskAdapter`] only translates between the protocol and [`Event`]s, it does not own a session:
+//! the caller performs the [`SessionAction`] returned for each message and passes the resulting
+//! events to [`VoskAdapter::reply`]. [`VoskServer`] wires it up to a real session.
+
+use crate::error::Result;
+use crate::limits::SessionLimiter;
+use crate::transcript::{self, Event, Word};
+use crate::websocket;
+use april_asr_rs::codec::wav;
+use april_asr_rs::AprilModel;
+use serde::Serialize;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::Message;
+
+/// A message received from a Vosk client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoskClientMessage {
+    Config(VoskConfig),
+    Audio(Vec<i16>),
+    Eof,
+    Reset,
+}
+
+impl VoskClientMessage {
+    /// Parse a text frame. Vosk clients send these as JSON objects with a single key.
+    pub fn from_text(text: &str) -> std::result::Result<Self, String> {
+        let value: serde_json::Value =
+            serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+        if let Some(config) = value.get("config") {
+            Ok(Self::Config(VoskConfig::from_json(config)?))
+        } else if value.get("eof").is_some() {
+            Ok(Self::Eof)
+        } else if value.get("reset").is_some() {
+            Ok(Self::Reset)
+        } else {
+            Err(format!("unknown message: {}", text))
+        }
+    }
+
+    /// Parse a binary frame of little-endian pcm16 audio.
+    pub fn from_binary(audio: &[u8]) -> std::result::Result<Self, String> {
+        if audio.len() % 2 == 1 {
+            return Err("audio frames must contain whole pcm16 samples".to_owned());
+        }
+        Ok(Self::Audio(wav::downmix(audio, 1)))
+    }
+}
+
+/// The subset of the Vosk `config` message april can honour.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VoskConfig {
+    pub sample_rate: Option<usize>,
+    /// Whether final results include per-word timings in `result`.
+    pub words: bool,
+}
+
+impl VoskConfig {
+    fn from_json(config: &serde_json::Value) -> std::result::Result<Self, String> {
+        // Vosk clients send the sample rate as either an integer or a float,
+        // and `words` as either a boolean or 0/1
+        let sample_rate = match config.get("sample_rate") {
+            None => None,
+            Some(rate) => Some(
+                rate.as_f64()
+                    .filter(|rate| *rate > 0.0)
+                    .ok_or_else(|| format!("invalid sample_rate: {}", rate))?
+                    as usize,
+            ),
+        };
+        let words = match config.get("words") {
+            None => false,
+            Some(serde_json::Value::Bool(words)) => *words,
+            Some(words) => words.as_i64().is_some_and(|words| words != 0),
+        };
+        Ok(Self { sample_rate, words })
+    }
+}
+
+/// What the caller must do with its session before calling [`VoskAdapter::reply`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionAction {
+    /// Nothing; the message only changed the adapter's state.
+    None,
+    Feed(Vec<i16>),
+    Flush,
+    /// Drop the session along with the audio it holds, and continue with a new one.
+    Reset,
+}
+
+/// A message sent back to a Vosk client.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum VoskReply {
+    Partial {
+        partial: String,
+    },
+    Result {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<Vec<VoskWord>>,
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VoskWord {
+    pub conf: f32,
+    pub end: f64,
+    pub start: f64,
+    pub word: String,
+}
+
+impl From<&Word> for VoskWord {
+    fn from(word: &Word) -> Self {
+        Self {
+            conf: word.logprob.exp().clamp(0.0, 1.0),
+            end: word.end_ms as f64 / 1000.0,
+            start: word.start_ms as f64 / 1000.0,
+            word: word.word.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VoskAdapter {
+    config: VoskConfig,
+    /// Words of the most recent partial result
+    partial: Vec<Word>,
+    /// Words finalized since the last reply
+    finals: Vec<Word>,
+    /// Whether the last message gets a reply at all
+    reply_pending: bool,
+    /// Whether the last message is answered with a final result even if nothing was finalized
+    force_result: bool,
+    finished: bool,
+}
+
+impl VoskAdapter {
+    pub fn config(&self) -> &VoskConfig {
+        &self.config
+    }
+
+    /// Whether the client sent `eof` and the connection should be closed after the last reply.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Handle a client message, returning what the session needs to do before [`Self::reply`].
+    pub fn on_message(&mut self, message: VoskClientMessage) -> SessionAction {
+        self.reply_pending = !matches!(message, VoskClientMessage::Config(_));
+        match message {
+            VoskClientMessage::Config(config) => {
+                self.config = config;
+                SessionAction::None
+            }
+            VoskClientMessage::Audio(samples) => SessionAction::Feed(samples),
+            VoskClientMessage::Eof => {
+                self.force_result = true;
+                self.finished = true;
+                SessionAction::Flush
+            }
+            VoskClientMessage::Reset => {
+                self.force_result = true;
+                self.partial.clear();
+                SessionAction::Reset
+            }
+        }
+    }
+
+    /// Take the session events produced by the last action and build the reply to send back.
+    /// Returns `None` for messages Vosk does not answer, such as `config`.
+    pub fn reply(&mut self, events: impl IntoIterator<Item = Event>) -> Option<VoskReply> {
+        let mut finalized = false;
+        for event in events {
+            match event {
+                Event::Partial { words, .. } => self.partial = words,
+                Event::Final { words, .. } => {
+                    self.finals.extend(words);
+                    self.partial.clear();
+                    finalized = true;
+                }
+                Event::Silence | Event::CantKeepUp => {}
+            }
+        }
+
+        if !std::mem::take(&mut self.reply_pending) {
+            return None;
+        }
+        let force_result = std::mem::take(&mut self.force_result);
+        if finalized || force_result {
+            let words = std::mem::take(&mut self.finals);
+            Some(VoskReply::Result {
+                text: join_words(&words),
+                result: self
+                    .config
+                    .words
+                    .then(|| words.iter().map(VoskWord::from).collect()),
+            })
+        } else {
+            Some(VoskReply::Partial {
+                partial: join_words(&self.partial),
+            })
+        }
+    }
+}
+
+fn join_words(words: &[Word]) -> String {
+    words
+        .iter()
+        .map(|word| word.word.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub struct VoskServer {
+    listener: TcpListener,
+    model: Arc<AprilModel>,
+    limiter: SessionLimiter,
+}
+
+impl VoskServer {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        model: Arc<AprilModel>,
+        limiter: SessionLimiter,
+    ) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            model,
+            limiter,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept connections forever, handling each one on its own thread.
+    pub fn run(self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let model = Arc::clone(&self.model);
+            let limiter = self.limiter.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = handle_connection(stream, &model, &limiter) {
+                    eprintln!("vosk connection from {:?} failed: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    model: &AprilModel,
+    limiter: &SessionLimiter,
+) -> Result<()> {
+    let mut ws = websocket::accept(stream)?;
+    let close = |ws: &mut tungstenite::WebSocket<TcpStream>, code, reason: String| {
+        ws.close(Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        }))?;
+        websocket::finish_close(ws)
+    };
+
+    let Some(_permit) = limiter.try_acquire() else {
+        return close(&mut ws, CloseCode::Again, "too many active sessions".into());
+    };
+
+    let mut adapter = VoskAdapter::default();
+    // created on the first audio frame, since the config message may change the sample rate
+    let mut session = None;
+    loop {
+        let message = match ws.read() {
+            Ok(Message::Text(text)) => VoskClientMessage::from_text(&text),
+            Ok(Message::Binary(audio)) => VoskClientMessage::from_binary(&audio),
+            Ok(Message::Close(_)) => return websocket::finish_close(&mut ws),
+            Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_)) => continue,
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => return close(&mut ws, CloseCode::Invalid, e),
+        };
+
+        let mut action = adapter.on_message(message);
+        if action == SessionAction::Reset {
+            // the next audio frame starts a new session
+            session = None;
+        } else if action != SessionAction::None && session.is_none() {
+            let model_rate = model.get_sample_rate();
+            let sample_rate = adapter.config().sample_rate.unwrap_or(model_rate);
+            if sample_rate != model_rate {
+                let reason = format!(
+                    "sample rate {} does not match the model's {}",
+                    sample_rate, model_rate
+                );
+                return close(&mut ws, CloseCode::Unsupported, reason);
+            }
+            let (new_session, results) = transcript::create_session(model, limiter.vocabulary())?;
+            limiter.metrics().track(new_session.metrics_handle());
+            session = Some((new_session, results));
+        }
+
+        let mut events = Vec::new();
+        if let Some((session, results)) = session.as_mut() {
+            match &mut action {
+                SessionAction::None | SessionAction::Reset => {}
+                SessionAction::Feed(samples) => session.feed_pcm16(samples),
+                SessionAction::Flush => session.flush(),
+            }
+            events.extend(results.try_iter());
+        }
+        if let Some(reply) = adapter.reply(events) {
+            let json = serde_json::to_string(&reply).expect("vosk replies always serialize");
+            ws.send(Message::Text(json))?;
+        }
+
+        if adapter.is_finished() {
+            ws.close(None)?;
+            return websocket::finish_close(&mut ws);
+        }
+    }
+}
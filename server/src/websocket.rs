@@ -0,0 +1,219 @@
+//! Streaming transcription over WebSocket.
+//!
+//! Clients open a connection and send a `start` control message, then binary frames of
+//! little-endian pcm16 audio. Control messages are JSON text frames:
+//!
+//! * `{"type": "start", "sample_rate": 16000}` starts a session. The sample rate must match the model.
+//! * `{"type": "flush"}` flushes the session, forcing out a final result.
+//! * `{"type": "end"}` flushes the session and closes the connection.
+//!
+//! The server answers `start` with `{"type": "ready", "sample_rate": ...}`, then streams
+//! [`Event`]s back as JSON text frames. Protocol errors are reported as
+//! `{"type": "error", "message": ...}` before the connection is closed.
+
+use crate::error::Result;
+use crate::limits::SessionLimiter;
+use crate::transcript::{self, Event, EventSink};
+use april_asr_rs::codec::wav;
+use april_asr_rs::{AprilModel, AprilSession};
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::{Message, WebSocket};
+
+pub struct WebSocketServer {
+    listener: TcpListener,
+    model: Arc<AprilModel>,
+    limiter: SessionLimiter,
+}
+
+impl WebSocketServer {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        model: Arc<AprilModel>,
+        limiter: SessionLimiter,
+    ) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            model,
+            limiter,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept connections forever, handling each one on its own thread.
+    pub fn run(self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let model = Arc::clone(&self.model);
+            let limiter = self.limiter.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = handle_connection(stream, &model, &limiter) {
+                    eprintln!("websocket connection from {:?} failed: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Control {
+    Start { sample_rate: usize },
+    Flush,
+    End,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply<'a> {
+    Ready { sample_rate: usize },
+    Error { message: &'a str },
+}
+
+struct Connection<'m> {
+    ws: WebSocket<TcpStream>,
+    model: &'m AprilModel,
+    session: Option<(AprilSession<'m, EventSink>, Receiver<Event>)>,
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    model: &AprilModel,
+    limiter: &SessionLimiter,
+) -> Result<()> {
+    let mut conn = Connection {
+        ws: accept(stream)?,
+        model,
+        session: None,
+    };
+
+    let Some(_permit) = limiter.try_acquire() else {
+        return conn.close_with_error(CloseCode::Again, "too many active sessions");
+    };
+
+    loop {
+        match conn.ws.read() {
+            Ok(Message::Binary(audio)) => {
+                let Some((session, _)) = conn.session.as_mut() else {
+                    return conn.close_with_error(CloseCode::Policy, "audio sent before start");
+                };
+                if audio.len() % 2 == 1 {
+                    return conn.close_with_error(
+                        CloseCode::Invalid,
+                        "audio frames must contain whole pcm16 samples",
+                    );
+                }
+                let mut samples = wav::downmix(&audio, 1);
+                session.feed_pcm16(&mut samples);
+                conn.send_events()?;
+            }
+            Ok(Message::Text(text)) => match serde_json::from_str::<Control>(&text) {
+                Ok(Control::Start { sample_rate }) => {
+                    if conn.session.is_some() {
+                        return conn.close_with_error(CloseCode::Policy, "session already started");
+                    }
+                    let model_rate = conn.model.get_sample_rate();
+                    if sample_rate != model_rate {
+                        let message = format!(
+                            "sample rate {} does not match the model's {}",
+                            sample_rate, model_rate
+                        );
+                        return conn.close_with_error(CloseCode::Unsupported, &message);
+                    }
+                    let (session, results) =
+                        transcript::create_session(conn.model, limiter.vocabulary())?;
+                    limiter.metrics().track(session.metrics_handle());
+                    conn.session = Some((session, results));
+                    conn.send_json(&Reply::Ready { sample_rate })?;
+                }
+                Ok(Control::Flush) => {
+                    if let Some((session, _)) = conn.session.as_mut() {
+                        session.flush();
+                    }
+                    conn.send_events()?;
+                }
+                Ok(Control::End) => {
+                    if let Some((session, _)) = conn.session.as_mut() {
+                        session.flush();
+                    }
+                    conn.send_events()?;
+                    conn.ws.close(None)?;
+                    return conn.finish_close();
+                }
+                Err(e) => {
+                    let message = format!("invalid control message: {}", e);
+                    return conn.close_with_error(CloseCode::Invalid, &message);
+                }
+            },
+            Ok(Message::Close(_)) => return conn.finish_close(),
+            Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_)) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+impl Connection<'_> {
+    fn send_json(&mut self, value: &impl Serialize) -> Result<()> {
+        let json = serde_json::to_string(value).expect("server messages always serialize");
+        self.ws.send(Message::Text(json))?;
+        Ok(())
+    }
+
+    /// Forward every result the session produced since the last call.
+    fn send_events(&mut self) -> Result<()> {
+        let Some((_, results)) = self.session.as_ref() else {
+            return Ok(());
+        };
+        let events: Vec<Event> = results.try_iter().collect();
+        for event in &events {
+            self.send_json(event)?;
+        }
+        Ok(())
+    }
+
+    fn close_with_error(&mut self, code: CloseCode, message: &str) -> Result<()> {
+        self.send_json(&Reply::Error { message })?;
+        self.ws.close(Some(CloseFrame {
+            code,
+            reason: message.to_owned().into(),
+        }))?;
+        self.finish_close()
+    }
+
+    fn finish_close(&mut self) -> Result<()> {
+        finish_close(&mut self.ws)
+    }
+}
+
+/// Run the server side of the WebSocket handshake on a freshly accepted connection.
+pub(crate) fn accept(stream: TcpStream) -> Result<WebSocket<TcpStream>> {
+    tungstenite::accept(stream).map_err(|e| match e {
+        tungstenite::HandshakeError::Failure(e) => e.into(),
+        tungstenite::HandshakeError::Interrupted(_) => {
+            unreachable!("handshake interrupted on a blocking stream")
+        }
+    })
+}
+
+/// Keep reading until the client acknowledges the close handshake.
+pub(crate) fn finish_close(ws: &mut WebSocket<TcpStream>) -> Result<()> {
+    loop {
+        match ws.read() {
+            Ok(_) => {}
+            Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                return Ok(())
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
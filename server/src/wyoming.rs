@@ -0,0 +1,338 @@
+//! Speech-to-text over the Wyoming protocol, as used by Home Assistant.
+//!
+//! Every Wyoming event is a JSON header line, optionally followed by `data_length` bytes of
+//! extra JSON data and `payload_length` bytes of binary payload. A transcription looks like:
+//!
+//! 1. the client sends `describe`, and the server answers with `info`
+//! 2. the client sends `transcribe`, `audio-start`, a series of `audio-chunk`s and `audio-stop`
+//! 3. the server answers `audio-stop` with a `transcript` of everything between start and stop
+
+use crate::config::Limits;
+use crate::error::Result;
+use crate::limits::{SessionLimiter, SessionPermit};
+use crate::transcript::{self, Event, EventSink, Transcript};
+use april_asr_rs::codec::wav;
+use april_asr_rs::{AprilModel, AprilSession};
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+/// Version of the Wyoming protocol this server speaks.
+pub const WYOMING_VERSION: &str = "1.5.2";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WyomingEvent {
+    pub event_type: String,
+    pub data: Map<String, Value>,
+    pub payload: Vec<u8>,
+}
+
+impl WyomingEvent {
+    /// Create an event without a payload. `data` should be a JSON object; anything else is ignored.
+    pub fn new(event_type: impl Into<String>, data: Value) -> Self {
+        Self {
+            event_type: event_type.into(),
+            data: match data {
+                Value::Object(data) => data,
+                _ => Map::new(),
+            },
+            payload: Vec::new(),
+        }
+    }
+
+    pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Read the next event, or `None` if the stream ended cleanly before one started.
+    ///
+    /// The header line, data and payload may each be `max_length` bytes long at most. Longer
+    /// ones are rejected as invalid data before anything is allocated for them.
+    pub fn read_from(
+        reader: &mut impl BufRead,
+        max_length: usize,
+    ) -> std::io::Result<Option<Self>> {
+        let mut header = String::new();
+        let read = reader.take(max_length as u64 + 1).read_line(&mut header)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read > max_length {
+            return Err(invalid_data(format!(
+                "event header is longer than {} bytes",
+                max_length
+            )));
+        }
+        let header: Map<String, Value> = serde_json::from_str(&header).map_err(invalid_data)?;
+        let event_type = header
+            .get("type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid_data("event header has no type"))?
+            .to_owned();
+        let length = |key: &str| {
+            let length = header.get(key).and_then(Value::as_u64).unwrap_or(0);
+            if length > max_length as u64 {
+                return Err(invalid_data(format!(
+                    "event {} of {} is over the limit of {} bytes",
+                    key, length, max_length
+                )));
+            }
+            Ok(length as usize)
+        };
+
+        // data may be inline in the header (older clients), in a separate block, or both
+        let mut data = match header.get("data") {
+            Some(Value::Object(data)) => data.clone(),
+            _ => Map::new(),
+        };
+        let data_length = length("data_length")?;
+        if data_length > 0 {
+            let mut block = vec![0; data_length];
+            reader.read_exact(&mut block)?;
+            let block: Map<String, Value> = serde_json::from_slice(&block).map_err(invalid_data)?;
+            data.extend(block);
+        }
+
+        let mut payload = vec![0; length("payload_length")?];
+        reader.read_exact(&mut payload)?;
+
+        Ok(Some(Self {
+            event_type,
+            data,
+            payload,
+        }))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let data = (!self.data.is_empty())
+            .then(|| serde_json::to_vec(&self.data).expect("JSON maps always serialize"));
+        let mut header = json!({
+            "type": self.event_type,
+            "version": WYOMING_VERSION,
+        });
+        if let Some(data) = &data {
+            header["data_length"] = data.len().into();
+        }
+        if !self.payload.is_empty() {
+            header["payload_length"] = self.payload.len().into();
+        }
+
+        let mut buf = serde_json::to_vec(&header).expect("JSON values always serialize");
+        buf.push(b'\n');
+        buf.extend(data.unwrap_or_default());
+        buf.extend(&self.payload);
+        writer.write_all(&buf)?;
+        writer.flush()
+    }
+}
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+}
+
+/// The `info` event describing `model` as the single available ASR model.
+pub fn info_event(model: &AprilModel) -> WyomingEvent {
+    let attribution = json!({
+        "name": "april-asr",
+        "url": "https://github.com/abb128/april-asr",
+    });
+    let name = model.get_model_name().unwrap_or_default();
+    let languages: Vec<&str> = model.get_model_language().into_iter().collect();
+    WyomingEvent::new(
+        "info",
+        json!({
+            "asr": [{
+                "name": "april-asr",
+                "description": "april-asr speech recognition",
+                "attribution": attribution,
+                "installed": true,
+                "version": env!("CARGO_PKG_VERSION"),
+                "models": [{
+                    "name": name,
+                    "description": model.get_model_description().unwrap_or_default(),
+                    "attribution": attribution,
+                    "installed": true,
+                    "version": null,
+                    "languages": languages,
+                }],
+            }],
+            "tts": [],
+            "handle": [],
+            "intent": [],
+            "wake": [],
+        }),
+    )
+}
+
+pub fn error_event(text: &str) -> WyomingEvent {
+    WyomingEvent::new("error", json!({ "text": text, "code": "april-asr" }))
+}
+
+pub struct WyomingServer {
+    listener: TcpListener,
+    model: Arc<AprilModel>,
+    limiter: SessionLimiter,
+    limits: Limits,
+}
+
+impl WyomingServer {
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        model: Arc<AprilModel>,
+        limiter: SessionLimiter,
+        limits: Limits,
+    ) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            model,
+            limiter,
+            limits,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Accept connections forever, handling each one on its own thread.
+    pub fn run(self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let model = Arc::clone(&self.model);
+            let limiter = self.limiter.clone();
+            let max_event_bytes = self.limits.max_event_bytes;
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = handle_connection(stream, &model, &limiter, max_event_bytes) {
+                    eprintln!("wyoming connection from {:?} failed: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Audio format announced by `audio-start`, and repeated on every `audio-chunk`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AudioFormat {
+    rate: usize,
+    width: usize,
+    channels: usize,
+}
+
+impl AudioFormat {
+    fn from_data(data: &Map<String, Value>) -> Option<Self> {
+        let field = |key: &str| data.get(key).and_then(Value::as_u64).map(|v| v as usize);
+        Some(Self {
+            rate: field("rate")?,
+            width: field("width")?,
+            channels: field("channels")?,
+        })
+    }
+}
+
+struct Recognition<'m> {
+    _permit: SessionPermit,
+    format: AudioFormat,
+    session: AprilSession<'m, EventSink>,
+    results: Receiver<Event>,
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    model: &AprilModel,
+    limiter: &SessionLimiter,
+    max_event_bytes: usize,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut recognition: Option<Recognition> = None;
+
+    loop {
+        let event = match WyomingEvent::read_from(&mut reader, max_event_bytes) {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            // the rest of the stream can't be framed anymore, so give up on the connection
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                error_event(&e.to_string()).write_to(&mut writer)?;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match event.event_type.as_str() {
+            "describe" => info_event(model).write_to(&mut writer)?,
+            // april models only know a single language, so there is nothing to select
+            "transcribe" => {}
+            "audio-start" => {
+                recognition = None;
+                let Some(format) = AudioFormat::from_data(&event.data) else {
+                    error_event("audio-start is missing rate, width or channels")
+                        .write_to(&mut writer)?;
+                    continue;
+                };
+                if let Err(e) = check_format(format, model) {
+                    error_event(&e).write_to(&mut writer)?;
+                    continue;
+                }
+                let Some(permit) = limiter.try_acquire() else {
+                    error_event("too many active sessions").write_to(&mut writer)?;
+                    continue;
+                };
+                let (session, results) = transcript::create_session(model, limiter.vocabulary())?;
+                limiter.metrics().track(session.metrics_handle());
+                recognition = Some(Recognition {
+                    _permit: permit,
+                    format,
+                    session,
+                    results,
+                });
+            }
+            "audio-chunk" => {
+                let Some(recognition) = recognition.as_mut() else {
+                    continue;
+                };
+                let format = AudioFormat::from_data(&event.data).unwrap_or(recognition.format);
+                if format != recognition.format {
+                    error_event("audio format changed mid-stream").write_to(&mut writer)?;
+                    continue;
+                }
+                let mut samples = wav::downmix(&event.payload, format.channels);
+                recognition.session.feed_pcm16(&mut samples);
+            }
+            "audio-stop" => {
+                let Some(mut recognition) = recognition.take() else {
+                    continue;
+                };
+                recognition.session.flush();
+                let transcript = Transcript::from_events(recognition.results.try_iter());
+                WyomingEvent::new("transcript", json!({ "text": transcript.text }))
+                    .write_to(&mut writer)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_format(format: AudioFormat, model: &AprilModel) -> std::result::Result<(), String> {
+    if format.width != 2 {
+        return Err(format!(
+            "unsupported sample width {}, only 16-bit audio is supported",
+            format.width
+        ));
+    }
+    if format.channels == 0 {
+        return Err("audio has no channels".to_owned());
+    }
+    let model_rate = model.get_sample_rate();
+    if format.rate != model_rate {
+        return Err(format!(
+            "sample rate {} does not match the model's {}",
+            format.rate, model_rate
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! Sends requests to the HTTP server over localhost, against the stub april library.
+//!
+//! Run with `cargo test --features stub`. The stub recognizes every 500ms block of audio with
+//! sound in it as the next number word.
+#![cfg(feature = "stub")]
+
+use april_asr_rs::AprilModel;
+use april_asr_server::{HttpServer, Limits, SessionLimiter};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+
+/// The stub loads any readable file as a model.
+const MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+const BLOCK: usize = 8000;
+
+fn serve(limiter: SessionLimiter, limits: Limits) -> SocketAddr {
+    let model = Arc::new(AprilModel::new(MODEL_PATH).expect("failed to load stub model"));
+    let server = HttpServer::bind("127.0.0.1:0", model, limiter, limits).unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+    addr
+}
+
+/// Send a request, returning the status code and the body of the response.
+fn request(addr: SocketAddr, method: &str, target: &str, body: &[u8]) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        method,
+        target,
+        body.len()
+    )
+    .unwrap();
+    stream.write_all(body).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_owned())
+}
+
+fn pcm(samples: &[i16]) -> Vec<u8> {
+    samples.iter().flat_map(|s| s.to_le_bytes()).collect()
+}
+
+fn wav(samples: &[i16]) -> Vec<u8> {
+    let data = pcm(samples);
+    let mut bytes = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0\x01\0\x01\0".to_vec();
+    bytes.extend(16000u32.to_le_bytes());
+    bytes.extend(32000u32.to_le_bytes());
+    bytes.extend(b"\x02\0\x10\0data");
+    bytes.extend((data.len() as u32).to_le_bytes());
+    bytes.extend(data);
+    bytes
+}
+
+#[test]
+fn transcribes_wav_and_raw_audio() {
+    let addr = serve(SessionLimiter::new(1), Limits::default());
+    let mut audio = vec![1000; 2 * BLOCK];
+    audio.extend(vec![0; BLOCK]);
+    audio.extend(vec![1000; BLOCK]);
+
+    let (status, body) = request(addr, "POST", "/transcribe", &wav(&audio));
+    assert_eq!(status, 200, "{}", body);
+    let transcript: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(transcript["text"], "ONE TWO. ONE.");
+    assert_eq!(transcript["words"].as_array().unwrap().len(), 3);
+    assert_eq!(transcript["words"][2]["start_ms"], 1500);
+
+    let (status, body) = request(addr, "POST", "/transcribe?sample_rate=16000", &pcm(&audio));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), transcript);
+
+    let (status, body) = request(addr, "GET", "/models", &[]);
+    assert_eq!(status, 200);
+    let models: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(models["models"][0]["name"], "stub");
+    assert_eq!(models["models"][0]["sample_rate"], 16000);
+}
+
+#[test]
+fn rejects_bad_requests() {
+    let limits = Limits {
+        max_body_bytes: 1024,
+        ..Limits::default()
+    };
+    let addr = serve(SessionLimiter::new(1), limits.clone());
+    let error = |(status, body): (u16, String)| {
+        let body: Value = serde_json::from_str(&body).unwrap();
+        (status, body["error"].as_str().unwrap().to_owned())
+    };
+
+    assert_eq!(
+        error(request(
+            addr,
+            "POST",
+            "/transcribe?sample_rate=8000",
+            &pcm(&[0; 4])
+        )),
+        (
+            422,
+            "sample rate 8000 does not match the model's 16000".into()
+        )
+    );
+    assert_eq!(
+        error(request(addr, "POST", "/transcribe", &[0; 3])),
+        (400, "raw audio must contain whole pcm16 frames".into())
+    );
+    assert_eq!(
+        error(request(addr, "POST", "/transcribe", &[0; 1026])),
+        (413, "request body too large".into())
+    );
+    assert_eq!(error(request(addr, "GET", "/transcribe", &[])).0, 405);
+    assert_eq!(error(request(addr, "GET", "/nowhere", &[])).0, 404);
+
+    // a server without free sessions refuses before reading the body
+    let addr = serve(SessionLimiter::new(0), limits);
+    assert_eq!(
+        error(request(addr, "POST", "/transcribe", &pcm(&[0; 4]))),
+        (503, "too many active sessions".into())
+    );
+}
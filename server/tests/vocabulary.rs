@@ -0,0 +1,94 @@
+//! Loads vocabulary files, and reloads them while they are in use.
+
+use april_asr_server::{Error, SharedVocabulary, VocabularyFile};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("april-vocabulary-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+#[test]
+fn loads_toml_and_json() {
+    let toml = VocabularyFile::from_toml(
+        r#"
+        [[replace]]
+        from = "cube control"
+        to = "kubectl"
+
+        [[replace]]
+        from = "kubernetes"
+        to = "Kubernetes"
+        match = "phonetic"
+        preserve_case = false
+        "#,
+    )
+    .unwrap();
+    let json = VocabularyFile::from_json(
+        r#"{"replace": [
+            {"from": "cube control", "to": "kubectl"},
+            {"from": "kubernetes", "to": "Kubernetes", "match": "phonetic", "preserve_case": false}
+        ]}"#,
+    )
+    .unwrap();
+    for file in [toml, json] {
+        let replaced = file
+            .to_vocabulary()
+            .apply_text("CUBE CONTROL GET CUBER NETTIES");
+        assert_eq!(replaced.to_string(), "KUBECTL GET Kubernetes");
+    }
+
+    assert!(
+        VocabularyFile::from_toml("[[replace]]\nfrom = \"a\"\nto = \"b\"\nmatch = \"sounds\"")
+            .is_err()
+    );
+    let path = temp_file("broken.json");
+    std::fs::write(&path, "{\"replace\": [{\"from\": \"a\"}]}").unwrap();
+    assert!(matches!(
+        SharedVocabulary::load_file(&path),
+        Err(Error::Vocabulary { .. })
+    ));
+}
+
+#[test]
+fn reloads_changed_file() {
+    let path = temp_file("reload.toml");
+    std::fs::write(&path, "[[replace]]\nfrom = \"one\"\nto = \"1\"\n").unwrap();
+    let vocabulary = SharedVocabulary::load_file(&path).unwrap();
+    let before = vocabulary.current();
+    vocabulary.watch(path.clone(), Duration::from_millis(10));
+
+    let write = |contents: &str, modified: SystemTime| {
+        std::fs::write(&path, contents).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(modified).unwrap();
+    };
+    let wait_for = |expected: &str| {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while vocabulary.current().apply_text("one two").to_string() != expected {
+            assert!(Instant::now() < deadline, "vocabulary was not reloaded");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    let later = SystemTime::now() + Duration::from_secs(10);
+    write("[[replace]]\nfrom = \"two\"\nto = \"2\"\n", later);
+    wait_for("one 2");
+    // vocabularies taken before the reload are left as they were
+    assert_eq!(before.apply_text("one two").to_string(), "1 two");
+
+    // a broken file keeps the previous vocabulary
+    write(
+        "[[replace]]\nfrom = \"two\"\n",
+        later + Duration::from_secs(10),
+    );
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(vocabulary.current().len(), 1);
+    write(
+        "[[replace]]\nfrom = \"one two\"\nto = \"12\"\n",
+        later + Duration::from_secs(20),
+    );
+    wait_for("12");
+}
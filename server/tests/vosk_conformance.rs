@@ -0,0 +1,112 @@
+//! Replays Vosk client sessions through [`VoskAdapter`]. `tests/vosk_server.rs` runs the
+//! server itself over a socket.
+//!
+//! Each file in `tests/vosk_sessions` is one session, one line per client message. Every line holds
+//! the message the client sent, the events april produced while handling it, and the reply a
+//! `vosk-server` sends back for it (`null` if it sends none). The sessions are written by hand
+//! after the messages of `vosk-server` and its example clients, not captured from a running
+//! server. Audio content is not stored, only the size of each frame, since the april events are
+//! given along with it.
+
+use april_asr_server::{Event, SessionAction, VoskAdapter, VoskClientMessage};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct Step {
+    client: ClientFrame,
+    april: Vec<Event>,
+    reply: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ClientFrame {
+    Text(String),
+    AudioBytes(usize),
+}
+
+fn replay(path: &Path) {
+    let recording = std::fs::read_to_string(path).expect("failed to read recorded session");
+    let mut adapter = VoskAdapter::default();
+    let mut sent_eof = false;
+
+    for (line, step) in recording.lines().enumerate() {
+        let step: Step = serde_json::from_str(step)
+            .unwrap_or_else(|e| panic!("{}:{}: invalid step: {}", path.display(), line + 1, e));
+        let message = match &step.client {
+            ClientFrame::Text(text) => VoskClientMessage::from_text(text),
+            ClientFrame::AudioBytes(len) => VoskClientMessage::from_binary(&vec![0; *len]),
+        }
+        .unwrap_or_else(|e| panic!("{}:{}: rejected message: {}", path.display(), line + 1, e));
+        sent_eof |= message == VoskClientMessage::Eof;
+
+        let expected_action = match &message {
+            VoskClientMessage::Config(_) => SessionAction::None,
+            VoskClientMessage::Audio(samples) => SessionAction::Feed(samples.clone()),
+            VoskClientMessage::Eof => SessionAction::Flush,
+            VoskClientMessage::Reset => SessionAction::Reset,
+        };
+        let action = adapter.on_message(message);
+        assert_eq!(
+            action,
+            expected_action,
+            "{}:{}: unexpected session action",
+            path.display(),
+            line + 1
+        );
+
+        let reply = adapter
+            .reply(step.april)
+            .map(|reply| serde_json::to_value(reply).unwrap())
+            .unwrap_or(serde_json::Value::Null);
+        assert_eq!(
+            reply,
+            step.reply,
+            "{}:{}: reply differs from the recording",
+            path.display(),
+            line + 1
+        );
+    }
+
+    assert_eq!(adapter.is_finished(), sent_eof, "{}", path.display());
+}
+
+#[test]
+fn recorded_sessions() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vosk_sessions");
+    let mut sessions: Vec<_> = std::fs::read_dir(&dir)
+        .expect("failed to list recorded sessions")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    sessions.sort();
+    assert!(
+        !sessions.is_empty(),
+        "no recorded sessions in {}",
+        dir.display()
+    );
+
+    for session in sessions {
+        replay(&session);
+    }
+}
+
+#[test]
+fn parses_client_messages() {
+    assert_eq!(
+        VoskClientMessage::from_text(r#"{"eof" : 1}"#),
+        Ok(VoskClientMessage::Eof)
+    );
+    assert_eq!(
+        VoskClientMessage::from_text(r#"{"reset" : 1}"#),
+        Ok(VoskClientMessage::Reset)
+    );
+    assert_eq!(
+        VoskClientMessage::from_binary(&[1, 0, 0xff, 0xff]),
+        Ok(VoskClientMessage::Audio(vec![1, -1]))
+    );
+    assert!(VoskClientMessage::from_binary(&[1, 0, 0]).is_err());
+    assert!(VoskClientMessage::from_text(r#"{"config": {"sample_rate": "fast"}}"#).is_err());
+    assert!(VoskClientMessage::from_text(r#"{"hello": 1}"#).is_err());
+}
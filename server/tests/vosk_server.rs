@@ -0,0 +1,114 @@
+//! Talks to the Vosk-compatible server over localhost like a Vosk client, against the stub
+//! april library.
+//!
+//! Run with `cargo test --features stub`. The stub recognizes every 500ms block of audio with
+//! sound in it as the next number word, and ends the sentence on silence.
+#![cfg(feature = "stub")]
+
+use april_asr_rs::AprilModel;
+use april_asr_server::{SessionLimiter, VoskServer};
+use serde_json::{json, Value};
+use std::net::TcpStream;
+use std::sync::Arc;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+/// The stub loads any readable file as a model.
+const MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+const BLOCK: usize = 8000;
+
+type Client = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn connect() -> Client {
+    let model = Arc::new(AprilModel::new(MODEL_PATH).expect("failed to load stub model"));
+    let server = VoskServer::bind("127.0.0.1:0", model, SessionLimiter::new(1)).unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+    let (ws, _) = tungstenite::connect(format!("ws://{}", addr)).unwrap();
+    ws
+}
+
+fn read_json(ws: &mut Client) -> Value {
+    match ws.read().unwrap() {
+        Message::Text(text) => serde_json::from_str(&text).unwrap(),
+        other => panic!("expected a text frame, got {:?}", other),
+    }
+}
+
+fn audio(sample: i16, blocks: usize) -> Message {
+    Message::Binary(sample.to_le_bytes().repeat(blocks * BLOCK))
+}
+
+#[test]
+fn answers_every_message_like_vosk() {
+    let mut ws = connect();
+    ws.send(Message::Text(
+        r#"{"config" : {"sample_rate" : 16000.0, "words" : 1}}"#.into(),
+    ))
+    .unwrap();
+
+    ws.send(audio(1000, 1)).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"partial": "ONE"}));
+    ws.send(audio(1000, 1)).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"partial": "ONE TWO"}));
+    ws.send(audio(0, 1)).unwrap();
+    let result = read_json(&mut ws);
+    assert_eq!(result["text"], "ONE TWO.");
+    assert_eq!(result["result"][1]["word"], "TWO.");
+    assert_eq!(result["result"][1]["start"], 0.5);
+
+    ws.send(audio(1000, 1)).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"partial": "ONE"}));
+    ws.send(Message::Text(r#"{"eof" : 1}"#.into())).unwrap();
+    assert_eq!(read_json(&mut ws)["text"], "ONE.");
+    assert!(matches!(ws.read().unwrap(), Message::Close(_)));
+}
+
+#[test]
+fn reset_drops_the_utterance() {
+    let mut ws = connect();
+    ws.send(audio(1000, 1)).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"partial": "ONE"}));
+    ws.send(audio(1000, 1)).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"partial": "ONE TWO"}));
+    ws.send(Message::Text(r#"{"reset" : 1}"#.into())).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"text": ""}));
+
+    // recognition starts over, and the dropped words never show up
+    ws.send(audio(1000, 1)).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"partial": "ONE"}));
+    ws.send(Message::Text(r#"{"eof" : 1}"#.into())).unwrap();
+    assert_eq!(read_json(&mut ws), json!({"text": "ONE."}));
+    assert!(matches!(ws.read().unwrap(), Message::Close(_)));
+}
+
+#[test]
+fn closes_on_bad_messages() {
+    let mut ws = connect();
+    ws.send(Message::Text(
+        r#"{"config" : {"sample_rate" : 8000}}"#.into(),
+    ))
+    .unwrap();
+    ws.send(audio(1000, 1)).unwrap();
+    match ws.read().unwrap() {
+        Message::Close(Some(frame)) => {
+            assert_eq!(
+                frame.reason,
+                "sample rate 8000 does not match the model's 16000"
+            )
+        }
+        other => panic!("expected a close frame, got {:?}", other),
+    }
+
+    let mut ws = connect();
+    ws.send(Message::Binary(vec![0; 3])).unwrap();
+    match ws.read().unwrap() {
+        Message::Close(Some(frame)) => {
+            assert_eq!(
+                frame.reason,
+                "audio frames must contain whole pcm16 samples"
+            )
+        }
+        other => panic!("expected a close frame, got {:?}", other),
+    }
+}
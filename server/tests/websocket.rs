@@ -0,0 +1,90 @@
+//! Streams audio to the WebSocket server over localhost, against the stub april library.
+//!
+//! Run with `cargo test --features stub`. The stub recognizes every 500ms block of audio with
+//! sound in it as the next number word.
+#![cfg(feature = "stub")]
+
+use april_asr_rs::AprilModel;
+use april_asr_server::{SessionLimiter, WebSocketServer};
+use serde_json::{json, Value};
+use std::net::TcpStream;
+use std::sync::Arc;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+/// The stub loads any readable file as a model.
+const MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+const BLOCK: usize = 8000;
+
+fn connect(limiter: SessionLimiter) -> WebSocket<MaybeTlsStream<TcpStream>> {
+    let model = Arc::new(AprilModel::new(MODEL_PATH).expect("failed to load stub model"));
+    let server = WebSocketServer::bind("127.0.0.1:0", model, limiter).unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+    let (ws, _) = tungstenite::connect(format!("ws://{}", addr)).unwrap();
+    ws
+}
+
+fn send_json(ws: &mut WebSocket<MaybeTlsStream<TcpStream>>, value: Value) {
+    ws.send(Message::Text(value.to_string())).unwrap();
+}
+
+fn read_json(ws: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Value {
+    match ws.read().unwrap() {
+        Message::Text(text) => serde_json::from_str(&text).unwrap(),
+        other => panic!("expected a text frame, got {:?}", other),
+    }
+}
+
+fn speech(blocks: usize) -> Vec<u8> {
+    1000i16.to_le_bytes().repeat(blocks * BLOCK)
+}
+
+#[test]
+fn streams_partial_and_final_results() {
+    let mut ws = connect(SessionLimiter::new(1));
+    send_json(&mut ws, json!({"type": "start", "sample_rate": 16000}));
+    assert_eq!(
+        read_json(&mut ws),
+        json!({"type": "ready", "sample_rate": 16000})
+    );
+
+    // results for a frame are sent right after it is fed
+    ws.send(Message::Binary(speech(1))).unwrap();
+    let partial = read_json(&mut ws);
+    assert_eq!(partial["type"], "partial");
+    assert_eq!(partial["text"], " ONE");
+    ws.send(Message::Binary(speech(1))).unwrap();
+    let partial = read_json(&mut ws);
+    assert_eq!(partial["text"], " ONE TWO");
+    assert_eq!(partial["words"][1]["word"], "TWO");
+    assert_eq!(partial["words"][1]["start_ms"], 500);
+
+    send_json(&mut ws, json!({"type": "end"}));
+    let result = read_json(&mut ws);
+    assert_eq!(result["type"], "final");
+    assert_eq!(result["text"], " ONE TWO.");
+    assert_eq!(result["words"].as_array().unwrap().len(), 2);
+    assert_eq!(read_json(&mut ws), json!({"type": "silence"}));
+    assert!(matches!(ws.read().unwrap(), Message::Close(_)));
+}
+
+#[test]
+fn rejects_bad_sessions() {
+    let mut ws = connect(SessionLimiter::new(1));
+    ws.send(Message::Binary(speech(1))).unwrap();
+    assert_eq!(
+        read_json(&mut ws),
+        json!({"type": "error", "message": "audio sent before start"})
+    );
+
+    let mut ws = connect(SessionLimiter::new(1));
+    send_json(&mut ws, json!({"type": "start", "sample_rate": 8000}));
+    assert_eq!(
+        read_json(&mut ws)["message"],
+        "sample rate 8000 does not match the model's 16000"
+    );
+
+    let mut ws = connect(SessionLimiter::new(0));
+    assert_eq!(read_json(&mut ws)["message"], "too many active sessions");
+}
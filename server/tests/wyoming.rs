@@ -0,0 +1,172 @@
+//! Drives the Wyoming server with a fake Home Assistant client.
+//!
+//! The end-to-end test runs against the stub april library, with `cargo test --features stub`.
+//! The stub recognizes every 500ms block of audio with sound in it as the next number word.
+
+use april_asr_server::WyomingEvent;
+use serde_json::json;
+
+/// Limit on the parts of an event, as large as the tests need.
+const MAX_LENGTH: usize = 1024;
+
+#[test]
+fn event_round_trip() {
+    let event = WyomingEvent::new(
+        "audio-chunk",
+        json!({"rate": 16000, "width": 2, "channels": 1}),
+    )
+    .with_payload(vec![1, 2, 3, 4]);
+    let mut wire = Vec::new();
+    event.write_to(&mut wire).unwrap();
+    WyomingEvent::new("audio-stop", json!({}))
+        .write_to(&mut wire)
+        .unwrap();
+
+    let header_end = wire.iter().position(|b| *b == b'\n').unwrap();
+    let header: serde_json::Value = serde_json::from_slice(&wire[..header_end]).unwrap();
+    assert_eq!(header["type"], "audio-chunk");
+    assert_eq!(header["payload_length"], 4);
+    assert!(header.get("data").is_none());
+
+    let mut reader = &wire[..];
+    assert_eq!(
+        WyomingEvent::read_from(&mut reader, MAX_LENGTH).unwrap(),
+        Some(event)
+    );
+    let stop = WyomingEvent::read_from(&mut reader, MAX_LENGTH)
+        .unwrap()
+        .unwrap();
+    assert_eq!(stop.event_type, "audio-stop");
+    assert!(stop.data.is_empty() && stop.payload.is_empty());
+    assert_eq!(
+        WyomingEvent::read_from(&mut reader, MAX_LENGTH).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn reads_inline_data() {
+    // older clients put data in the header instead of a separate block
+    let wire = b"{\"type\": \"transcribe\", \"data\": {\"language\": \"en\"}}\n";
+    let event = WyomingEvent::read_from(&mut &wire[..], MAX_LENGTH)
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.event_type, "transcribe");
+    assert_eq!(event.data["language"], "en");
+}
+
+#[test]
+fn rejects_truncated_payload() {
+    let wire = b"{\"type\": \"audio-chunk\", \"payload_length\": 8}\n\x00\x00";
+    assert!(WyomingEvent::read_from(&mut &wire[..], MAX_LENGTH).is_err());
+}
+
+#[test]
+fn rejects_oversized_events() {
+    for wire in [
+        "{\"type\": \"audio-chunk\", \"payload_length\": 1000000000000}\n".to_owned(),
+        "{\"type\": \"transcribe\", \"data_length\": 1025}\n".to_owned(),
+        format!("{{\"type\": \"{}\"}}\n", "x".repeat(MAX_LENGTH)),
+    ] {
+        let error = WyomingEvent::read_from(&mut wire.as_bytes(), MAX_LENGTH).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+    let wire = b"{\"type\": \"audio-chunk\", \"payload_length\": 1024}\n";
+    let mut wire = wire.to_vec();
+    wire.extend([0; 1024]);
+    let event = WyomingEvent::read_from(&mut &wire[..], MAX_LENGTH).unwrap();
+    assert_eq!(event.unwrap().payload.len(), 1024);
+}
+
+#[cfg(feature = "stub")]
+#[test]
+fn fake_client_session() {
+    use april_asr_rs::AprilModel;
+    use april_asr_server::{Limits, SessionLimiter, WyomingServer};
+    use std::io::BufReader;
+    use std::net::TcpStream;
+    use std::sync::Arc;
+
+    // the stub loads any readable file as a model
+    let model_path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    let model = Arc::new(AprilModel::new(model_path).expect("failed to load stub model"));
+    let language = model.get_model_language().unwrap().to_owned();
+    let rate = model.get_sample_rate();
+
+    let limits = Limits {
+        max_event_bytes: MAX_LENGTH * 4,
+        ..Limits::default()
+    };
+    let server = WyomingServer::bind("127.0.0.1:0", model, SessionLimiter::new(1), limits).unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    let mut replies = BufReader::new(client.try_clone().unwrap());
+
+    WyomingEvent::new("describe", json!({}))
+        .write_to(&mut client)
+        .unwrap();
+    let info = WyomingEvent::read_from(&mut replies, MAX_LENGTH)
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.event_type, "info");
+    assert_eq!(
+        info.data["asr"][0]["models"][0]["languages"],
+        json!([language])
+    );
+
+    let format = json!({"rate": rate, "width": 2, "channels": 1});
+    WyomingEvent::new("transcribe", json!({"language": language}))
+        .write_to(&mut client)
+        .unwrap();
+    WyomingEvent::new("audio-start", format.clone())
+        .write_to(&mut client)
+        .unwrap();
+    let chunk: Vec<u8> = 1000i16.to_le_bytes().repeat(MAX_LENGTH);
+    // one second of sound, in chunks of 64ms
+    for _ in 0..rate / MAX_LENGTH {
+        WyomingEvent::new("audio-chunk", format.clone())
+            .with_payload(chunk.clone())
+            .write_to(&mut client)
+            .unwrap();
+    }
+    WyomingEvent::new("audio-chunk", format.clone())
+        .with_payload(chunk[..rate % MAX_LENGTH * 2].to_vec())
+        .write_to(&mut client)
+        .unwrap();
+    WyomingEvent::new("audio-stop", json!({}))
+        .write_to(&mut client)
+        .unwrap();
+
+    let transcript = WyomingEvent::read_from(&mut replies, MAX_LENGTH)
+        .unwrap()
+        .unwrap();
+    assert_eq!(transcript.event_type, "transcript");
+    assert_eq!(transcript.data["text"], "ONE TWO.");
+
+    // a mismatched sample rate is reported instead of silently producing garbage
+    WyomingEvent::new(
+        "audio-start",
+        json!({"rate": rate + 1, "width": 2, "channels": 1}),
+    )
+    .write_to(&mut client)
+    .unwrap();
+    let error = WyomingEvent::read_from(&mut replies, MAX_LENGTH)
+        .unwrap()
+        .unwrap();
+    assert_eq!(error.event_type, "error");
+
+    // an oversized event is answered with an error, and ends the connection
+    WyomingEvent::new("audio-chunk", format)
+        .with_payload(vec![0; MAX_LENGTH * 4 + 2])
+        .write_to(&mut client)
+        .unwrap();
+    let error = WyomingEvent::read_from(&mut replies, MAX_LENGTH)
+        .unwrap()
+        .unwrap();
+    assert_eq!(error.event_type, "error");
+    assert!(WyomingEvent::read_from(&mut replies, MAX_LENGTH)
+        .unwrap()
+        .is_none());
+}
@@ -1,5 +1,7 @@
 use crate::april_result_type::AprilResultType;
 use crate::april_token::{AprilToken, AprilTokenFlags, AprilTokens};
+use crate::metrics::MetricsHandle;
+use crate::recording::Recorder;
 use std::ffi::{c_void, CStr};
 use std::marker::PhantomData;
 
@@ -8,6 +10,7 @@ pub type AprilHandlerCallback<D> = Box<dyn FnMut(&D, AprilResultType, AprilToken
 pub struct AprilConfig<D: Sized + Send + Sync> {
     ptr: april_asr_rs_sys::AprilConfig,
     internal_safe_user_data_ptr: *mut c_void,
+    recorder: Option<Recorder>,
     phantom_type: PhantomData<D>,
 }
 
@@ -45,12 +48,32 @@ impl<D: Sized + Send + Sync> AprilConfig<D> {
         Self {
             ptr,
             internal_safe_user_data_ptr,
+            recorder: None,
             phantom_type: PhantomData,
         }
     }
 
     // setting speaker field is unimplemented as docs state 'Currently not implemented, has no effect.'
 
+    /// Set the flags sessions created from this config run with. Defaults to none, which makes
+    /// sessions synchronous.
+    pub fn set_flags(&mut self, flags: AprilConfigFlags) {
+        self.ptr.flags = flags.bits() as _;
+    }
+
+    /// Record everything sessions created from this config are fed, and the results they
+    /// emit, to replay them later with [`crate::Recording`]. Results are only recorded with a
+    /// handler set through [`Self::set_handler_fn`].
+    ///
+    /// The recorder is kept by [`crate::AprilModel::create_session`], not by [`Self::into_raw`].
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub(crate) fn take_recorder(&mut self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
     /// Set callback handler for April to call. Unsafe variant, see [`Self::set_handler_fn`] for safe variant.
     /// Calling this function clears any prior state automatically.
     ///
@@ -102,6 +125,15 @@ impl<D: Sized + Send + Sync> AprilConfig<D> {
 
             // Convert the result type
             let result_type_rusty = AprilResultType::from(result_type);
+            span!(
+                TRACE,
+                "april_callback",
+                result = %result_type_rusty,
+                tokens = num_tokens
+            );
+            if result_type_rusty == AprilResultType::ErrorCantKeepUp {
+                event!(WARN, "april can't keep up, audio was dropped");
+            }
 
             // Turn the tokens ptr we got into a tokens vec
             let tokens = if tokens.is_null() {
@@ -130,11 +162,14 @@ impl<D: Sized + Send + Sync> AprilConfig<D> {
                 tokens
             };
 
-            (rusty_user_data.callback)(
-                &rusty_user_data.data,
-                result_type_rusty,
-                AprilTokens(tokens),
-            );
+            let tokens = AprilTokens(tokens);
+            if let Some(metrics) = &rusty_user_data.metrics {
+                metrics.result(result_type_rusty);
+            }
+            if let Some(recorder) = &rusty_user_data.recorder {
+                recorder.record_result(result_type_rusty, &tokens);
+            }
+            (rusty_user_data.callback)(&rusty_user_data.data, result_type_rusty, tokens);
         }
 
         // Box the user's fn handler
@@ -143,6 +178,8 @@ impl<D: Sized + Send + Sync> AprilConfig<D> {
         let boxed_data_struct = Box::new(AprilInnerCallbackData {
             callback: fn_handler,
             data,
+            recorder: None,
+            metrics: None,
         });
         // Convert that boxed data into a raw *mut c_void ptr
         let raw_data_ptr = Box::into_raw(boxed_data_struct) as *mut c_void;
@@ -179,12 +216,14 @@ impl<D: Sized + Send + Sync> Default for AprilConfig<D> {
                 flags: 0,
             },
             internal_safe_user_data_ptr: std::ptr::null_mut(),
+            recorder: None,
             phantom_type: PhantomData,
         }
     }
 }
 
 bitflags::bitflags! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub struct AprilConfigFlags: i32 {
         /// If set, the input audio should be fed in realtime (1 second of audio per second)
         /// in small chunks.
@@ -230,7 +269,27 @@ pub(crate) unsafe fn clean_up_user_data<D: Sized + Send + Sync>(user_data: *mut
     }
 }
 
+/// Make the trampoline report every result to the session's `metrics` and `recorder`.
+///
+/// # Safety
+/// `user_data` must be null or obtained from [`AprilConfig::into_raw`], and not in use by april
+/// yet.
+pub(crate) unsafe fn attach_observers<D: Sized + Send + Sync>(
+    user_data: *mut c_void,
+    metrics: MetricsHandle,
+    recorder: Option<Recorder>,
+) {
+    if !user_data.is_null() {
+        // SAFETY: the caller guarantees nothing else accesses the data yet
+        let inner_data = unsafe { &mut *(user_data as *mut AprilInnerCallbackData<D>) };
+        inner_data.metrics = Some(metrics);
+        inner_data.recorder = recorder;
+    }
+}
+
 struct AprilInnerCallbackData<D: Sized + Send + Sync> {
     callback: AprilHandlerCallback<D>,
     data: D,
+    recorder: Option<Recorder>,
+    metrics: Option<MetricsHandle>,
 }
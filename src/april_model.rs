@@ -1,53 +1,77 @@
 use crate::april_config::AprilConfig;
+use crate::april_config::AprilConfigFlags;
 use crate::april_session::AprilSession;
 use crate::error::{Error, Result};
-use std::ffi::{CStr, CString};
+use crate::metrics::MetricsHandle;
+use std::ffi::{c_char, CStr, CString};
+use std::path::{Path, PathBuf};
 
 pub struct AprilModel {
     ptr: april_asr_rs_sys::AprilASRModel,
 }
 
 macro_rules! null_ptr_error {
-    ($ptr: expr) => {
+    ($ptr: expr, $call: literal) => {
         if $ptr.is_null() {
-            return Err(Error::NullPtr);
+            return Err(Error::NullPtr { call: $call });
         }
     };
 }
 
+/// Read a string returned by april from `call`.
+///
+/// # Safety
+/// `ptr` must be a nul-terminated string living as long as `'a`.
+unsafe fn april_str<'a>(ptr: *const c_char, call: &'static str) -> Result<&'a str> {
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|source| Error::InvalidUtf8 { call, source })
+}
+
 impl AprilModel {
+    /// Load the model at `path`.
+    ///
+    /// The file is checked first, so that a missing or unreadable model is reported as
+    /// [`Error::ModelFile`] instead of as a generic april failure.
     pub fn new(path: impl Into<Vec<u8>>) -> Result<Self> {
-        crate::do_init();
+        crate::do_init()?;
 
         Self::_new(CString::new(path)?)
     }
 
     fn _new(path: CString) -> Result<Self> {
+        let model_path = bytes_to_path(path.as_bytes());
+        span!(DEBUG, "aam_create_model", path = %model_path.display());
+        check_model_file(&model_path)?;
+
         let res = unsafe { april_asr_rs_sys::aam_create_model(path.as_ptr()) };
-        null_ptr_error!(res);
+        if res.is_null() {
+            event!(ERROR, "april failed to load the model");
+            return Err(Error::ModelLoad { path: model_path });
+        }
 
         Ok(Self { ptr: res })
     }
 
     pub fn get_model_name(&self) -> Result<&str> {
         let name_ptr = unsafe { april_asr_rs_sys::aam_get_name(self.ptr) };
-        null_ptr_error!(name_ptr);
+        null_ptr_error!(name_ptr, "aam_get_name");
 
-        Ok(unsafe { CStr::from_ptr(name_ptr) }.to_str()?)
+        unsafe { april_str(name_ptr, "aam_get_name") }
     }
 
     pub fn get_model_description(&self) -> Result<&str> {
         let name_ptr = unsafe { april_asr_rs_sys::aam_get_description(self.ptr) };
-        null_ptr_error!(name_ptr);
+        null_ptr_error!(name_ptr, "aam_get_description");
 
-        Ok(unsafe { CStr::from_ptr(name_ptr) }.to_str()?)
+        unsafe { april_str(name_ptr, "aam_get_description") }
     }
 
     pub fn get_model_language(&self) -> Result<&str> {
         let name_ptr = unsafe { april_asr_rs_sys::aam_get_language(self.ptr) };
-        null_ptr_error!(name_ptr);
+        null_ptr_error!(name_ptr, "aam_get_language");
 
-        Ok(unsafe { CStr::from_ptr(name_ptr) }.to_str()?)
+        unsafe { april_str(name_ptr, "aam_get_language") }
     }
 
     pub fn get_sample_rate(&self) -> usize {
@@ -56,14 +80,83 @@ impl AprilModel {
 
     pub fn create_session<D: Sized + Send + Sync>(
         &self,
-        config: AprilConfig<D>,
-    ) -> Result<AprilSession<D>> {
+        mut config: AprilConfig<D>,
+    ) -> Result<AprilSession<'_, D>> {
+        let recorder = config.take_recorder();
         let (raw_cfg, user_data_ptr) = config.into_raw();
+        let flags = AprilConfigFlags::from_bits_retain(raw_cfg.flags as i32);
+        span!(
+            DEBUG,
+            "aas_create_session",
+            ?flags,
+            recording = recorder.is_some()
+        );
+        let metrics = MetricsHandle::new(self.get_sample_rate());
+        let recorder = recorder.map(|recorder| recorder.record_session(flags));
+        // SAFETY: user_data_ptr comes from into_raw, and april doesn't have it yet
+        unsafe {
+            crate::april_config::attach_observers::<D>(
+                user_data_ptr,
+                metrics.clone(),
+                recorder.clone(),
+            )
+        };
         let raw_session = unsafe { april_asr_rs_sys::aas_create_session(self.ptr, raw_cfg) };
-        AprilSession::new(raw_session, user_data_ptr)
+        AprilSession::new(
+            raw_session,
+            user_data_ptr,
+            flags,
+            self.get_sample_rate(),
+            metrics,
+            recorder,
+        )
+    }
+
+    /// Check that audio at `sample_rate` can be fed to sessions of this model as it is.
+    pub fn check_sample_rate(&self, sample_rate: usize) -> Result<()> {
+        let expected = self.get_sample_rate();
+        if sample_rate == expected {
+            Ok(())
+        } else {
+            Err(Error::SampleRate {
+                expected,
+                actual: sample_rate,
+            })
+        }
+    }
+}
+
+fn check_model_file(path: &Path) -> Result<()> {
+    let file_error = |source| Error::ModelFile {
+        path: path.to_owned(),
+        source,
+    };
+    if path.is_dir() {
+        return Err(file_error(std::io::Error::new(
+            std::io::ErrorKind::IsADirectory,
+            "is a directory",
+        )));
     }
+    std::fs::File::open(path).map_err(file_error)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
 }
 
+// SAFETY: a model is never mutated by april after it has been loaded, and april itself shares
+// one model between sessions running on different threads.
+unsafe impl Send for AprilModel {}
+unsafe impl Sync for AprilModel {}
+
 impl Drop for AprilModel {
     fn drop(&mut self) {
         unsafe { april_asr_rs_sys::aam_free(self.ptr) }
@@ -1,11 +1,20 @@
+use crate::april_config::AprilConfigFlags;
 use crate::april_model::AprilModel;
+use crate::codec::wav::Wav;
 use crate::error::{Error, Result};
+use crate::metrics::{MetricsHandle, SessionMetrics};
+use crate::recording::Recorder;
 use std::ffi::c_void;
 use std::marker::PhantomData;
+use std::time::Instant;
 
 pub struct AprilSession<'a, D: Sized + Send + Sync> {
     ptr: april_asr_rs_sys::AprilASRSession,
     user_data_ptr: *mut c_void,
+    flags: AprilConfigFlags,
+    sample_rate: usize,
+    metrics: MetricsHandle,
+    recorder: Option<Recorder>,
     phantom_model: PhantomData<&'a AprilModel>,
     phantom_type: PhantomData<D>,
 }
@@ -14,13 +23,25 @@ impl<'a, D: Sized + Send + Sync> AprilSession<'a, D> {
     pub(crate) fn new(
         ptr: april_asr_rs_sys::AprilASRSession,
         user_data_ptr: *mut c_void,
+        flags: AprilConfigFlags,
+        sample_rate: usize,
+        metrics: MetricsHandle,
+        recorder: Option<Recorder>,
     ) -> Result<AprilSession<'a, D>> {
         if ptr.is_null() {
-            Err(Error::NullPtr)
+            // SAFETY: april didn't take the user data, so it's still ours to clean up
+            unsafe { crate::april_config::clean_up_user_data::<D>(user_data_ptr) };
+            Err(Error::NullPtr {
+                call: "aas_create_session",
+            })
         } else {
             Ok(Self {
                 ptr,
                 user_data_ptr,
+                flags,
+                sample_rate,
+                metrics,
+                recorder,
                 phantom_model: PhantomData,
                 phantom_type: PhantomData,
             })
@@ -31,25 +52,80 @@ impl<'a, D: Sized + Send + Sync> AprilSession<'a, D> {
         if pcm.is_empty() {
             return;
         }
+        span!(TRACE, "aas_feed_pcm16", samples = pcm.len());
+        if let Some(recorder) = &self.recorder {
+            recorder.record_audio(pcm);
+        }
+        self.metrics.feeding(pcm.len());
 
+        let start = Instant::now();
         // SAFETY: self.ptr is a valid pointer to a AprilSession
         // pcm is a valid array of c_shorts with pcm.len() elements
         unsafe { april_asr_rs_sys::aas_feed_pcm16(self.ptr, pcm.as_mut_ptr(), pcm.len() as _) }
+        self.metrics.processed(start.elapsed());
+    }
+
+    /// Feed decoded WAV audio, which must be at the model's sample rate.
+    pub fn feed_wav(&mut self, wav: &mut Wav) -> Result<()> {
+        if wav.sample_rate as usize != self.sample_rate {
+            return Err(Error::SampleRate {
+                expected: self.sample_rate,
+                actual: wav.sample_rate as usize,
+            });
+        }
+        self.feed_pcm16(&mut wav.samples);
+        Ok(())
     }
 
     pub fn flush(&mut self) {
+        span!(TRACE, "aas_flush");
+        if let Some(recorder) = &self.recorder {
+            recorder.record_flush();
+        }
+        let start = Instant::now();
         unsafe { april_asr_rs_sys::aas_flush(self.ptr) }
+        self.metrics.processed(start.elapsed());
+    }
+
+    /// Audio fed, processing time and result latencies of this session so far.
+    pub fn metrics(&self) -> SessionMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// A handle to [`Self::metrics`] that can be read from other threads, and outlives the
+    /// session.
+    pub fn metrics_handle(&self) -> MetricsHandle {
+        self.metrics.clone()
+    }
+
+    /// How much faster than realtime the session runs, only known with
+    /// [`AprilConfigFlags::ASYNC_RT`]. Below 1.0, accuracy is being traded for speed.
+    pub fn get_realtime_speedup(&self) -> Result<f32> {
+        if !self.flags.contains(AprilConfigFlags::ASYNC_RT) {
+            return Err(Error::SessionMode {
+                operation: "get_realtime_speedup",
+                flags: self.flags,
+            });
+        }
+        Ok(unsafe { april_asr_rs_sys::aas_realtime_get_speedup(self.ptr) })
+    }
+
+    pub fn flags(&self) -> AprilConfigFlags {
+        self.flags
     }
 
-    pub fn get_realtime_speedup(&self) -> f32 {
-        unsafe { april_asr_rs_sys::aas_realtime_get_speedup(self.ptr) }
+    /// Sample rate of the model, which audio must be fed at.
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
     }
 }
 
 impl<D: Sized + Send + Sync> Drop for AprilSession<'_, D> {
     fn drop(&mut self) {
+        span!(DEBUG, "aas_free");
         // Run april cleanup
         unsafe { april_asr_rs_sys::aas_free(self.ptr) }
+        self.metrics.finished();
 
         // After we've destroyed everything coming from April, we can safely destroy our data now
         // SAFETY: this ptr was passed in from a Box::<T>::into_raw call, or it was originally a nullptr,
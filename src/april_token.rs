@@ -3,6 +3,48 @@ use std::fmt::{Debug, Formatter};
 
 #[derive(Debug, Clone)]
 pub struct AprilTokens<'a>(pub Vec<AprilToken<'a>>);
+
+impl AprilTokens<'_> {
+    /// Group the tokens into words, using [`AprilTokenFlags::WORD_BOUNDARY`] to find where each word starts.
+    ///
+    /// Tokens before the first word boundary are treated as their own word.
+    pub fn words(&self) -> Vec<AprilWord> {
+        let mut words: Vec<AprilWord> = Vec::new();
+        for token in &self.0 {
+            match words.last_mut() {
+                Some(word) if !token.flag_bits.contains(AprilTokenFlags::WORD_BOUNDARY) => {
+                    word.text.push_str(&token.token);
+                    word.logprob += token.logprob;
+                    word.end_ms = token.time_ms;
+                }
+                _ => words.push(AprilWord {
+                    text: token.token.trim_start().to_owned(),
+                    logprob: token.logprob,
+                    start_ms: token.time_ms,
+                    end_ms: token.time_ms,
+                }),
+            }
+        }
+        words
+    }
+
+    /// Copy the token text out of april's buffers, so the tokens can outlive the callback.
+    pub fn into_owned(self) -> AprilTokens<'static> {
+        AprilTokens(
+            self.0
+                .into_iter()
+                .map(|token| {
+                    AprilToken::new(
+                        Cow::Owned(token.token.into_owned()),
+                        token.logprob,
+                        token.flag_bits,
+                        token.time_ms,
+                    )
+                })
+                .collect(),
+        )
+    }
+}
 impl std::fmt::Display for AprilTokens<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         for token in &self.0 {
@@ -43,6 +85,19 @@ impl<'a> AprilToken<'a> {
     }
 }
 
+/// A word made up of one or more consecutive tokens. See [`AprilTokens::words`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AprilWord {
+    /// The word text, without the leading space of its first token
+    pub text: String,
+    /// Sum of the log probabilities of the tokens in this word
+    pub logprob: f32,
+    /// Time of the first token in this word
+    pub start_ms: usize,
+    /// Time of the last token in this word
+    pub end_ms: usize,
+}
+
 bitflags::bitflags! {
     #[derive(Copy, Clone, Debug)]
     pub struct AprilTokenFlags: u32 {
@@ -57,3 +112,48 @@ bitflags::bitflags! {
         const SENTENCE_END = 0x00000002;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(text: &'static str, flags: AprilTokenFlags, time_ms: usize) -> AprilToken<'static> {
+        AprilToken::new(Cow::Borrowed(text), -0.5, flags, time_ms)
+    }
+
+    #[test]
+    fn words_group_tokens_at_boundaries() {
+        let tokens = AprilTokens(vec![
+            token("'S", AprilTokenFlags::EMPTY, 0),
+            token(" HEL", AprilTokenFlags::WORD_BOUNDARY, 100),
+            token("LO", AprilTokenFlags::EMPTY, 200),
+            token(" THERE", AprilTokenFlags::WORD_BOUNDARY, 300),
+            token(".", AprilTokenFlags::SENTENCE_END, 400),
+        ]);
+        assert_eq!(tokens.to_string(), "'S HELLO THERE.");
+        assert_eq!(
+            tokens.words(),
+            [
+                AprilWord {
+                    text: "'S".into(),
+                    logprob: -0.5,
+                    start_ms: 0,
+                    end_ms: 0,
+                },
+                AprilWord {
+                    text: "HELLO".into(),
+                    logprob: -1.0,
+                    start_ms: 100,
+                    end_ms: 200,
+                },
+                AprilWord {
+                    text: "THERE.".into(),
+                    logprob: -1.0,
+                    start_ms: 300,
+                    end_ms: 400,
+                },
+            ]
+        );
+        assert!(AprilTokens(Vec::new()).words().is_empty());
+    }
+}
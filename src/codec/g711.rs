@@ -0,0 +1,136 @@
+//! G.711 µ-law and A-law, as described in ITU-T G.711. Both are 8kHz and stateless.
+
+use super::Decoder;
+
+/// Decode a single µ-law byte into a linear pcm16 sample.
+pub fn ulaw_to_linear(byte: u8) -> i16 {
+    let byte = !byte;
+    let magnitude = ((((byte & 0x0f) as i16) << 3) + 0x84) << ((byte & 0x70) >> 4);
+    if byte & 0x80 != 0 {
+        0x84 - magnitude
+    } else {
+        magnitude - 0x84
+    }
+}
+
+/// Decode a single A-law byte into a linear pcm16 sample.
+pub fn alaw_to_linear(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let segment = (byte & 0x70) >> 4;
+    let mut magnitude = ((byte & 0x0f) as i16) << 4;
+    match segment {
+        0 => magnitude += 0x8,
+        1 => magnitude += 0x108,
+        _ => magnitude = (magnitude + 0x108) << (segment - 1),
+    }
+    if byte & 0x80 != 0 {
+        magnitude
+    } else {
+        -magnitude
+    }
+}
+
+/// Decode a buffer of µ-law bytes, one sample per byte.
+pub fn decode_ulaw(bytes: &[u8]) -> Vec<i16> {
+    bytes.iter().copied().map(ulaw_to_linear).collect()
+}
+
+/// Decode a buffer of A-law bytes, one sample per byte.
+pub fn decode_alaw(bytes: &[u8]) -> Vec<i16> {
+    bytes.iter().copied().map(alaw_to_linear).collect()
+}
+
+/// Which G.711 companding law a [`G711Decoder`] expands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum G711Law {
+    Ulaw,
+    Alaw,
+}
+
+/// [`Decoder`] for G.711. There is no state to carry between chunks, this only exists so
+/// G.711 can be used wherever the stateful codecs are.
+#[derive(Debug, Clone)]
+pub struct G711Decoder {
+    law: G711Law,
+}
+
+impl G711Decoder {
+    pub fn new(law: G711Law) -> Self {
+        Self { law }
+    }
+
+    pub fn ulaw() -> Self {
+        Self::new(G711Law::Ulaw)
+    }
+
+    pub fn alaw() -> Self {
+        Self::new(G711Law::Alaw)
+    }
+}
+
+impl Decoder for G711Decoder {
+    fn sample_rate(&self) -> usize {
+        8000
+    }
+
+    fn decode_into(&mut self, input: &[u8], out: &mut Vec<i16>) {
+        let expand = match self.law {
+            G711Law::Ulaw => ulaw_to_linear,
+            G711Law::Alaw => alaw_to_linear,
+        };
+        out.extend(input.iter().copied().map(expand));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ulaw_vectors() {
+        // from the reference tables of ITU-T G.711 and Sun's g711.c
+        let vectors = [
+            (0x00, -32124),
+            (0x0f, -16764),
+            (0x7e, -8),
+            (0x7f, 0),
+            (0x80, 32124),
+            (0xef, 132),
+            (0xfe, 8),
+            (0xff, 0),
+        ];
+        for (byte, linear) in vectors {
+            assert_eq!(ulaw_to_linear(byte), linear, "µ-law {:#04x}", byte);
+        }
+        for byte in 0..=0x7f {
+            assert_eq!(ulaw_to_linear(byte), -ulaw_to_linear(byte | 0x80));
+        }
+    }
+
+    #[test]
+    fn alaw_vectors() {
+        let vectors = [
+            (0x55, -8),
+            (0xd5, 8),
+            (0x54, -24),
+            (0x2a, -32256),
+            (0xaa, 32256),
+            (0x80, 5504),
+            (0x00, -5504),
+        ];
+        for (byte, linear) in vectors {
+            assert_eq!(alaw_to_linear(byte), linear, "A-law {:#04x}", byte);
+        }
+        for byte in 0..=0x7f {
+            assert_eq!(alaw_to_linear(byte), -alaw_to_linear(byte | 0x80));
+        }
+        assert_eq!(
+            decode_alaw(&[0xd5, 0x55]),
+            G711Decoder::alaw().decode(&[0xd5, 0x55])
+        );
+        assert_eq!(
+            decode_ulaw(&[0x80, 0xef]),
+            G711Decoder::ulaw().decode(&[0x80, 0xef])
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Decoders turning telephony audio into the pcm16 expected by [`crate::AprilSession::feed_pcm16`].
+
+mod adpcm;
+mod g711;
+mod g722;
+mod resample;
+pub mod wav;
+
+pub use adpcm::{ImaAdpcmDecoder, ImaLayout, MsAdpcmDecoder};
+pub use g711::{alaw_to_linear, decode_alaw, decode_ulaw, ulaw_to_linear, G711Decoder, G711Law};
+pub use g722::G722Decoder;
+pub use resample::Resampler;
+
+use crate::AprilModel;
+
+/// A streaming decoder from encoded bytes to mono pcm16.
+///
+/// Decoders keep whatever state their codec needs between calls, so a stream can be fed in
+/// chunks of any size, split anywhere.
+pub trait Decoder {
+    /// Sample rate of the decoded audio.
+    fn sample_rate(&self) -> usize;
+
+    /// Decode the next chunk of the stream, appending the samples to `out`.
+    fn decode_into(&mut self, input: &[u8], out: &mut Vec<i16>);
+
+    /// Decode anything still buffered at the end of the stream, such as a short final block.
+    fn finish_into(&mut self, _out: &mut Vec<i16>) {}
+
+    /// Decode the next chunk of the stream.
+    fn decode(&mut self, input: &[u8]) -> Vec<i16> {
+        let mut out = Vec::new();
+        self.decode_into(input, &mut out);
+        out
+    }
+}
+
+impl<D: Decoder + ?Sized> Decoder for Box<D> {
+    fn sample_rate(&self) -> usize {
+        (**self).sample_rate()
+    }
+
+    fn decode_into(&mut self, input: &[u8], out: &mut Vec<i16>) {
+        (**self).decode_into(input, out)
+    }
+
+    fn finish_into(&mut self, out: &mut Vec<i16>) {
+        (**self).finish_into(out)
+    }
+}
+
+/// A [`Decoder`] followed by a [`Resampler`], producing audio at the rate a model expects.
+#[derive(Debug, Clone)]
+pub struct Transcoder<D> {
+    decoder: D,
+    resampler: Resampler,
+    decoded: Vec<i16>,
+}
+
+impl<D: Decoder> Transcoder<D> {
+    /// # Panics
+    /// Panics if either the decoder's or the target sample rate is zero.
+    pub fn new(decoder: D, sample_rate: usize) -> Self {
+        Self {
+            resampler: Resampler::new(decoder.sample_rate(), sample_rate),
+            decoder,
+            decoded: Vec::new(),
+        }
+    }
+
+    /// Transcode to the sample rate of `model`.
+    pub fn for_model(decoder: D, model: &AprilModel) -> Self {
+        Self::new(decoder, model.get_sample_rate())
+    }
+
+    /// Transcode the next chunk of the stream, appending the samples to `out`.
+    pub fn transcode_into(&mut self, input: &[u8], out: &mut Vec<i16>) {
+        self.decoded.clear();
+        self.decoder.decode_into(input, &mut self.decoded);
+        self.resampler.process_into(&self.decoded, out);
+    }
+
+    /// Transcode the next chunk of the stream.
+    pub fn transcode(&mut self, input: &[u8]) -> Vec<i16> {
+        let mut out = Vec::new();
+        self.transcode_into(input, &mut out);
+        out
+    }
+
+    /// Transcode anything the decoder still buffers at the end of the stream.
+    pub fn finish(&mut self) -> Vec<i16> {
+        self.decoded.clear();
+        self.decoder.finish_into(&mut self.decoded);
+        self.resampler.process(&self.decoded)
+    }
+
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    pub fn into_decoder(self) -> D {
+        self.decoder
+    }
+}
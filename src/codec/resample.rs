@@ -0,0 +1,106 @@
+/// Streaming sample rate converter using linear interpolation.
+///
+/// Keeps the last sample of each chunk around, so audio can be converted chunk by chunk
+/// without discontinuities at the chunk boundaries.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    from_rate: u64,
+    to_rate: u64,
+    /// Position of the next output sample, in units of `1 / to_rate` input samples,
+    /// counted from `last` (position 0) into the next chunk (position `to_rate` onwards).
+    position: u64,
+    last: i16,
+}
+
+impl Resampler {
+    /// # Panics
+    /// Panics if either rate is zero.
+    pub fn new(from_rate: usize, to_rate: usize) -> Self {
+        assert!(
+            from_rate > 0 && to_rate > 0,
+            "sample rates must be non-zero"
+        );
+        Self {
+            from_rate: from_rate as u64,
+            to_rate: to_rate as u64,
+            // start exactly on the first sample of the first chunk
+            position: to_rate as u64,
+            last: 0,
+        }
+    }
+
+    /// Convert the next chunk of audio, appending the converted samples to `out`.
+    pub fn process_into(&mut self, input: &[i16], out: &mut Vec<i16>) {
+        if self.from_rate == self.to_rate {
+            out.extend_from_slice(input);
+            return;
+        }
+
+        let len = input.len() as u64;
+        while self.position / self.to_rate < len {
+            let index = (self.position / self.to_rate) as usize;
+            let fraction = (self.position % self.to_rate) as i64;
+            let a = if index == 0 {
+                self.last
+            } else {
+                input[index - 1]
+            } as i64;
+            let b = input[index] as i64;
+            out.push((a + (b - a) * fraction / self.to_rate as i64) as i16);
+            self.position += self.from_rate;
+        }
+
+        if let Some(last) = input.last() {
+            self.last = *last;
+            self.position -= len * self.to_rate;
+        }
+    }
+
+    /// Convert the next chunk of audio.
+    pub fn process(&mut self, input: &[i16]) -> Vec<i16> {
+        let mut out =
+            Vec::with_capacity(input.len() * self.to_rate as usize / self.from_rate as usize + 1);
+        self.process_into(input, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_length_follows_rate() {
+        for (from_rate, to_rate) in [(8000, 16000), (16000, 8000), (44100, 16000), (16000, 16000)] {
+            let mut resampler = Resampler::new(from_rate, to_rate);
+            let mut out = Vec::new();
+            // a second of audio in uneven chunks
+            for chunk in vec![1000; from_rate].chunks(333) {
+                resampler.process_into(chunk, &mut out);
+            }
+            // the last input sample is held back until the next chunk, when interpolating
+            let expected = to_rate as i64;
+            let slack = (to_rate / from_rate) as i64 + 1;
+            assert!(
+                (out.len() as i64 - expected).abs() <= slack,
+                "{} to {}: {} samples",
+                from_rate,
+                to_rate,
+                out.len()
+            );
+            assert!(out.iter().all(|sample| *sample == 1000));
+        }
+    }
+
+    #[test]
+    fn interpolates_across_chunks() {
+        let mut resampler = Resampler::new(8000, 16000);
+        let mut out = resampler.process(&[0, 100]);
+        out.extend(resampler.process(&[200, 300]));
+        assert_eq!(out, [0, 50, 100, 150, 200, 250]);
+
+        let mut resampler = Resampler::new(16000, 8000);
+        let out = resampler.process(&[0, 10, 20, 30, 40, 50]);
+        assert_eq!(out, [0, 20, 40]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Just enough of a WAV reader to accept uncompressed pcm16 audio.
+
+/// Decoded WAV audio, downmixed to mono.
+#[derive(Debug)]
+pub struct Wav {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+/// Parse a RIFF/WAVE file holding 16-bit PCM audio.
+/// Multichannel audio is downmixed to mono by averaging the channels.
+pub fn parse(bytes: &[u8]) -> Result<Wav, &'static str> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("not a RIFF/WAVE file");
+    }
+
+    let mut format = None;
+    let mut rest = &bytes[12..];
+    while rest.len() >= 8 {
+        let id = &rest[0..4];
+        let len = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let body = rest.get(8..8 + len).ok_or("truncated WAV chunk")?;
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err("truncated WAV fmt chunk");
+                }
+                let audio_format = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits_per_sample = u16::from_le_bytes([body[14], body[15]]);
+                // 0xFFFE is WAVE_FORMAT_EXTENSIBLE, which we accept as long as the samples are pcm16
+                if (audio_format != 1 && audio_format != 0xFFFE) || bits_per_sample != 16 {
+                    return Err("only 16-bit PCM WAV files are supported");
+                }
+                if channels == 0 {
+                    return Err("WAV file has no channels");
+                }
+                format = Some((channels, sample_rate));
+            }
+            b"data" => {
+                let (channels, sample_rate) = format.ok_or("WAV data chunk before fmt chunk")?;
+                return Ok(Wav {
+                    sample_rate,
+                    samples: downmix(body, channels as usize),
+                });
+            }
+            _ => {}
+        }
+        // chunks are padded to an even length
+        rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+    }
+    Err("WAV file has no data chunk")
+}
+
+/// Decode little-endian pcm16 bytes, averaging every `channels` interleaved samples into one.
+pub fn downmix(bytes: &[u8], channels: usize) -> Vec<i16> {
+    bytes
+        .chunks_exact(2 * channels)
+        .map(|frame| {
+            let sum: i32 = frame
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]) as i32)
+                .sum();
+            (sum / channels as i32) as i16
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A WAV file with the given fmt fields and pcm16 samples, and a chunk before the data to
+    /// skip.
+    fn wav(audio_format: u16, channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        bytes.extend(b"fmt \x10\0\0\0");
+        bytes.extend(audio_format.to_le_bytes());
+        bytes.extend(channels.to_le_bytes());
+        bytes.extend(sample_rate.to_le_bytes());
+        bytes.extend((sample_rate * channels as u32 * 2).to_le_bytes());
+        bytes.extend((channels * 2).to_le_bytes());
+        bytes.extend(16u16.to_le_bytes());
+        // odd-length chunks are padded
+        bytes.extend(b"LIST\x03\0\0\0abc\0");
+        bytes.extend(b"data");
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn parses_mono_and_downmixes_stereo() {
+        let mono = parse(&wav(1, 1, 16000, &[1, -2, 300])).unwrap();
+        assert_eq!(mono.sample_rate, 16000);
+        assert_eq!(mono.samples, [1, -2, 300]);
+
+        let stereo = parse(&wav(0xFFFE, 2, 8000, &[100, 300, -4, -6])).unwrap();
+        assert_eq!(stereo.sample_rate, 8000);
+        assert_eq!(stereo.samples, [200, -5]);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert_eq!(
+            parse(b"RIFF\0\0\0\0AVI ").unwrap_err(),
+            "not a RIFF/WAVE file"
+        );
+        assert_eq!(
+            parse(&wav(3, 1, 16000, &[0])).unwrap_err(),
+            "only 16-bit PCM WAV files are supported"
+        );
+        assert_eq!(
+            parse(&wav(1, 0, 16000, &[0])).unwrap_err(),
+            "WAV file has no channels"
+        );
+        let mut truncated = wav(1, 1, 16000, &[1, 2]);
+        truncated.pop();
+        assert_eq!(parse(&truncated).unwrap_err(), "truncated WAV chunk");
+        assert_eq!(
+            parse(b"RIFF\0\0\0\0WAVEdata\x02\0\0\0\0\0").unwrap_err(),
+            "WAV data chunk before fmt chunk"
+        );
+        assert_eq!(
+            parse(b"RIFF\0\0\0\0WAVE").unwrap_err(),
+            "WAV file has no data chunk"
+        );
+    }
+}
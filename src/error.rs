@@ -1,18 +1,49 @@
+use crate::april_config::AprilConfigFlags;
 use std::ffi::NulError;
 use std::fmt::Formatter;
+use std::path::PathBuf;
 use std::str::Utf8Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
+    /// A string passed to april contained a nul byte
     CString(NulError),
-    /// Rust bindings got a nullptr from the C side
-    NullPtr,
-    /// Invalid UTF-8 was detected in a string from April
-    InvalidUtf8(Utf8Error),
-    /// Empty audio buffer was passed to feed_pcm16
-    EmptyAudio,
+    /// April returned a nullptr from `call`
+    NullPtr { call: &'static str },
+    /// April returned a string from `call` that isn't valid UTF-8
+    InvalidUtf8 {
+        call: &'static str,
+        source: Utf8Error,
+    },
+    /// The model file is missing, unreadable or not a file, so april wasn't asked to load it
+    ModelFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// April failed to load a readable model file, most likely because it isn't a valid model
+    /// for this version of april
+    ModelLoad { path: PathBuf },
+    /// Audio was given at a sample rate other than the model's
+    SampleRate { expected: usize, actual: usize },
+    /// `operation` is not available in a session running with `flags`
+    SessionMode {
+        operation: &'static str,
+        flags: AprilConfigFlags,
+    },
+    /// April fell behind in a session with [`crate::KeepUpPolicy::Fail`]
+    CantKeepUp,
+    /// A session recording could not be written or read
+    Recording(std::io::Error),
+    /// A [`crate::Grammar`] pattern is malformed
+    Grammar { pattern: String, reason: String },
+    /// A [`crate::SessionWorker`] stopped, so it takes no more commands
+    WorkerStopped,
+    /// The april library could not be loaded at runtime, or wasn't loaded yet
+    #[cfg(feature = "dynamic")]
+    Library(april_asr_rs_sys::LoadError),
 }
 
 impl std::fmt::Display for Error {
@@ -21,16 +52,53 @@ impl std::fmt::Display for Error {
             Error::CString(e) => {
                 write!(f, "failed to get CString: {}", e)
             }
-            Error::NullPtr => f.write_str("got null ptr from april"),
-            Error::InvalidUtf8(e) => {
-                write!(f, "got invalid UTF-8 in a string from april: {}", e)
+            Error::NullPtr { call } => write!(f, "got null ptr from april in {}", call),
+            Error::InvalidUtf8 { call, source } => {
+                write!(f, "got invalid UTF-8 from april in {}: {}", call, source)
             }
-            Error::EmptyAudio => f.write_str("attempting to feed an empty audio sample to april"),
+            Error::ModelFile { path, source } => {
+                write!(f, "can't read model file {}: {}", path.display(), source)
+            }
+            Error::ModelLoad { path } => write!(
+                f,
+                "april failed to load model {}, it may be corrupt or for another april version",
+                path.display()
+            ),
+            Error::SampleRate { expected, actual } => write!(
+                f,
+                "audio is at {} Hz, but the model expects {} Hz",
+                actual, expected
+            ),
+            Error::SessionMode { operation, flags } => write!(
+                f,
+                "{} is not available in a session with flags {:?}",
+                operation, flags
+            ),
+            Error::CantKeepUp => f.write_str("april can't keep up with the audio"),
+            Error::Recording(e) => write!(f, "session recording failed: {}", e),
+            Error::Grammar { pattern, reason } => {
+                write!(f, "invalid grammar pattern {:?}: {}", pattern, reason)
+            }
+            Error::WorkerStopped => f.write_str("the session worker has stopped"),
+            #[cfg(feature = "dynamic")]
+            Error::Library(e) => write!(f, "{}", e),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CString(e) => Some(e),
+            Error::InvalidUtf8 { source, .. } => Some(source),
+            Error::ModelFile { source, .. } => Some(source),
+            Error::Recording(e) => Some(e),
+            #[cfg(feature = "dynamic")]
+            Error::Library(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<NulError> for Error {
     fn from(err: NulError) -> Self {
@@ -38,8 +106,9 @@ impl From<NulError> for Error {
     }
 }
 
-impl From<Utf8Error> for Error {
-    fn from(err: Utf8Error) -> Self {
-        Self::InvalidUtf8(err)
+#[cfg(feature = "dynamic")]
+impl From<april_asr_rs_sys::LoadError> for Error {
+    fn from(err: april_asr_rs_sys::LoadError) -> Self {
+        Self::Library(err)
     }
 }
@@ -0,0 +1,489 @@
+use crate::april_token::AprilTokens;
+use crate::error::{Error, Result};
+use crate::hotword::normalize;
+use crate::numbers::parse_number;
+use std::collections::HashMap;
+
+/// The value of a slot in an [`IntentMatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotValue {
+    /// A `<name:number>` slot, parsed from digits or English number words
+    Number(i64),
+    /// A `<name>` slot holds the words it matched, a `<name:kind>` slot the value of `kind`
+    /// that matched, as given to [`Grammar::define_slot`]
+    Text(String),
+}
+
+/// A sentence matched to an intent of a [`Grammar`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntentMatch {
+    pub intent: String,
+    /// Slots in the order they appear in the pattern
+    pub slots: Vec<(String, SlotValue)>,
+    /// Characters that had to be corrected in fuzzily matched words, 0 for an exact match
+    pub errors: usize,
+}
+
+impl IntentMatch {
+    pub fn slot(&self, name: &str) -> Option<&SlotValue> {
+        self.slots
+            .iter()
+            .find(|(slot, _)| slot == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Matches recognized sentences to intents, for voice control.
+///
+/// Each intent has one or more patterns made of:
+/// - words, matched regardless of case and punctuation
+/// - `[optional words]`, which can also hold alternatives: `[the | a]`
+/// - `(alternative | words)`
+/// - `<name>` slots matching one or more words, `<name:number>` slots matching a number and
+///   `<name:kind>` slots matching a value defined with [`Grammar::define_slot`]
+///
+/// Unless disabled with [`Grammar::set_fuzzy`], words of 4 letters and more also match with a
+/// character wrong, missing or extra, and words of 8 letters and more with two, to tolerate
+/// recognition errors. The match with the fewest errors wins, then the intent added first.
+///
+/// ```
+/// use april_asr_rs::{Grammar, SlotValue};
+///
+/// let mut grammar = Grammar::new();
+/// grammar.define_slot("room", &["kitchen", "living room"]);
+/// grammar
+///     .add_intent("set_timer", "set [a | the] timer (for | to) <minutes:number> (minute | minutes)")
+///     .unwrap()
+///     .add_intent("lights_on", "(turn | switch) on [the] lights in [the] <room:room>")
+///     .unwrap();
+///
+/// let timer = grammar.match_words(&["SET", "A", "TIMER", "FOR", "TWENTY", "FIVE", "MINUTES"]);
+/// let timer = timer.unwrap();
+/// assert_eq!(timer.intent, "set_timer");
+/// assert_eq!(timer.slot("minutes"), Some(&SlotValue::Number(25)));
+///
+/// // "LIVIN" is close enough to "living"
+/// let lights = grammar.match_words(&["TURN", "ON", "LIGHTS", "IN", "THE", "LIVIN", "ROOM."]);
+/// let lights = lights.unwrap();
+/// assert_eq!(lights.slot("room"), Some(&SlotValue::Text("living room".to_owned())));
+/// assert_eq!(lights.errors, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Grammar {
+    intents: Vec<(String, Vec<Node>)>,
+    /// Values of each slot kind, with their normalized words
+    slot_values: HashMap<String, Vec<(String, Vec<String>)>>,
+    fuzzy: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Word(String),
+    Optional(Vec<Vec<Node>>),
+    Alternatives(Vec<Vec<Node>>),
+    Slot { name: String, kind: SlotKind },
+}
+
+#[derive(Debug, Clone)]
+enum SlotKind {
+    Words,
+    Number,
+    Values(String),
+}
+
+/// A way to match a prefix of the nodes of a pattern.
+#[derive(Debug, Clone, Default)]
+struct Progress {
+    /// Index of the next word to match
+    position: usize,
+    errors: usize,
+    slots: Vec<(String, SlotValue)>,
+}
+
+impl Default for Grammar {
+    fn default() -> Self {
+        Self {
+            intents: Vec::new(),
+            slot_values: HashMap::new(),
+            fuzzy: true,
+        }
+    }
+}
+
+impl Grammar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define the values a `<name:kind>` slot matches. Must come before adding patterns using
+    /// `kind`. Defining a kind again adds to its values.
+    pub fn define_slot(&mut self, kind: &str, values: &[&str]) -> &mut Self {
+        self.slot_values
+            .entry(kind.to_owned())
+            .or_default()
+            .extend(values.iter().map(|value| {
+                let words = value.split_whitespace().filter_map(normalize).collect();
+                (value.to_string(), words)
+            }));
+        self
+    }
+
+    /// Add a pattern for `intent`, which can have several. Fails if the pattern is malformed or
+    /// uses a slot kind not defined yet.
+    pub fn add_intent(&mut self, intent: &str, pattern: &str) -> Result<&mut Self> {
+        let fail = |reason: String| Error::Grammar {
+            pattern: pattern.to_owned(),
+            reason,
+        };
+        let mut parser = Parser {
+            rest: pattern.trim_start(),
+        };
+        let nodes = parser.sequence(&[]).map_err(fail)?;
+        if let Some(c) = parser.rest.chars().next() {
+            return Err(fail(format!("unexpected `{}`", c)));
+        }
+        if nodes.is_empty() {
+            return Err(fail("the pattern is empty".to_owned()));
+        }
+        self.check_slots(&nodes).map_err(fail)?;
+        self.intents.push((intent.to_owned(), nodes));
+        Ok(self)
+    }
+
+    pub fn set_fuzzy(&mut self, fuzzy: bool) -> &mut Self {
+        self.fuzzy = fuzzy;
+        self
+    }
+
+    /// Match the words of a final result.
+    pub fn match_tokens(&self, tokens: &AprilTokens) -> Option<IntentMatch> {
+        let words: Vec<_> = tokens.words().into_iter().map(|word| word.text).collect();
+        self.match_words(&words)
+    }
+
+    /// Match a sentence, given as words. Every word has to be matched.
+    pub fn match_words(&self, words: &[impl AsRef<str>]) -> Option<IntentMatch> {
+        let words: Vec<String> = words
+            .iter()
+            .filter_map(|word| normalize(word.as_ref()))
+            .collect();
+        let mut best: Option<IntentMatch> = None;
+        for (intent, nodes) in &self.intents {
+            let mut matches = Vec::new();
+            self.match_nodes(nodes, &words, Progress::default(), &mut matches);
+            let Some(progress) = matches
+                .into_iter()
+                .filter(|progress| progress.position == words.len())
+                .min_by_key(|progress| progress.errors)
+            else {
+                continue;
+            };
+            if best
+                .as_ref()
+                .is_none_or(|best| progress.errors < best.errors)
+            {
+                best = Some(IntentMatch {
+                    intent: intent.clone(),
+                    slots: progress.slots,
+                    errors: progress.errors,
+                });
+            }
+        }
+        best
+    }
+
+    fn check_slots(&self, nodes: &[Node]) -> std::result::Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Word(_) => {}
+                Node::Optional(alternatives) | Node::Alternatives(alternatives) => {
+                    for alternative in alternatives {
+                        self.check_slots(alternative)?;
+                    }
+                }
+                Node::Slot {
+                    kind: SlotKind::Values(kind),
+                    ..
+                } if !self.slot_values.contains_key(kind) => {
+                    return Err(format!("slot kind `{}` is not defined", kind));
+                }
+                Node::Slot { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Push every way of matching `nodes` from `progress` onto `out`.
+    fn match_nodes(
+        &self,
+        nodes: &[Node],
+        words: &[String],
+        progress: Progress,
+        out: &mut Vec<Progress>,
+    ) {
+        let Some((node, rest)) = nodes.split_first() else {
+            out.push(progress);
+            return;
+        };
+        match node {
+            Node::Word(expected) => {
+                let Some(word) = words.get(progress.position) else {
+                    return;
+                };
+                if let Some(errors) = self.word_errors(expected, word) {
+                    let progress = Progress {
+                        position: progress.position + 1,
+                        errors: progress.errors + errors,
+                        slots: progress.slots,
+                    };
+                    self.match_nodes(rest, words, progress, out);
+                }
+            }
+            Node::Optional(alternatives) | Node::Alternatives(alternatives) => {
+                let mut matched = Vec::new();
+                if matches!(node, Node::Optional(_)) {
+                    matched.push(progress.clone());
+                }
+                for alternative in alternatives {
+                    self.match_nodes(alternative, words, progress.clone(), &mut matched);
+                }
+                for progress in matched {
+                    self.match_nodes(rest, words, progress, out);
+                }
+            }
+            Node::Slot { name, kind } => {
+                for end in progress.position + 1..=words.len() {
+                    let candidate = &words[progress.position..end];
+                    let Some((value, errors)) = self.slot_value(kind, candidate) else {
+                        continue;
+                    };
+                    let mut slots = progress.slots.clone();
+                    slots.push((name.clone(), value));
+                    let progress = Progress {
+                        position: end,
+                        errors: progress.errors + errors,
+                        slots,
+                    };
+                    self.match_nodes(rest, words, progress, out);
+                }
+            }
+        }
+    }
+
+    fn slot_value(&self, kind: &SlotKind, words: &[String]) -> Option<(SlotValue, usize)> {
+        match kind {
+            SlotKind::Words => Some((SlotValue::Text(words.join(" ").to_lowercase()), 0)),
+            SlotKind::Number => {
+                let words: Vec<&str> = words.iter().map(String::as_str).collect();
+                parse_number(&words).map(|number| (SlotValue::Number(number), 0))
+            }
+            SlotKind::Values(kind) => self.slot_values[kind]
+                .iter()
+                .filter(|(_, value)| value.len() == words.len())
+                .filter_map(|(text, value)| {
+                    let errors = value
+                        .iter()
+                        .zip(words)
+                        .map(|(expected, word)| self.word_errors(expected, word))
+                        .sum::<Option<usize>>()?;
+                    Some((SlotValue::Text(text.clone()), errors))
+                })
+                .min_by_key(|(_, errors)| *errors),
+        }
+    }
+
+    /// Characters to correct in `word` to get `expected`, if few enough to accept it.
+    fn word_errors(&self, expected: &str, word: &str) -> Option<usize> {
+        if expected == word {
+            return Some(0);
+        }
+        if !self.fuzzy {
+            return None;
+        }
+        fuzzy_errors(expected, word)
+    }
+}
+
+/// Characters to correct in `word` to get `expected`, if few enough to tolerate as a
+/// recognition error: one in words of 4 letters and more, two in words of 8 letters and more.
+pub(crate) fn fuzzy_errors(expected: &str, word: &str) -> Option<usize> {
+    let allowed = expected.chars().count() / 4;
+    let distance = edit_distance(expected, word);
+    (distance <= allowed.min(2)).then_some(distance)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    /// Parse nodes until the end of the pattern or one of `terminators`, which is left unread.
+    fn sequence(&mut self, terminators: &[char]) -> std::result::Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.rest.chars().next() {
+            if terminators.contains(&c) {
+                break;
+            }
+            let node = match c {
+                '[' => Node::Optional(self.alternatives(']')?),
+                '(' => Node::Alternatives(self.alternatives(')')?),
+                '<' => self.slot()?,
+                ']' | ')' | '|' | '>' => return Err(format!("unexpected `{}`", c)),
+                _ => {
+                    let end = self
+                        .rest
+                        .find(|c: char| c.is_whitespace() || "[]()<>|".contains(c))
+                        .unwrap_or(self.rest.len());
+                    let word = &self.rest[..end];
+                    self.rest = &self.rest[end..];
+                    match normalize(word) {
+                        Some(word) => Node::Word(word),
+                        None => return Err(format!("`{}` is not a word", word)),
+                    }
+                }
+            };
+            nodes.push(node);
+            self.rest = self.rest.trim_start();
+        }
+        Ok(nodes)
+    }
+
+    /// Parse `|` separated alternatives after an opening bracket, up to `close`.
+    fn alternatives(&mut self, close: char) -> std::result::Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = Vec::new();
+        loop {
+            // skip the opening bracket or `|`
+            self.rest = self.rest[1..].trim_start();
+            let alternative = self.sequence(&['|', close])?;
+            if alternative.is_empty() {
+                return Err("empty alternative".to_owned());
+            }
+            alternatives.push(alternative);
+            match self.rest.chars().next() {
+                Some('|') => continue,
+                Some(c) if c == close => {
+                    self.rest = &self.rest[1..];
+                    return Ok(alternatives);
+                }
+                _ => return Err(format!("missing `{}`", close)),
+            }
+        }
+    }
+
+    fn slot(&mut self) -> std::result::Result<Node, String> {
+        let Some(end) = self.rest.find('>') else {
+            return Err("missing `>`".to_owned());
+        };
+        let slot = &self.rest[1..end];
+        self.rest = &self.rest[end + 1..];
+        let (name, kind) = match slot.split_once(':') {
+            Some((name, "number")) => (name, SlotKind::Number),
+            Some((name, kind)) => (name, SlotKind::Values(kind.trim().to_owned())),
+            None => (slot, SlotKind::Words),
+        };
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("invalid slot name `{}`", name));
+        }
+        Ok(Node::Slot {
+            name: name.to_owned(),
+            kind,
+        })
+    }
+}
+
+/// Levenshtein distance between two words, in characters.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_words() {
+        let mut grammar = Grammar::new();
+        grammar.define_slot("direction", &["up", "down"]);
+        grammar
+            .add_intent(
+                "timer",
+                "set [a] timer for <minutes:number> (minute | minutes)",
+            )
+            .unwrap()
+            .add_intent("volume", "turn [the] volume <direction:direction> [please]")
+            .unwrap()
+            .add_intent("say", "say <text>")
+            .unwrap();
+
+        let minutes = |sentence: &str| {
+            let words: Vec<_> = sentence.split(' ').collect();
+            let matched = grammar.match_words(&words)?;
+            assert_eq!(matched.intent, "timer");
+            match matched.slot("minutes") {
+                Some(SlotValue::Number(minutes)) => Some(*minutes),
+                _ => None,
+            }
+        };
+        assert_eq!(minutes("set timer for 90 minutes"), Some(90));
+        assert_eq!(minutes("set a timer for one minute"), Some(1));
+        assert_eq!(minutes("set a timer for twenty five minutes"), Some(25));
+        assert_eq!(
+            minutes("set a timer for a hundred and five minutes"),
+            Some(105)
+        );
+        assert_eq!(
+            minutes("set a timer for two thousand three hundred minutes"),
+            Some(2300)
+        );
+        assert_eq!(minutes("set a timer for five twenty minutes"), None);
+        assert_eq!(minutes("set a timer for minutes"), None);
+
+        let matched = grammar
+            .match_words(&["TURN", "VOLUME", "DOWN", "PLEASE."])
+            .unwrap();
+        assert_eq!(
+            matched.slot("direction"),
+            Some(&SlotValue::Text("down".to_owned()))
+        );
+        assert_eq!(matched.errors, 0);
+        // one letter off in "volume" and "please"
+        let matched = grammar
+            .match_words(&["TURN", "VOLUM", "UP", "PLEAS"])
+            .unwrap();
+        assert_eq!((matched.intent.as_str(), matched.errors), ("volume", 2));
+        // "up" is too short to be matched fuzzily
+        assert!(grammar.match_words(&["TURN", "VOLUME", "UB"]).is_none());
+        grammar.set_fuzzy(false);
+        assert!(grammar.match_words(&["TURN", "VOLUM", "UP"]).is_none());
+
+        let matched = grammar.match_words(&["SAY", "HELLO", "WORLD"]).unwrap();
+        assert_eq!(
+            matched.slot("text"),
+            Some(&SlotValue::Text("hello world".to_owned()))
+        );
+
+        for pattern in [
+            "",
+            "set (a | ) timer",
+            "set [a timer",
+            "<minutes:unknown>",
+            "a ] b",
+        ] {
+            assert!(matches!(
+                grammar.add_intent("broken", pattern),
+                Err(Error::Grammar { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::april_result_type::AprilResultType;
+use crate::april_token::{AprilTokens, AprilWord};
+use crate::recognizer::ResultHandler;
+use std::ops::RangeInclusive;
+
+/// A phrase for a [`HotwordSpotter`] to look for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotword {
+    /// The phrase as given to [`HotwordSpotter::add`]
+    pub phrase: String,
+    /// Occurrences whose logprob is lower than this are ignored
+    pub min_logprob: f32,
+    /// The normalized words of the phrase
+    words: Vec<String>,
+}
+
+/// One occurrence of a [`Hotword`] in the results of a session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotwordEvent {
+    /// Index of the hotword, in the order they were added
+    pub index: usize,
+    pub phrase: String,
+    /// Whether the occurrence was first seen in a partial or a final result
+    pub result: AprilResultType,
+    /// Sum of the log probabilities of the tokens of the occurrence
+    pub logprob: f32,
+    /// Time of the first token of the occurrence
+    pub start_ms: usize,
+    /// Time of the last token of the occurrence
+    pub end_ms: usize,
+}
+
+/// Spots hotwords, such as wake or command words, in the partial and final results of a
+/// session, so they are reported as soon as april first recognizes them.
+///
+/// Each occurrence is reported once: later partials of the same sentence revise the earlier
+/// ones, so an occurrence overlapping the time of one reported before in the sentence, give or
+/// take 100ms, isn't reported again, even if revisions dropped it in between. Matching ignores
+/// case and punctuation.
+///
+/// ```
+/// use april_asr_rs::HotwordSpotter;
+/// # use april_asr_rs::{AprilConfigFlags, Recognizer, RecognizerSession, ScriptedRecognizer};
+/// # let recognizer = ScriptedRecognizer::new(16000)
+/// #     .partial_at(500, "HEY APRIL")
+/// #     .final_at(1000, "HEY APRIL STOP.");
+///
+/// let mut spotter = HotwordSpotter::new();
+/// spotter.add("hey april", -5.0).add("stop", -2.0);
+/// let mut session = recognizer
+///     .new_session(
+///         AprilConfigFlags::empty(),
+///         spotter.handler(|event| println!("{} at {}ms", event.phrase, event.start_ms)),
+///     )
+///     .unwrap();
+/// # session.feed_pcm16(&mut [0; 16000]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HotwordSpotter {
+    hotwords: Vec<Hotword>,
+    /// Times of the occurrences of each hotword reported in the current sentence
+    reported: Vec<Vec<RangeInclusive<usize>>>,
+}
+
+impl HotwordSpotter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look for `phrase`, only reporting occurrences with a logprob of at least `min_logprob`.
+    /// The logprob of an occurrence sums those of its tokens, so longer phrases need lower
+    /// thresholds.
+    pub fn add(&mut self, phrase: &str, min_logprob: f32) -> &mut Self {
+        self.hotwords.push(Hotword {
+            phrase: phrase.to_owned(),
+            min_logprob,
+            words: phrase.split_whitespace().filter_map(normalize).collect(),
+        });
+        self.reported.push(Vec::new());
+        self
+    }
+
+    pub fn hotwords(&self) -> &[Hotword] {
+        &self.hotwords
+    }
+
+    /// Look for hotwords in a result, returning the occurrences not reported before.
+    pub fn process(&mut self, result: AprilResultType, tokens: &AprilTokens) -> Vec<HotwordEvent> {
+        let mut events = Vec::new();
+        if matches!(
+            result,
+            AprilResultType::RecognitionPartial | AprilResultType::RecognitionFinal
+        ) {
+            let words = tokens.words();
+            let words: Vec<(String, &AprilWord)> = words
+                .iter()
+                .filter_map(|word| Some((normalize(&word.text)?, word)))
+                .collect();
+            for (index, hotword) in self.hotwords.iter().enumerate() {
+                let occurrences = find(&hotword.words, &words)
+                    .into_iter()
+                    .filter(|occurrence| occurrence.logprob >= hotword.min_logprob);
+                for occurrence in occurrences {
+                    let span = occurrence.start_ms..=occurrence.end_ms;
+                    let reported = &mut self.reported[index];
+                    // revisions may move words a little, so the latest times are kept
+                    if let Some(seen) = reported.iter_mut().find(|seen| overlap(seen, &span)) {
+                        *seen = span;
+                        continue;
+                    }
+                    reported.push(span);
+                    events.push(HotwordEvent {
+                        index,
+                        phrase: hotword.phrase.clone(),
+                        result,
+                        ..occurrence
+                    });
+                }
+            }
+        }
+        if matches!(
+            result,
+            AprilResultType::RecognitionFinal | AprilResultType::Silence
+        ) {
+            // the next sentence starts from scratch
+            self.reported.iter_mut().for_each(Vec::clear);
+        }
+        events
+    }
+
+    /// A handler calling `on_event` with every hotword in the results, to start a session with.
+    pub fn handler(&self, on_event: impl FnMut(HotwordEvent) + Send + 'static) -> ResultHandler {
+        self.clone().handler_then(on_event, Box::new(|_, _| {}))
+    }
+
+    /// Like [`HotwordSpotter::handler`], but also passes every result on to `next`.
+    pub fn handler_then(
+        mut self,
+        mut on_event: impl FnMut(HotwordEvent) + Send + 'static,
+        mut next: ResultHandler,
+    ) -> ResultHandler {
+        Box::new(move |result, tokens| {
+            for event in self.process(result, &tokens) {
+                on_event(event);
+            }
+            next(result, tokens)
+        })
+    }
+}
+
+/// Occurrences of `phrase` in `words`, without their hotword fields filled in.
+fn find(phrase: &[String], words: &[(String, &AprilWord)]) -> Vec<HotwordEvent> {
+    if phrase.is_empty() || phrase.len() > words.len() {
+        return Vec::new();
+    }
+    words
+        .windows(phrase.len())
+        .filter(|window| window.iter().map(|(text, _)| text).eq(phrase))
+        .map(|window| HotwordEvent {
+            index: 0,
+            phrase: String::new(),
+            result: AprilResultType::Unknown,
+            logprob: window.iter().map(|(_, word)| word.logprob).sum(),
+            start_ms: window[0].1.start_ms,
+            end_ms: window[window.len() - 1].1.end_ms,
+        })
+        .collect()
+}
+
+/// How far apart revisions of a sentence may place the same occurrence.
+const REVISION_SLACK_MS: usize = 100;
+
+fn overlap(a: &RangeInclusive<usize>, b: &RangeInclusive<usize>) -> bool {
+    *a.start() <= b.end() + REVISION_SLACK_MS && *b.start() <= a.end() + REVISION_SLACK_MS
+}
+
+/// Uppercase `word` without punctuation, or `None` if nothing is left.
+pub(crate) fn normalize(word: &str) -> Option<String> {
+    let word: String = word
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '\'')
+        .flat_map(char::to_uppercase)
+        .collect();
+    (!word.is_empty()).then_some(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::april_config::AprilConfigFlags;
+    use crate::recognizer::{Recognizer, RecognizerSession};
+    use crate::scripted::ScriptedRecognizer;
+    use std::sync::mpsc;
+
+    /// The hotwords spotted in the results of `recognizer`, fed 100ms at a time for `ms`.
+    fn spot(recognizer: ScriptedRecognizer, spotter: &HotwordSpotter, ms: usize) -> Vec<String> {
+        let (tx, events) = mpsc::channel();
+        let mut session = recognizer
+            .new_session(
+                AprilConfigFlags::empty(),
+                spotter.handler(move |event| tx.send(event).unwrap()),
+            )
+            .unwrap();
+        for _ in 0..ms / 100 {
+            session.feed_pcm16(&mut [0; 1600]);
+        }
+        session.flush();
+        drop(session);
+        events
+            .iter()
+            .map(|event| format!("{} {}@{}", event.result, event.phrase, event.start_ms))
+            .collect()
+    }
+
+    #[test]
+    fn reports_occurrences_dropped_by_revisions_once() {
+        let mut spotter = HotwordSpotter::new();
+        spotter.add("stop", -1.0);
+
+        // a revision drops the first occurrence, and a later one shows up
+        let recognizer = ScriptedRecognizer::new(16000)
+            .partial_at(500, "STOP@300")
+            .partial_at(1000, "TOP@300 IT@700")
+            .partial_at(1500, "TOP@300 IT@700 STOP@1200")
+            .final_on_flush("TOP@300 IT@700 STOP.@1200");
+        assert_eq!(
+            spot(recognizer, &spotter, 1500),
+            [
+                "partially completed stop@300",
+                "partially completed stop@1200"
+            ]
+        );
+
+        // a revision drops it and the next one adds it back, a little moved
+        let recognizer = ScriptedRecognizer::new(16000)
+            .partial_at(500, "STOP@300")
+            .partial_at(1000, "TOP@300 IT@700")
+            .partial_at(1500, "STOP@320 IT@700")
+            .final_on_flush("STOP@320 IT.@700");
+        assert_eq!(
+            spot(recognizer, &spotter, 1500),
+            ["partially completed stop@300"]
+        );
+
+        // the next sentence starts over
+        let recognizer = ScriptedRecognizer::new(16000)
+            .final_at(500, "STOP.@300")
+            .final_on_flush("STOP.@300");
+        assert_eq!(
+            spot(recognizer, &spotter, 500),
+            ["final result stop@300", "final result stop@300"]
+        );
+    }
+
+    #[test]
+    fn reports_occurrences_once_confident_enough() {
+        let recognizer = ScriptedRecognizer::new(16000)
+            .partial_at(500, "HEY@100 APRIL@300")
+            .partial_at(1000, "HEY@100 APRIL@300 HEY@700")
+            .final_on_flush("HEY@100 APRIL@300 HEY@700 APRIL.@900");
+        let mut spotter = HotwordSpotter::new();
+        spotter.add("Hey, April!", 0.0).add("april", 0.0);
+        assert_eq!(
+            spot(recognizer, &spotter, 1000),
+            [
+                "partially completed Hey, April!@100",
+                "partially completed april@300",
+                "final result Hey, April!@700",
+                "final result april@900",
+            ]
+        );
+    }
+}
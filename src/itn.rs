@@ -0,0 +1,322 @@
+use crate::april_token::{AprilTokens, AprilWord};
+use crate::error::Result;
+use crate::hotword::normalize;
+use crate::numbers::{ordinal_suffix, parse_number, parse_ordinal, parse_year};
+use crate::recognizer::Recognizer;
+use std::sync::Arc;
+
+/// Inverse text normalization rules of one language, turning spoken forms into written ones.
+pub trait ItnRules: Send + Sync {
+    /// Rewrite the span starting at the first of `words`, returning its written form and the
+    /// number of words it replaces, at least one. `words` are uppercase without punctuation,
+    /// and end at the next sentence end or punctuation.
+    fn rewrite(&self, words: &[&str]) -> Option<(String, usize)>;
+}
+
+/// Converts final results from spoken to written form, as in "twenty five dollars" to "$25".
+///
+/// Words of a span rewritten together are merged into one word starting when the first began
+/// and ending when the last did, with their logprobs summed. Other words are left untouched.
+///
+/// ```
+/// use april_asr_rs::InverseNormalizer;
+///
+/// let itn = InverseNormalizer::for_language("en").unwrap();
+/// assert_eq!(
+///     itn.normalize_text("IT COST TWENTY FIVE DOLLARS ON MARCH THIRD TWENTY TWENTY."),
+///     "IT COST $25 ON March 3, 2020."
+/// );
+/// ```
+#[derive(Clone)]
+pub struct InverseNormalizer {
+    rules: Arc<dyn ItnRules>,
+}
+
+impl std::fmt::Debug for InverseNormalizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InverseNormalizer").finish_non_exhaustive()
+    }
+}
+
+impl InverseNormalizer {
+    pub fn new(rules: impl ItnRules + 'static) -> Self {
+        Self {
+            rules: Arc::new(rules),
+        }
+    }
+
+    /// The built-in rules for `language`, such as "en" or "en-us", if there are any. Only
+    /// English has rules so far.
+    pub fn for_language(language: &str) -> Option<Self> {
+        let base = language.split(['-', '_']).next().unwrap_or_default();
+        match base.to_ascii_lowercase().as_str() {
+            "en" => Some(Self::new(EnglishItn)),
+            _ => None,
+        }
+    }
+
+    /// The built-in rules for the language of `recognizer`, if there are any.
+    pub fn for_model(recognizer: &impl Recognizer) -> Result<Option<Self>> {
+        Ok(Self::for_language(recognizer.get_model_language()?))
+    }
+
+    pub fn normalize(&self, tokens: &AprilTokens) -> Vec<AprilWord> {
+        self.normalize_words(&tokens.words())
+    }
+
+    /// Rewrite words, as given by [`AprilTokens::words`].
+    pub fn normalize_words(&self, words: &[AprilWord]) -> Vec<AprilWord> {
+        // the normalized form of each word, where spans can't continue after the word
+        let spoken: Vec<(Option<String>, bool)> = words
+            .iter()
+            .map(|word| {
+                let ends_span = word.text.ends_with(|c: char| !c.is_alphanumeric());
+                (normalize(&word.text), ends_span)
+            })
+            .collect();
+
+        let mut written = Vec::with_capacity(words.len());
+        let mut i = 0;
+        while i < words.len() {
+            let mut span = Vec::new();
+            for (word, ends_span) in &spoken[i..] {
+                let Some(word) = word else { break };
+                span.push(word.as_str());
+                if *ends_span {
+                    break;
+                }
+            }
+            match self.rules.rewrite(&span) {
+                Some((text, count)) if count >= 1 && count <= span.len() => {
+                    let merged = &words[i..i + count];
+                    let last = &merged[count - 1];
+                    // keep punctuation after the span, as in "dollars."
+                    let trailing = last.text.len()
+                        - last
+                            .text
+                            .trim_end_matches(|c: char| !c.is_alphanumeric())
+                            .len();
+                    written.push(AprilWord {
+                        text: text + &last.text[last.text.len() - trailing..],
+                        logprob: merged.iter().map(|word| word.logprob).sum(),
+                        start_ms: merged[0].start_ms,
+                        end_ms: last.end_ms,
+                    });
+                    i += count;
+                }
+                _ => {
+                    written.push(words[i].clone());
+                    i += 1;
+                }
+            }
+        }
+        written
+    }
+
+    /// Rewrite text split into words at whitespace, mostly useful for testing rules.
+    pub fn normalize_text(&self, text: &str) -> String {
+        let words: Vec<AprilWord> = text
+            .split_whitespace()
+            .map(|word| AprilWord {
+                text: word.to_owned(),
+                logprob: 0.0,
+                start_ms: 0,
+                end_ms: 0,
+            })
+            .collect();
+        let written: Vec<String> = self
+            .normalize_words(&words)
+            .into_iter()
+            .map(|word| word.text)
+            .collect();
+        written.join(" ")
+    }
+}
+
+/// The English [`ItnRules`], for money, percentages, dates, times of day, ordinals and
+/// numbers. Numbers and ordinals under ten said as one word are left alone, as in "one of
+/// them" or "the first time".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnglishItn;
+
+const MONTHS: [&str; 12] = [
+    "JANUARY",
+    "FEBRUARY",
+    "MARCH",
+    "APRIL",
+    "MAY",
+    "JUNE",
+    "JULY",
+    "AUGUST",
+    "SEPTEMBER",
+    "OCTOBER",
+    "NOVEMBER",
+    "DECEMBER",
+];
+
+impl ItnRules for EnglishItn {
+    fn rewrite(&self, words: &[&str]) -> Option<(String, usize)> {
+        date(words)
+            .or_else(|| time_of_day(words))
+            .or_else(|| amount(words))
+    }
+}
+
+/// The longest span at the start of `words` that `parse` accepts, with its length.
+fn longest(words: &[&str], parse: impl Fn(&[&str]) -> Option<i64>) -> Option<(i64, usize)> {
+    (1..=words.len())
+        .rev()
+        .find_map(|len| parse(&words[..len]).map(|value| (value, len)))
+}
+
+/// "march third twenty twenty" to "March 3, 2020".
+fn date(words: &[&str]) -> Option<(String, usize)> {
+    let (month, rest) = words.split_first()?;
+    let month = MONTHS.iter().position(|m| m == month)?;
+    let (day, day_len) = longest(rest, parse_ordinal).filter(|(day, _)| (1..=31).contains(day))?;
+    let month = MONTHS[month][..1].to_owned() + &MONTHS[month][1..].to_lowercase();
+    // "june first twelve thirty p m" is a time of day, not a year
+    let year = longest(&rest[day_len..], parse_year).filter(|(_, year_len)| {
+        !matches!(
+            &rest[day_len + year_len..],
+            ["AM" | "PM" | "O'CLOCK", ..] | ["A" | "P", "M", ..]
+        )
+    });
+    match year {
+        Some((year, year_len)) => Some((
+            format!("{} {}, {}", month, day, year),
+            1 + day_len + year_len,
+        )),
+        None => Some((format!("{} {}", month, day), 1 + day_len)),
+    }
+}
+
+/// "ten thirty p m" to "10:30 PM", "seven o'clock" to "7:00".
+fn time_of_day(words: &[&str]) -> Option<(String, usize)> {
+    let (hour, rest) = words.split_first()?;
+    let hour = parse_number(&[hour]).filter(|hour| (1..=12).contains(hour))?;
+    if let ["O'CLOCK", ..] = rest {
+        return Some((format!("{}:00", hour), 2));
+    }
+    let (minutes, minutes_len) = match rest {
+        ["OH", unit, ..] => (parse_number(&[unit]).filter(|unit| *unit < 10)?, 2),
+        _ => longest(&rest[..rest.len().min(2)], parse_number)
+            .filter(|(minutes, _)| (10..60).contains(minutes))
+            .unwrap_or((0, 0)),
+    };
+    let (period, period_len) = match &rest[minutes_len..] {
+        ["AM", ..] => ("AM", 1),
+        ["PM", ..] => ("PM", 1),
+        ["A", "M", ..] => ("AM", 2),
+        ["P", "M", ..] => ("PM", 2),
+        _ => return None,
+    };
+    Some((
+        format!("{}:{:02} {}", hour, minutes, period),
+        1 + minutes_len + period_len,
+    ))
+}
+
+/// Money, percentages, ordinals and numbers.
+fn amount(words: &[&str]) -> Option<(String, usize)> {
+    if let Some((number, len)) = longest(words, parse_number) {
+        let rest = &words[len..];
+        match rest {
+            ["DOLLAR" | "DOLLARS", "AND", ..] => {
+                if let Some((cents, cents_len)) = longest(&rest[2..], parse_number) {
+                    if cents < 100 && matches!(rest.get(2 + cents_len), Some(&"CENT" | &"CENTS")) {
+                        return Some((format!("${}.{:02}", number, cents), len + 3 + cents_len));
+                    }
+                }
+                return Some((format!("${}", number), len + 1));
+            }
+            ["DOLLAR" | "DOLLARS", ..] => return Some((format!("${}", number), len + 1)),
+            ["CENT" | "CENTS", ..] if number < 100 => {
+                return Some((format!("$0.{:02}", number), len + 1))
+            }
+            ["PERCENT", ..] => return Some((format!("{}%", number), len + 1)),
+            _ => {}
+        }
+    }
+
+    let ordinal = longest(words, parse_ordinal);
+    let cardinal = longest(words, parse_number);
+    match (ordinal, cardinal) {
+        // prefer the longer reading, as "twenty first" over "twenty"
+        (Some((ordinal, len)), cardinal) if cardinal.is_none_or(|(_, other)| len > other) => {
+            (ordinal >= 10 || len > 1)
+                .then(|| (format!("{}{}", ordinal, ordinal_suffix(ordinal)), len))
+        }
+        (_, Some((number, len))) => (number >= 10 || len > 1).then(|| (number.to_string(), len)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_english() {
+        let itn = InverseNormalizer::for_language("en").unwrap();
+        let cases = [
+            ("TWENTY FIVE DOLLARS", "$25"),
+            ("A HUNDRED DOLLARS AND FIFTY CENTS.", "$100.50."),
+            ("FIVE CENTS", "$0.05"),
+            ("UP TWELVE PERCENT", "UP 12%"),
+            ("ON MARCH THIRD TWENTY TWENTY", "ON March 3, 2020"),
+            ("MAY TWENTY FIRST NINETEEN EIGHTY FOUR", "May 21, 1984"),
+            ("JULY FOURTH TWO THOUSAND AND FIVE", "July 4, 2005"),
+            // a sentence end splits the date from the year
+            ("JUNE FIRST. TWO THOUSAND", "June 1. 2000"),
+            ("AT TEN THIRTY P M", "AT 10:30 PM"),
+            ("AT SEVEN OH FIVE AM OR NINE O'CLOCK", "AT 7:05 AM OR 9:00"),
+            ("THE TWENTY FIRST CENTURY", "THE 21st CENTURY"),
+            ("ONE OF THE FIRST THREE", "ONE OF THE FIRST THREE"),
+            (
+                "TWO THOUSAND THREE HUNDRED AND TWELVE PEOPLE",
+                "2312 PEOPLE",
+            ),
+            ("TWELFTH AND THIRTEENTH", "12th AND 13th"),
+            // "and" is only part of a number when more of it follows
+            ("ONE HUNDRED AND COUNTING", "100 AND COUNTING"),
+            (
+                "I HAVE TWO HUNDRED AND YOU HAVE FIVE",
+                "I HAVE 200 AND YOU HAVE FIVE",
+            ),
+            ("TWO THOUSAND AND", "2000 AND"),
+            ("JUNE FIRST TWELVE THIRTY PM", "June 1 12:30 PM"),
+            ("MAY THIRD SEVEN O'CLOCK", "May 3 7:00"),
+            ("JULY FOURTH TEN P M", "July 4 10:00 PM"),
+        ];
+        for (spoken, written) in cases {
+            assert_eq!(itn.normalize_text(spoken), written, "for {:?}", spoken);
+        }
+
+        let words: Vec<_> = ["IT", "COST", "TWENTY", "FIVE", "DOLLARS."]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| AprilWord {
+                text: text.to_string(),
+                logprob: -0.5,
+                start_ms: i * 100,
+                end_ms: i * 100 + 50,
+            })
+            .collect();
+        let written = itn.normalize_words(&words);
+        assert_eq!(written.len(), 3);
+        assert_eq!(
+            written[2],
+            AprilWord {
+                text: "$25.".to_owned(),
+                logprob: -1.5,
+                start_ms: 200,
+                end_ms: 450,
+            }
+        );
+        assert_eq!(written[..2], words[..2]);
+
+        assert!(InverseNormalizer::for_language("en-US").is_some());
+        assert!(InverseNormalizer::for_language("fr").is_none());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::april_config::AprilConfigFlags;
+use crate::april_result_type::AprilResultType;
+use crate::error::{Error, Result};
+use crate::recognizer::{Recognizer, RecognizerSession, ResultHandler};
+use crate::worker::{Finals, SHUTDOWN_FLUSH_TIMEOUT};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What a [`KeepUpSession`] does once april reports [`AprilResultType::ErrorCantKeepUp`].
+///
+/// April has already dropped the audio it couldn't process by then. The policy is applied on
+/// the next call to [`KeepUpSession::feed_pcm16`] or [`KeepUpSession::flush`], and the result
+/// is still passed on to the handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepUpPolicy {
+    /// Carry on as if nothing happened
+    Ignore,
+    /// Flush the session, so that the sentence cut by the dropped audio is finalized, and
+    /// carry on with new audio from there
+    DropAndResync,
+    /// Replace the session by one with [`AprilConfigFlags::ASYNC_RT`], which trades accuracy
+    /// for keeping up. The old session is flushed first, and if a sentence was in progress,
+    /// kept until its final result arrives or [`SHUTDOWN_FLUSH_TIMEOUT`] passes.
+    SwitchToRealtime,
+    /// Make `feed_pcm16` block for as long as the audio it is given lasts from then on, so a
+    /// producer faster than realtime is slowed down to it
+    Backpressure,
+    /// Fail the next call with [`Error::CantKeepUp`]
+    Fail,
+}
+
+/// Counters of how often sessions fell behind and what was done about it. Clones share the
+/// same counters, so several sessions can count into one.
+///
+/// Read them with [`Self::counts`]. The server exports the ones handed out by its
+/// `ServerMetrics::keep_up_stats` on `GET /metrics`, for programs embedding it to count their
+/// sessions into.
+#[derive(Debug, Clone, Default)]
+pub struct KeepUpStats {
+    counters: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    cant_keep_up: AtomicU64,
+    resyncs: AtomicU64,
+    switched_to_realtime: AtomicU64,
+    paced_micros: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// A snapshot of [`KeepUpStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KeepUpCounts {
+    /// [`AprilResultType::ErrorCantKeepUp`] results received
+    pub cant_keep_up: u64,
+    /// Flushes done by [`KeepUpPolicy::DropAndResync`]
+    pub resyncs: u64,
+    /// Sessions replaced by [`KeepUpPolicy::SwitchToRealtime`]
+    pub switched_to_realtime: u64,
+    /// Time producers were blocked by [`KeepUpPolicy::Backpressure`]
+    pub paced_seconds: f64,
+    /// Errors returned by [`KeepUpPolicy::Fail`]
+    pub failures: u64,
+}
+
+impl KeepUpStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn counts(&self) -> KeepUpCounts {
+        let counters = &self.counters;
+        KeepUpCounts {
+            cant_keep_up: counters.cant_keep_up.load(Ordering::Relaxed),
+            resyncs: counters.resyncs.load(Ordering::Relaxed),
+            switched_to_realtime: counters.switched_to_realtime.load(Ordering::Relaxed),
+            paced_seconds: counters.paced_micros.load(Ordering::Relaxed) as f64 / 1e6,
+            failures: counters.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A session applying a [`KeepUpPolicy`] when april can't keep up with the audio.
+///
+/// Only sessions with [`AprilConfigFlags::ASYNC_NO_RT`] ever fall behind, synchronous ones make
+/// the producer wait and `ASYNC_RT` ones lower accuracy instead.
+pub struct KeepUpSession<'a, R: Recognizer + 'a> {
+    recognizer: &'a R,
+    session: R::Session<'a>,
+    flags: AprilConfigFlags,
+    policy: KeepUpPolicy,
+    shared: Arc<Shared>,
+    stats: KeepUpStats,
+    /// When [`KeepUpPolicy::Backpressure`] started pacing, and the samples fed since
+    pacing: Option<(Instant, usize)>,
+}
+
+/// State shared with the result handlers of the sessions of a [`KeepUpSession`].
+struct Shared {
+    handler: Mutex<ResultHandler>,
+    fell_behind: AtomicBool,
+    /// Whether a partial result arrived since the last final one
+    sentence_open: AtomicBool,
+    finals: Finals,
+}
+
+impl<'a, R: Recognizer + 'a> KeepUpSession<'a, R> {
+    pub fn new(
+        recognizer: &'a R,
+        flags: AprilConfigFlags,
+        policy: KeepUpPolicy,
+        handler: ResultHandler,
+        stats: KeepUpStats,
+    ) -> Result<Self> {
+        let shared = Arc::new(Shared {
+            handler: Mutex::new(handler),
+            fell_behind: AtomicBool::new(false),
+            sentence_open: AtomicBool::new(false),
+            finals: Finals::default(),
+        });
+        let session = Self::create(recognizer, flags, &shared, &stats)?;
+        Ok(Self {
+            recognizer,
+            session,
+            flags,
+            policy,
+            shared,
+            stats,
+            pacing: None,
+        })
+    }
+
+    fn create(
+        recognizer: &'a R,
+        flags: AprilConfigFlags,
+        shared: &Arc<Shared>,
+        stats: &KeepUpStats,
+    ) -> Result<R::Session<'a>> {
+        let shared = shared.clone();
+        let stats = stats.clone();
+        recognizer.new_session(
+            flags,
+            Box::new(move |result, tokens| {
+                match result {
+                    AprilResultType::ErrorCantKeepUp => {
+                        stats.counters.cant_keep_up.fetch_add(1, Ordering::Relaxed);
+                        shared.fell_behind.store(true, Ordering::Release);
+                    }
+                    AprilResultType::RecognitionPartial => {
+                        shared.sentence_open.store(true, Ordering::Release)
+                    }
+                    _ => {}
+                }
+                {
+                    let mut handler = shared.handler.lock().unwrap_or_else(|e| e.into_inner());
+                    (*handler)(result, tokens);
+                }
+                // counted once handled, so that waiting for it means it was delivered
+                if result == AprilResultType::RecognitionFinal {
+                    shared.sentence_open.store(false, Ordering::Release);
+                    shared.finals.counted();
+                }
+            }),
+        )
+    }
+
+    pub fn feed_pcm16(&mut self, pcm: &mut [i16]) -> Result<()> {
+        self.apply_policy()?;
+        self.session.feed_pcm16(pcm);
+        self.pace(pcm.len());
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.apply_policy()?;
+        self.session.flush();
+        Ok(())
+    }
+
+    /// Flags of the current session, which [`KeepUpPolicy::SwitchToRealtime`] changes.
+    pub fn flags(&self) -> AprilConfigFlags {
+        self.flags
+    }
+
+    pub fn policy(&self) -> KeepUpPolicy {
+        self.policy
+    }
+
+    pub fn session(&self) -> &R::Session<'a> {
+        &self.session
+    }
+
+    pub fn stats(&self) -> &KeepUpStats {
+        &self.stats
+    }
+
+    fn apply_policy(&mut self) -> Result<()> {
+        if !self.shared.fell_behind.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let counters = &self.stats.counters;
+        match self.policy {
+            KeepUpPolicy::Ignore => {}
+            KeepUpPolicy::DropAndResync => {
+                counters.resyncs.fetch_add(1, Ordering::Relaxed);
+                self.session.flush();
+            }
+            KeepUpPolicy::SwitchToRealtime => {
+                if !self.flags.contains(AprilConfigFlags::ASYNC_RT) {
+                    let flags =
+                        (self.flags - AprilConfigFlags::ASYNC_NO_RT) | AprilConfigFlags::ASYNC_RT;
+                    let session = Self::create(self.recognizer, flags, &self.shared, &self.stats)?;
+                    // finalize the sentence in progress, which async sessions drop when freed
+                    let seen = self.shared.finals.count();
+                    self.session.flush();
+                    if self.shared.sentence_open.load(Ordering::Acquire) {
+                        self.shared
+                            .finals
+                            .wait_past(seen, 0, SHUTDOWN_FLUSH_TIMEOUT);
+                    }
+                    self.session = session;
+                    self.flags = flags;
+                    counters
+                        .switched_to_realtime
+                        .fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            KeepUpPolicy::Backpressure => {
+                self.pacing.get_or_insert((Instant::now(), 0));
+            }
+            KeepUpPolicy::Fail => {
+                counters.failures.fetch_add(1, Ordering::Relaxed);
+                return Err(Error::CantKeepUp);
+            }
+        }
+        Ok(())
+    }
+
+    /// Block until `samples` more samples have played since pacing started.
+    fn pace(&mut self, samples: usize) {
+        let Some((started, paced)) = &mut self.pacing else {
+            return;
+        };
+        *paced += samples;
+        let rate = self.recognizer.get_sample_rate().max(1);
+        let due = *started + Duration::from_secs_f64(*paced as f64 / rate as f64);
+        let wait = due.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+            self.stats
+                .counters
+                .paced_micros
+                .fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripted::{ScriptedRecognizer, Trigger};
+    use std::sync::mpsc;
+
+    #[test]
+    fn switching_to_realtime_finalizes_the_sentence_in_progress() {
+        let recognizer = ScriptedRecognizer::new(16000)
+            .partial_at(500, "ONE")
+            .then(
+                Trigger::Audio { ms: 500 },
+                AprilResultType::ErrorCantKeepUp,
+                "",
+            )
+            .final_on_flush("ONE.");
+        let (tx, rx) = mpsc::channel();
+        let stats = KeepUpStats::new();
+        let mut session = KeepUpSession::new(
+            &recognizer,
+            AprilConfigFlags::ASYNC_NO_RT,
+            KeepUpPolicy::SwitchToRealtime,
+            Box::new(move |result, tokens| {
+                let _ = tx.send((result, tokens.to_string()));
+            }),
+            stats.clone(),
+        )
+        .unwrap();
+        session.feed_pcm16(&mut [0; 8000]).unwrap();
+        session.feed_pcm16(&mut [0; 1600]).unwrap();
+        assert_eq!(session.flags(), AprilConfigFlags::ASYNC_RT);
+        assert_eq!(stats.counts().switched_to_realtime, 1);
+
+        let results: Vec<_> = rx.try_iter().collect();
+        assert_eq!(
+            results,
+            [
+                (AprilResultType::RecognitionPartial, " ONE".to_owned()),
+                (AprilResultType::ErrorCantKeepUp, String::new()),
+                (AprilResultType::RecognitionFinal, " ONE.".to_owned()),
+            ]
+        );
+    }
+}
@@ -1,28 +1,103 @@
+#[cfg(not(feature = "dynamic"))]
 use std::ffi::c_int;
+#[cfg(not(feature = "dynamic"))]
 use std::sync::Once;
 
+/// Enter a `tracing` span until the end of the enclosing block. Expands to nothing without the
+/// `tracing` feature, so neither the span nor its fields are evaluated.
+macro_rules! span {
+    ($level:ident, $name:literal $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::span!(tracing::Level::$level, $name $(, $($fields)*)?).entered();
+    };
+}
+
+/// Emit a `tracing` event, or nothing without the `tracing` feature.
+macro_rules! event {
+    ($level:ident, $($args:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::event!(tracing::Level::$level, $($args)*);
+    };
+}
+
 mod april_config;
 mod april_model;
 mod april_result_type;
 mod april_session;
 mod april_token;
+pub mod codec;
 mod error;
+mod grammar;
+mod hotword;
+mod itn;
+mod keep_up;
+mod metrics;
+mod numbers;
+mod recognizer;
+mod recording;
+mod restore;
+mod ring;
+mod scripted;
+mod vocabulary;
+mod worker;
 
+#[cfg(feature = "dynamic")]
+pub use april_asr_rs_sys::LoadError;
 pub use april_config::{AprilConfig, AprilConfigFlags, AprilHandlerCallback};
 pub use april_model::AprilModel;
 pub use april_result_type::AprilResultType;
 pub use april_session::AprilSession;
-pub use april_token::{AprilToken, AprilTokenFlags, AprilTokens};
+pub use april_token::{AprilToken, AprilTokenFlags, AprilTokens, AprilWord};
 pub use error::{Error, Result};
+pub use grammar::{Grammar, IntentMatch, SlotValue};
+pub use hotword::{Hotword, HotwordEvent, HotwordSpotter};
+pub use itn::{EnglishItn, InverseNormalizer, ItnRules};
+pub use keep_up::{KeepUpCounts, KeepUpPolicy, KeepUpSession, KeepUpStats};
+pub use metrics::{LatencyStats, MetricsHandle, SessionMetrics};
+pub use recognizer::{Recognizer, RecognizerSession, ResultHandler};
+pub use recording::{ModelInfo, RecordedEvent, RecordedResult, Recorder, Recording, ResultDiff};
+pub use restore::{Annotation, Change, PunctuationReason, Restored, TextRestorer};
+pub use ring::{audio_ring, OverflowPolicy, RingConsumer, RingProducer, RingStats};
+pub use scripted::{ScriptedRecognizer, ScriptedResult, ScriptedSession, Trigger};
+pub use vocabulary::{
+    MatchMode, Replaced, Replacement, Vocabulary, VocabularyEntry, MIN_PHONETIC_LETTERS,
+};
+pub use worker::{SessionWorker, WorkerCommand, WorkerResult, SHUTDOWN_FLUSH_TIMEOUT};
 
+#[cfg(not(feature = "dynamic"))]
 static ASSERT_INIT: Once = Once::new();
 
 /// Initialize April once and exactly once. Safe to call multiple times,
 /// later calls have no effect.
-fn do_init() {
+#[cfg(not(feature = "dynamic"))]
+fn do_init() -> Result<()> {
     ASSERT_INIT.call_once(|| unsafe {
         april_asr_rs_sys::aam_api_init(april_asr_rs_sys::APRIL_VERSION as c_int)
-    })
+    });
+    Ok(())
+}
+
+/// With the `dynamic` backend, April is initialized by [`load_library`], which must come first.
+#[cfg(feature = "dynamic")]
+fn do_init() -> Result<()> {
+    if april_asr_rs_sys::is_loaded() {
+        Ok(())
+    } else {
+        Err(LoadError::NotLoaded.into())
+    }
+}
+
+/// Load libaprilasr from `path` and initialize it, before creating any [`AprilModel`].
+///
+/// Only available with the `dynamic` feature, which loads April at runtime instead of linking
+/// it. Fails if the library can't be opened or doesn't export the whole API. The library's
+/// version isn't checked, as April can't report it, so it must be built from the same
+/// april_api.h as these bindings. Loading the same path twice is allowed, but a different
+/// library can't replace the loaded one.
+#[cfg(feature = "dynamic")]
+pub fn load_library(path: impl AsRef<std::ffi::OsStr>) -> Result<()> {
+    span!(DEBUG, "load_library", path = ?path.as_ref());
+    Ok(april_asr_rs_sys::load(path)?)
 }
 
 pub use april_asr_rs_sys::AprilRecognitionResultHandler;
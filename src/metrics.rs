@@ -0,0 +1,167 @@
+use crate::april_result_type::AprilResultType;
+use std::ops::AddAssign;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Latency samples of one kind, see [`SessionMetrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatencyStats {
+    pub count: u64,
+    pub total_seconds: f64,
+    pub max_seconds: f64,
+}
+
+impl LatencyStats {
+    pub fn mean_seconds(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total_seconds / self.count as f64)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        self.count += 1;
+        self.total_seconds += seconds;
+        self.max_seconds = self.max_seconds.max(seconds);
+    }
+}
+
+impl AddAssign<&LatencyStats> for LatencyStats {
+    fn add_assign(&mut self, other: &LatencyStats) {
+        self.count += other.count;
+        self.total_seconds += other.total_seconds;
+        self.max_seconds = self.max_seconds.max(other.max_seconds);
+    }
+}
+
+/// A snapshot of what a session has processed so far, see [`crate::AprilSession::metrics`].
+///
+/// Results are only observed for sessions with a handler set through
+/// [`crate::AprilConfig::set_handler_fn`]. Snapshots of several sessions can be summed with `+=`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionMetrics {
+    /// Seconds of audio fed to the session
+    pub audio_seconds: f64,
+    /// Wall time spent in `feed_pcm16` and `flush`. In async sessions these only queue audio,
+    /// so this is not the time spent recognizing it.
+    pub processing_seconds: f64,
+    pub partial_results: u64,
+    pub final_results: u64,
+    /// Number of [`AprilResultType::ErrorCantKeepUp`] results
+    pub cant_keep_up: u64,
+    /// Time from feeding audio to the first partial or final result after it, counted once per
+    /// result from the oldest chunk it covers. Audio followed by silence or dropped is not
+    /// counted.
+    pub first_partial_latency: LatencyStats,
+    /// Time from the first partial result of a sentence to its final result
+    pub partial_to_final_latency: LatencyStats,
+}
+
+impl SessionMetrics {
+    /// Processing time per second of audio: below 1.0, the session runs faster than realtime.
+    /// Only meaningful for synchronous sessions, async ones have
+    /// [`crate::AprilSession::get_realtime_speedup`] instead.
+    pub fn realtime_factor(&self) -> Option<f64> {
+        (self.audio_seconds > 0.0).then(|| self.processing_seconds / self.audio_seconds)
+    }
+}
+
+impl AddAssign<&SessionMetrics> for SessionMetrics {
+    fn add_assign(&mut self, other: &SessionMetrics) {
+        self.audio_seconds += other.audio_seconds;
+        self.processing_seconds += other.processing_seconds;
+        self.partial_results += other.partial_results;
+        self.final_results += other.final_results;
+        self.cant_keep_up += other.cant_keep_up;
+        self.first_partial_latency += &other.first_partial_latency;
+        self.partial_to_final_latency += &other.partial_to_final_latency;
+    }
+}
+
+/// Shared access to the metrics of a session, which stays readable from other threads and
+/// after the session is gone.
+#[derive(Debug, Clone)]
+pub struct MetricsHandle {
+    state: Arc<Mutex<MetricsState>>,
+}
+
+#[derive(Debug)]
+struct MetricsState {
+    metrics: SessionMetrics,
+    sample_rate: usize,
+    /// When the oldest chunk of audio not covered by a result yet was fed
+    uncovered_since: Option<Instant>,
+    /// When the first partial result of the current sentence arrived
+    sentence_started: Option<Instant>,
+    active: bool,
+}
+
+impl MetricsHandle {
+    pub(crate) fn new(sample_rate: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MetricsState {
+                metrics: SessionMetrics::default(),
+                sample_rate,
+                uncovered_since: None,
+                sentence_started: None,
+                active: true,
+            })),
+        }
+    }
+
+    pub fn snapshot(&self) -> SessionMetrics {
+        self.lock().metrics.clone()
+    }
+
+    /// Whether the session is still running. Its metrics don't change once it isn't.
+    pub fn is_active(&self) -> bool {
+        self.lock().active
+    }
+
+    /// Record that `samples` are about to be fed.
+    pub(crate) fn feeding(&self, samples: usize) {
+        let mut state = self.lock();
+        state.metrics.audio_seconds += samples as f64 / state.sample_rate.max(1) as f64;
+        state.uncovered_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Record time spent in a call to april. The lock is not held during the call, as results
+    /// arrive from within it.
+    pub(crate) fn processed(&self, elapsed: Duration) {
+        self.lock().metrics.processing_seconds += elapsed.as_secs_f64();
+    }
+
+    pub(crate) fn result(&self, result: AprilResultType) {
+        let now = Instant::now();
+        let mut state = self.lock();
+        let state = &mut *state;
+        match result {
+            AprilResultType::RecognitionPartial | AprilResultType::RecognitionFinal => {
+                if let Some(fed) = state.uncovered_since.take() {
+                    state.metrics.first_partial_latency.record(now - fed);
+                }
+                if result == AprilResultType::RecognitionPartial {
+                    state.metrics.partial_results += 1;
+                    state.sentence_started.get_or_insert(now);
+                } else {
+                    state.metrics.final_results += 1;
+                    if let Some(started) = state.sentence_started.take() {
+                        state.metrics.partial_to_final_latency.record(now - started);
+                    }
+                }
+            }
+            AprilResultType::ErrorCantKeepUp => {
+                state.metrics.cant_keep_up += 1;
+                state.uncovered_since = None;
+            }
+            AprilResultType::Silence => state.uncovered_since = None,
+            AprilResultType::Unknown | AprilResultType::Other(_) => {}
+        }
+    }
+
+    pub(crate) fn finished(&self) {
+        self.lock().active = false;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MetricsState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,156 @@
+//! Parsing of English number words, shared by [`crate::Grammar`] and the English
+//! [`crate::ItnRules`].
+
+/// Parse a number given as digits or as normalized English words, such as
+/// `["TWO", "HUNDRED", "AND", "FIVE"]`.
+pub(crate) fn parse_number(words: &[&str]) -> Option<i64> {
+    match *words {
+        [word] if word.bytes().all(|b| b.is_ascii_digit()) => return word.parse().ok(),
+        ["ZERO"] => return Some(0),
+        _ => {}
+    }
+
+    let mut total = 0;
+    let mut rest = words;
+    let mut last_scale = i64::MAX;
+    while !rest.is_empty() {
+        let (group, after) = below_thousand(rest)?;
+        let scale = match after.first() {
+            Some(&"MILLION") => 1_000_000,
+            Some(&"THOUSAND") => 1_000,
+            Some(_) => return None,
+            None => 1,
+        };
+        // scales go down, as in "two million five thousand"
+        if scale >= last_scale {
+            return None;
+        }
+        total += group * scale;
+        last_scale = scale;
+        rest = match if scale == 1 { after } else { &after[1..] } {
+            // as in "two thousand and five"
+            ["AND", rest @ ..] if scale > 1 && !rest.is_empty() => rest,
+            rest => rest,
+        };
+    }
+    Some(total)
+}
+
+/// Parse 1 to 999 at the start of `words`, as in "a hundred and twenty one".
+fn below_thousand<'a, 'b>(words: &'a [&'b str]) -> Option<(i64, &'a [&'b str])> {
+    let (hundreds, rest) = match words {
+        ["A", "HUNDRED" | "THOUSAND" | "MILLION", ..] => {
+            // "a" is one, but only before a multiplier
+            if words[1] != "HUNDRED" {
+                return Some((1, &words[1..]));
+            }
+            (1, &words[2..])
+        }
+        _ => match below_hundred(words) {
+            Some((value, ["HUNDRED", rest @ ..])) if value < 10 => (value, rest),
+            Some((value, rest)) => return Some((value, rest)),
+            None => return None,
+        },
+    };
+    // "and" only belongs to the number if more of it follows
+    let tens = match rest {
+        ["AND", after @ ..] => below_hundred(after),
+        _ => below_hundred(rest),
+    };
+    match tens {
+        Some((value, rest)) => Some((hundreds * 100 + value, rest)),
+        None => Some((hundreds * 100, rest)),
+    }
+}
+
+/// Parse 1 to 99 at the start of `words`.
+pub(crate) fn below_hundred<'a, 'b>(words: &'a [&'b str]) -> Option<(i64, &'a [&'b str])> {
+    const UNITS: [&str; 19] = [
+        "ONE",
+        "TWO",
+        "THREE",
+        "FOUR",
+        "FIVE",
+        "SIX",
+        "SEVEN",
+        "EIGHT",
+        "NINE",
+        "TEN",
+        "ELEVEN",
+        "TWELVE",
+        "THIRTEEN",
+        "FOURTEEN",
+        "FIFTEEN",
+        "SIXTEEN",
+        "SEVENTEEN",
+        "EIGHTEEN",
+        "NINETEEN",
+    ];
+    const TENS: [&str; 8] = [
+        "TWENTY", "THIRTY", "FORTY", "FIFTY", "SIXTY", "SEVENTY", "EIGHTY", "NINETY",
+    ];
+    let position = |list: &[&str], word: &str| list.iter().position(|w| *w == word);
+    let (first, rest) = words.split_first()?;
+    if let Some(unit) = position(&UNITS, first) {
+        return Some((unit as i64 + 1, rest));
+    }
+    let tens = (position(&TENS, first)? as i64 + 2) * 10;
+    match rest.split_first() {
+        Some((unit, after)) => match position(&UNITS[..9], unit) {
+            Some(unit) => Some((tens + unit as i64 + 1, after)),
+            None => Some((tens, rest)),
+        },
+        None => Some((tens, rest)),
+    }
+}
+
+/// Parse an ordinal given as normalized English words, such as `["TWENTY", "FIRST"]`.
+pub(crate) fn parse_ordinal(words: &[&str]) -> Option<i64> {
+    let (last, rest) = words.split_last()?;
+    let cardinal = match *last {
+        "FIRST" => "ONE".to_owned(),
+        "SECOND" => "TWO".to_owned(),
+        "THIRD" => "THREE".to_owned(),
+        "FIFTH" => "FIVE".to_owned(),
+        "EIGHTH" => "EIGHT".to_owned(),
+        "NINTH" => "NINE".to_owned(),
+        "TWELFTH" => "TWELVE".to_owned(),
+        _ => match (last.strip_suffix("IETH"), last.strip_suffix("TH")) {
+            (Some(tens), _) => format!("{}Y", tens),
+            (None, Some(cardinal)) => cardinal.to_owned(),
+            (None, None) => return None,
+        },
+    };
+    let mut words = rest.to_vec();
+    words.push(&cardinal);
+    parse_number(&words)
+}
+
+/// Parse a year read as pairs of digits, as in "nineteen eighty four" or "twenty oh five",
+/// or as a number from 1000 to 2999, as in "two thousand and ten".
+pub(crate) fn parse_year(words: &[&str]) -> Option<i64> {
+    if let Some(year) = parse_number(words).filter(|year| (1000..3000).contains(year)) {
+        return Some(year);
+    }
+    let (century, rest) = below_hundred(words).filter(|(century, _)| *century >= 10)?;
+    let year = match rest {
+        ["HUNDRED"] => 0,
+        ["OH", unit] => below_hundred(&[unit]).filter(|(unit, _)| *unit < 10)?.0,
+        _ => match below_hundred(rest)? {
+            (year, []) if year >= 10 => year,
+            _ => return None,
+        },
+    };
+    Some(century * 100 + year)
+}
+
+/// The English suffix of an ordinal, as in "1st" or "12th".
+pub(crate) fn ordinal_suffix(number: i64) -> &'static str {
+    match (number % 10, number % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
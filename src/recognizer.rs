@@ -0,0 +1,89 @@
+use crate::april_config::{AprilConfig, AprilConfigFlags};
+use crate::april_model::AprilModel;
+use crate::april_result_type::AprilResultType;
+use crate::april_session::AprilSession;
+use crate::april_token::AprilTokens;
+use crate::error::Result;
+
+/// Callback receiving the results of a [`RecognizerSession`].
+pub type ResultHandler = Box<dyn FnMut(AprilResultType, AprilTokens) + Send>;
+
+/// The model operations this crate relies on, so code driving recognition can be tested
+/// against [`crate::ScriptedRecognizer`] instead of a real model.
+///
+/// [`AprilModel`] is the real implementation.
+pub trait Recognizer: Send + Sync {
+    type Session<'a>: RecognizerSession
+    where
+        Self: 'a;
+
+    fn get_model_name(&self) -> Result<&str>;
+
+    fn get_model_description(&self) -> Result<&str>;
+
+    fn get_model_language(&self) -> Result<&str>;
+
+    fn get_sample_rate(&self) -> usize;
+
+    /// Start a session calling `handler` with every result.
+    fn new_session(
+        &self,
+        flags: AprilConfigFlags,
+        handler: ResultHandler,
+    ) -> Result<Self::Session<'_>>;
+}
+
+/// The session operations this crate relies on. See [`Recognizer`].
+pub trait RecognizerSession {
+    fn feed_pcm16(&mut self, pcm: &mut [i16]);
+
+    fn flush(&mut self);
+
+    /// Only available in sessions created with [`AprilConfigFlags::ASYNC_RT`].
+    fn get_realtime_speedup(&self) -> Result<f32>;
+}
+
+impl Recognizer for AprilModel {
+    type Session<'a> = AprilSession<'a, ()>;
+
+    fn get_model_name(&self) -> Result<&str> {
+        AprilModel::get_model_name(self)
+    }
+
+    fn get_model_description(&self) -> Result<&str> {
+        AprilModel::get_model_description(self)
+    }
+
+    fn get_model_language(&self) -> Result<&str> {
+        AprilModel::get_model_language(self)
+    }
+
+    fn get_sample_rate(&self) -> usize {
+        AprilModel::get_sample_rate(self)
+    }
+
+    fn new_session(
+        &self,
+        flags: AprilConfigFlags,
+        mut handler: ResultHandler,
+    ) -> Result<Self::Session<'_>> {
+        let mut config = AprilConfig::default();
+        config.set_flags(flags);
+        config.set_handler_fn(move |_: &(), result, tokens| handler(result, tokens), ());
+        self.create_session(config)
+    }
+}
+
+impl<D: Sized + Send + Sync> RecognizerSession for AprilSession<'_, D> {
+    fn feed_pcm16(&mut self, pcm: &mut [i16]) {
+        AprilSession::feed_pcm16(self, pcm)
+    }
+
+    fn flush(&mut self) {
+        AprilSession::flush(self)
+    }
+
+    fn get_realtime_speedup(&self) -> Result<f32> {
+        AprilSession::get_realtime_speedup(self)
+    }
+}
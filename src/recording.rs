@@ -0,0 +1,666 @@
+//! Capture of everything a session was fed and produced, to reproduce bad transcripts.
+//!
+//! A recording file starts with [`MAGIC`] and the model metadata, followed by one event per
+//! session creation, `feed_pcm16` call, `flush` call and result, in the order they happened.
+//! Every event is its tag, then the `u32` number of the session it belongs to, counting
+//! sessions from 0 in the order they were created, then its body. Integers are
+//! little-endian, strings are a `u32` length followed by UTF-8 bytes:
+//!
+//! | tag | event   | body                                                                   |
+//! |-----|---------|------------------------------------------------------------------------|
+//! | 0   | session | `i32` config flags                                                     |
+//! | 1   | audio   | `u32` sample count, then the `i16` samples                             |
+//! | 2   | flush   |                                                                        |
+//! | 3   | result  | `u32` result type, `u32` token count, then per token: string, `f32` logprob, `u32` flags, `u64` time in ms |
+
+use crate::april_config::AprilConfigFlags;
+use crate::april_result_type::AprilResultType;
+use crate::april_token::{AprilToken, AprilTokenFlags, AprilTokens};
+use crate::error::{Error, Result};
+use crate::recognizer::{Recognizer, RecognizerSession};
+use std::borrow::Cow;
+use std::fmt::Formatter;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// First bytes of every recording, the last one being the format version.
+pub const MAGIC: &[u8; 8] = b"APRILRC\x02";
+
+const TAG_SESSION: u8 = 0;
+const TAG_AUDIO: u8 = 1;
+const TAG_FLUSH: u8 = 2;
+const TAG_RESULT: u8 = 3;
+
+/// Metadata of the model a recording was made with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub name: String,
+    pub description: String,
+    pub language: String,
+    pub sample_rate: usize,
+}
+
+impl ModelInfo {
+    pub fn of(recognizer: &impl Recognizer) -> Result<Self> {
+        Ok(Self {
+            name: recognizer.get_model_name()?.to_owned(),
+            description: recognizer.get_model_description()?.to_owned(),
+            language: recognizer.get_model_language()?.to_owned(),
+            sample_rate: recognizer.get_sample_rate(),
+        })
+    }
+}
+
+/// Writes a recording as the sessions it is attached to run. Opt in with
+/// [`crate::AprilConfig::set_recorder`].
+///
+/// Clones share the same file, so one recorder can be attached to several sessions. Their
+/// events are interleaved in the file, each tagged with its session, and
+/// [`Recording::replay`] replays every session on its own. Results are only captured for
+/// sessions with a handler set through [`crate::AprilConfig::set_handler_fn`].
+#[derive(Clone)]
+pub struct Recorder {
+    state: Arc<Mutex<RecorderState>>,
+    /// Session the events are recorded for, as returned by [`Recorder::record_session`]
+    session: u32,
+}
+
+struct RecorderState {
+    writer: Box<dyn Write + Send>,
+    /// First write error, reported by [`Recorder::finish`]. Nothing is written after it.
+    error: Option<std::io::Error>,
+    /// Number of sessions recorded so far
+    sessions: u32,
+}
+
+impl Recorder {
+    /// Create a recording file at `path` for sessions of `model`.
+    pub fn create(path: impl AsRef<Path>, model: &impl Recognizer) -> Result<Self> {
+        let file = File::create(path).map_err(Error::Recording)?;
+        Self::new(BufWriter::new(file), &ModelInfo::of(model)?)
+    }
+
+    /// Start a recording written to `writer`.
+    pub fn new(mut writer: impl Write + Send + 'static, model: &ModelInfo) -> Result<Self> {
+        let mut header = MAGIC.to_vec();
+        put_str(&mut header, &model.name);
+        put_str(&mut header, &model.description);
+        put_str(&mut header, &model.language);
+        header.extend_from_slice(&(model.sample_rate as u64).to_le_bytes());
+        writer.write_all(&header).map_err(Error::Recording)?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(RecorderState {
+                writer: Box::new(writer),
+                error: None,
+                sessions: 0,
+            })),
+            session: 0,
+        })
+    }
+
+    /// Flush the recording, returning the first error met while writing it.
+    pub fn finish(&self) -> Result<()> {
+        let mut state = self.lock();
+        if let Some(e) = state.error.take() {
+            return Err(Error::Recording(e));
+        }
+        state.writer.flush().map_err(Error::Recording)
+    }
+
+    /// Record the creation of a new session, returning the recorder for its events.
+    pub(crate) fn record_session(&self, flags: AprilConfigFlags) -> Recorder {
+        let mut state = self.lock();
+        let session = Recorder {
+            state: Arc::clone(&self.state),
+            session: state.sessions,
+        };
+        state.sessions += 1;
+        let mut event = session.event(TAG_SESSION);
+        event.extend_from_slice(&flags.bits().to_le_bytes());
+        Self::write_locked(&mut state, &event);
+        session
+    }
+
+    pub(crate) fn record_audio(&self, pcm: &[i16]) {
+        let mut event = self.event(TAG_AUDIO);
+        event.reserve(4 + pcm.len() * 2);
+        event.extend_from_slice(&(pcm.len() as u32).to_le_bytes());
+        for sample in pcm {
+            event.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.write(&event);
+    }
+
+    pub(crate) fn record_flush(&self) {
+        self.write(&self.event(TAG_FLUSH));
+    }
+
+    pub(crate) fn record_result(&self, result: AprilResultType, tokens: &AprilTokens) {
+        let mut event = self.event(TAG_RESULT);
+        event.extend_from_slice(&result_type_code(result).to_le_bytes());
+        event.extend_from_slice(&(tokens.0.len() as u32).to_le_bytes());
+        for token in &tokens.0 {
+            put_str(&mut event, &token.token);
+            event.extend_from_slice(&token.logprob.to_le_bytes());
+            event.extend_from_slice(&token.flag_bits.bits().to_le_bytes());
+            event.extend_from_slice(&(token.time_ms as u64).to_le_bytes());
+        }
+        self.write(&event);
+    }
+
+    /// Start an event of this recorder's session.
+    fn event(&self, tag: u8) -> Vec<u8> {
+        let mut event = vec![tag];
+        event.extend_from_slice(&self.session.to_le_bytes());
+        event
+    }
+
+    fn write(&self, event: &[u8]) {
+        Self::write_locked(&mut self.lock(), event);
+    }
+
+    fn write_locked(state: &mut RecorderState, event: &[u8]) {
+        if state.error.is_none() {
+            if let Err(e) = state.writer.write_all(event) {
+                state.error = Some(e);
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RecorderState> {
+        // a panic while holding the lock can't leave an event half-built, only half-written
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl std::fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+/// A result as recorded, or as produced by [`Recording::replay`].
+#[derive(Debug, Clone)]
+pub struct RecordedResult {
+    pub result: AprilResultType,
+    pub tokens: AprilTokens<'static>,
+}
+
+impl RecordedResult {
+    /// Whether both results are of the same type with the same text, ignoring token timing
+    /// and probabilities, which differ between models.
+    pub fn same_as(&self, other: &RecordedResult) -> bool {
+        self.result == other.result && self.tokens.to_string() == other.tokens.to_string()
+    }
+}
+
+impl std::fmt::Display for RecordedResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {:?}", self.result, self.tokens.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RecordedEvent {
+    /// A session was created with these flags
+    Session(AprilConfigFlags),
+    /// One call to `feed_pcm16`
+    Audio(Vec<i16>),
+    Flush,
+    Result(RecordedResult),
+}
+
+/// A recording read back from a file written by a [`Recorder`].
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub model: ModelInfo,
+    /// Every event with the number of the session it belongs to, in the order they happened.
+    pub events: Vec<(u32, RecordedEvent)>,
+}
+
+impl Recording {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path).map_err(Error::Recording)?;
+        Self::read_from(BufReader::new(file))
+    }
+
+    pub fn read_from(mut reader: impl Read) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(Error::Recording)?;
+        Self::parse(&bytes).map_err(|reason| {
+            Error::Recording(std::io::Error::new(std::io::ErrorKind::InvalidData, reason))
+        })
+    }
+
+    fn parse(bytes: &[u8]) -> std::result::Result<Self, &'static str> {
+        let mut input = Input(bytes);
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err("not an april recording, or from another version");
+        }
+        let model = ModelInfo {
+            name: input.string()?,
+            description: input.string()?,
+            language: input.string()?,
+            sample_rate: input.u64()? as usize,
+        };
+
+        let mut events = Vec::new();
+        while !input.0.is_empty() {
+            let tag = input.take(1)?[0];
+            let session = input.u32()?;
+            let event = match tag {
+                TAG_SESSION => {
+                    RecordedEvent::Session(AprilConfigFlags::from_bits_retain(input.u32()? as i32))
+                }
+                TAG_AUDIO => {
+                    let len = input.u32()? as usize;
+                    let samples = input.take(len.checked_mul(2).ok_or("audio too long")?)?;
+                    RecordedEvent::Audio(
+                        samples
+                            .chunks_exact(2)
+                            .map(|s| i16::from_le_bytes([s[0], s[1]]))
+                            .collect(),
+                    )
+                }
+                TAG_FLUSH => RecordedEvent::Flush,
+                TAG_RESULT => {
+                    let result = AprilResultType::from(input.u32()?);
+                    let count = input.u32()?;
+                    let mut tokens = Vec::new();
+                    for _ in 0..count {
+                        let token = input.string()?;
+                        let logprob = f32::from_bits(input.u32()?);
+                        let flags = AprilTokenFlags::from_bits_retain(input.u32()?);
+                        let time_ms = input.u64()? as usize;
+                        tokens.push(AprilToken::new(Cow::Owned(token), logprob, flags, time_ms));
+                    }
+                    RecordedEvent::Result(RecordedResult {
+                        result,
+                        tokens: AprilTokens(tokens),
+                    })
+                }
+                _ => return Err("unknown event in recording"),
+            };
+            events.push((session, event));
+        }
+        Ok(Self { model, events })
+    }
+
+    /// The numbers of the recorded sessions, in the order they were created.
+    pub fn sessions(&self) -> Vec<u32> {
+        let mut sessions: Vec<u32> = self.events.iter().map(|(session, _)| *session).collect();
+        sessions.sort_unstable();
+        sessions.dedup();
+        sessions
+    }
+
+    /// The events of one session, in the order they happened.
+    pub fn session_events(&self, session: u32) -> impl Iterator<Item = &RecordedEvent> {
+        self.events
+            .iter()
+            .filter(move |(of, _)| *of == session)
+            .map(|(_, event)| event)
+    }
+
+    /// The results emitted while recording, session by session.
+    pub fn results(&self) -> Vec<RecordedResult> {
+        self.sessions()
+            .into_iter()
+            .flat_map(|session| {
+                self.session_events(session)
+                    .filter_map(|event| match event {
+                        RecordedEvent::Result(result) => Some(result.clone()),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    /// Feed the recorded audio and flushes of every session to a new session of
+    /// `recognizer`, with the same chunk boundaries, and return the results they emit,
+    /// session by session like [`Self::results`].
+    ///
+    /// Sessions are replayed one after the other and synchronously whatever flags they were
+    /// recorded with, so results only depend on the audio. The recognizer must run at the
+    /// recorded sample rate.
+    pub fn replay(&self, recognizer: &impl Recognizer) -> Result<Vec<RecordedResult>> {
+        if recognizer.get_sample_rate() != self.model.sample_rate {
+            return Err(Error::SampleRate {
+                expected: recognizer.get_sample_rate(),
+                actual: self.model.sample_rate,
+            });
+        }
+        let results = Arc::new(Mutex::new(Vec::new()));
+        for number in self.sessions() {
+            let sink = results.clone();
+            let mut session = recognizer.new_session(
+                AprilConfigFlags::empty(),
+                Box::new(move |result, tokens| {
+                    sink.lock().unwrap().push(RecordedResult {
+                        result,
+                        tokens: tokens.into_owned(),
+                    })
+                }),
+            )?;
+            for event in self.session_events(number) {
+                match event {
+                    RecordedEvent::Audio(samples) => session.feed_pcm16(&mut samples.clone()),
+                    RecordedEvent::Flush => session.flush(),
+                    RecordedEvent::Session(_) | RecordedEvent::Result(_) => {}
+                }
+            }
+        }
+        let results = std::mem::take(&mut *results.lock().unwrap());
+        Ok(results)
+    }
+
+    /// Compare the recorded results with `replayed` ones. They are lined up first with as few
+    /// differences as possible, so that a result more or less in the replay, as when audio is
+    /// chunked differently, is one difference rather than a shift of all the later ones.
+    /// Results only count as changed if they are of the same type.
+    pub fn diff(&self, replayed: &[RecordedResult]) -> Vec<ResultDiff> {
+        diff_results(&self.results(), replayed)
+    }
+}
+
+/// The differences left once `recorded` and `replayed` are lined up, see [`Recording::diff`].
+fn diff_results(recorded: &[RecordedResult], replayed: &[RecordedResult]) -> Vec<ResultDiff> {
+    let (n, m) = (recorded.len(), replayed.len());
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    // the number of differences between the results from `i` on and the replayed ones from
+    // `j` on, or `None` if they can't be matched
+    let matched = |i: usize, j: usize| {
+        let (recorded, replayed) = (&recorded[i], &replayed[j]);
+        (recorded.result == replayed.result).then(|| usize::from(!recorded.same_as(replayed)))
+    };
+    let mut costs = vec![0; (n + 1) * (m + 1)];
+    for i in (0..=n).rev() {
+        for j in (0..=m).rev() {
+            costs[at(i, j)] = if i == n || j == m {
+                (n - i) + (m - j)
+            } else {
+                let skip = costs[at(i + 1, j)].min(costs[at(i, j + 1)]) + 1;
+                matched(i, j).map_or(skip, |cost| skip.min(costs[at(i + 1, j + 1)] + cost))
+            };
+        }
+    }
+
+    // walk from the start, matching results as early as possible
+    let mut diffs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let cost = costs[at(i, j)];
+        if i < n && j < m {
+            if let Some(changed) = matched(i, j).filter(|c| costs[at(i + 1, j + 1)] + c == cost) {
+                if changed == 1 {
+                    diffs.push(ResultDiff::Changed {
+                        recorded_index: i,
+                        replayed_index: j,
+                        recorded: recorded[i].clone(),
+                        replayed: replayed[j].clone(),
+                    });
+                }
+                i += 1;
+                j += 1;
+                continue;
+            }
+        }
+        if i < n && costs[at(i + 1, j)] + 1 == cost {
+            diffs.push(ResultDiff::Missing {
+                recorded_index: i,
+                recorded: recorded[i].clone(),
+            });
+            i += 1;
+        } else {
+            diffs.push(ResultDiff::Extra {
+                replayed_index: j,
+                replayed: replayed[j].clone(),
+            });
+            j += 1;
+        }
+    }
+    diffs
+}
+
+/// A difference between recorded and replayed results, see [`Recording::diff`]. Indices are
+/// positions in the recorded and replayed results.
+#[derive(Debug, Clone)]
+pub enum ResultDiff {
+    /// A recorded result replayed with another text
+    Changed {
+        recorded_index: usize,
+        replayed_index: usize,
+        recorded: RecordedResult,
+        replayed: RecordedResult,
+    },
+    /// A recorded result the replay did not produce
+    Missing {
+        recorded_index: usize,
+        recorded: RecordedResult,
+    },
+    /// A replayed result that was not recorded
+    Extra {
+        replayed_index: usize,
+        replayed: RecordedResult,
+    },
+}
+
+impl std::fmt::Display for ResultDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultDiff::Changed {
+                recorded_index,
+                replayed_index,
+                recorded,
+                replayed,
+            } => write!(
+                f,
+                "#{}: recorded {}, replayed #{}: {}",
+                recorded_index, recorded, replayed_index, replayed
+            ),
+            ResultDiff::Missing {
+                recorded_index,
+                recorded,
+            } => write!(
+                f,
+                "#{}: recorded {}, missing from replay",
+                recorded_index, recorded
+            ),
+            ResultDiff::Extra {
+                replayed_index,
+                replayed,
+            } => write!(
+                f,
+                "not recorded, replayed #{}: {}",
+                replayed_index, replayed
+            ),
+        }
+    }
+}
+
+fn result_type_code(result: AprilResultType) -> u32 {
+    match result {
+        AprilResultType::Unknown => 0,
+        AprilResultType::RecognitionPartial => 1,
+        AprilResultType::RecognitionFinal => 2,
+        AprilResultType::ErrorCantKeepUp => 3,
+        AprilResultType::Silence => 4,
+        AprilResultType::Other(code) => code,
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Cursor over the bytes of a recording.
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], &'static str> {
+        if self.0.len() < len {
+            return Err("truncated recording");
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> std::result::Result<u64, &'static str> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> std::result::Result<String, &'static str> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "invalid UTF-8 in recording")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripted::ScriptedRecognizer;
+
+    /// A writer whose bytes stay readable after the recorder took it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn result(result: AprilResultType, text: &str) -> RecordedResult {
+        let flags = AprilTokenFlags::WORD_BOUNDARY;
+        RecordedResult {
+            result,
+            tokens: AprilTokens(vec![AprilToken::new(
+                Cow::Owned(text.to_owned()),
+                -0.5,
+                flags,
+                100,
+            )]),
+        }
+    }
+
+    #[test]
+    fn round_trips_interleaved_sessions() {
+        let recognizer = ScriptedRecognizer::new(16000)
+            .partial_at(500, "HELLO")
+            .final_on_flush("HELLO.");
+        let bytes = Shared::default();
+        let recorder = Recorder::new(bytes.clone(), &ModelInfo::of(&recognizer).unwrap()).unwrap();
+        let partial = result(AprilResultType::RecognitionPartial, " HELLO");
+        let last = result(AprilResultType::RecognitionFinal, " HELLO.");
+
+        // two sessions sharing the recorder, fed at the same time
+        let first = recorder.record_session(AprilConfigFlags::empty());
+        let second = recorder.record_session(AprilConfigFlags::ASYNC_RT);
+        first.record_audio(&[0; 8000]);
+        second.record_audio(&[1; 16000]);
+        first.record_result(partial.result, &partial.tokens);
+        second.record_result(partial.result, &partial.tokens);
+        first.record_flush();
+        first.record_result(last.result, &last.tokens);
+        recorder.finish().unwrap();
+
+        let bytes = bytes.0.lock().unwrap().clone();
+        let recording = Recording::read_from(&bytes[..]).unwrap();
+        assert_eq!(recording.model.name, "scripted");
+        assert_eq!(recording.model.sample_rate, 16000);
+        assert_eq!(recording.sessions(), [0, 1]);
+        assert!(matches!(
+            recording.session_events(1).collect::<Vec<_>>()[..],
+            [RecordedEvent::Session(flags), RecordedEvent::Audio(audio), RecordedEvent::Result(_)]
+                if *flags == AprilConfigFlags::ASYNC_RT && audio.len() == 16000
+        ));
+        let texts = |results: &[RecordedResult]| -> Vec<String> {
+            results.iter().map(ToString::to_string).collect()
+        };
+        assert_eq!(
+            texts(&recording.results()),
+            texts(&[partial.clone(), last, partial])
+        );
+
+        // each session is replayed on its own, so only the first one is flushed
+        let replayed = recording.replay(&recognizer).unwrap();
+        assert!(recording.diff(&replayed).is_empty(), "{:?}", replayed);
+
+        let invalid = |bytes: &[u8]| match Recording::read_from(bytes) {
+            Err(Error::Recording(e)) => e.kind() == std::io::ErrorKind::InvalidData,
+            _ => false,
+        };
+        for len in [0, 4, MAGIC.len() + 3, bytes.len() - 1] {
+            assert!(invalid(&bytes[..len]), "truncated to {} bytes", len);
+        }
+        let mut other_version = bytes.clone();
+        other_version[MAGIC.len() - 1] = 1;
+        assert!(invalid(&other_version));
+        let mut unknown_event = bytes.clone();
+        unknown_event.extend([9, 0, 0, 0, 0]);
+        assert!(invalid(&unknown_event));
+    }
+
+    #[test]
+    fn diffs_aligned_results() {
+        let partial = |text| result(AprilResultType::RecognitionPartial, text);
+        let last = |text| result(AprilResultType::RecognitionFinal, text);
+        let recorded = [
+            partial(" ONE"),
+            partial(" ONE TWO"),
+            last(" ONE TWO."),
+            partial(" THREE"),
+            last(" THREE."),
+        ];
+        assert!(diff_results(&recorded, &recorded).is_empty());
+
+        // a partial less, a partial more and a final changed
+        let replayed = [
+            partial(" ONE TWO"),
+            last(" ONE TOO."),
+            partial(" THREE"),
+            partial(" THREE FOUR"),
+            last(" THREE."),
+        ];
+        let diffs: Vec<String> = diff_results(&recorded, &replayed)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            diffs,
+            [
+                "#0: recorded partially completed: \" ONE\", missing from replay",
+                "#2: recorded final result: \" ONE TWO.\", replayed #1: final result: \" ONE TOO.\"",
+                "not recorded, replayed #3: partially completed: \" THREE FOUR\"",
+            ]
+        );
+
+        // results of another type are never changed into each other
+        let diffs = diff_results(&recorded[2..3], &replayed[..1]);
+        assert!(matches!(
+            diffs[..],
+            [
+                ResultDiff::Missing {
+                    recorded_index: 0,
+                    ..
+                },
+                ResultDiff::Extra {
+                    replayed_index: 0,
+                    ..
+                }
+            ]
+        ));
+    }
+}
@@ -0,0 +1,358 @@
+use crate::april_token::{AprilTokens, AprilWord};
+use std::collections::HashMap;
+
+const SENTENCE_ENDS: [char; 3] = ['.', '!', '?'];
+
+/// English words always capitalized, besides "I". "May" and "March" are left out, as they are
+/// more often verbs.
+const CAPITALIZED: [&str; 18] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+    "january",
+    "february",
+    "april",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+    "english",
+];
+
+/// Why a [`TextRestorer`] added a punctuation mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PunctuationReason {
+    /// The pause before the next word was this long
+    Silence { gap_ms: usize },
+    /// The result ended without a sentence end
+    EndOfResult,
+}
+
+/// A change made by a [`TextRestorer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The casing of the word changed from `original`
+    Cased { original: String },
+    /// `mark` was added after the word
+    Punctuated {
+        mark: char,
+        reason: PunctuationReason,
+    },
+}
+
+/// A change to the word at index `word` of [`Restored::words`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub word: usize,
+    pub change: Change,
+}
+
+/// Words with casing and punctuation restored, and what was changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Restored {
+    pub words: Vec<AprilWord>,
+    pub annotations: Vec<Annotation>,
+}
+
+impl std::fmt::Display for Restored {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, word) in self.words.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(&word.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Restores casing and punctuation in the results of models emitting uncased text without
+/// punctuation.
+///
+/// Sentences end after long pauses and at the end of each result. For models set up with
+/// [`TextRestorer::set_model_ends_sentences`], they only end at the `.`, `!` and `?` the model
+/// emitted instead. All models get commas after shorter pauses. Pauses run from the time of the
+/// last token of a word to that of the first token of the next, so they include how long the
+/// last token lasted.
+///
+/// Words the model emitted all in one case are cased: as given to
+/// [`TextRestorer::add_casing`], else as most often seen by [`TextRestorer::learn`], else in
+/// lowercase. English rules then capitalize sentence starts, "I", weekdays and months.
+/// Words with mixed case are left alone.
+///
+/// ```
+/// use april_asr_rs::{AprilWord, TextRestorer};
+///
+/// let words: Vec<AprilWord> = [("HELLO", 0), ("I'M", 200), ("USING", 1500), ("APRIL", 1700)]
+///     .into_iter()
+///     .map(|(text, start_ms)| AprilWord {
+///         text: text.to_owned(),
+///         logprob: 0.0,
+///         start_ms,
+///         end_ms: start_ms + 100,
+///     })
+///     .collect();
+/// let mut restorer = TextRestorer::new();
+/// restorer.add_casing(&["April"]);
+/// let restored = restorer.restore_words(&words);
+/// assert_eq!(restored.to_string(), "Hello I'm. Using April.");
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextRestorer {
+    comma_gap_ms: usize,
+    sentence_gap_ms: usize,
+    /// Whether the model emits [`crate::AprilTokenFlags::SENTENCE_END`] tokens
+    model_ends_sentences: bool,
+    /// Casing of words by their lowercase form, from [`TextRestorer::add_casing`]
+    fixed: HashMap<String, String>,
+    /// Counts of each casing of words by their lowercase form, from [`TextRestorer::learn`]
+    learned: HashMap<String, HashMap<String, usize>>,
+}
+
+impl Default for TextRestorer {
+    fn default() -> Self {
+        Self {
+            comma_gap_ms: 600,
+            sentence_gap_ms: 1000,
+            model_ends_sentences: false,
+            fixed: HashMap::new(),
+            learned: HashMap::new(),
+        }
+    }
+}
+
+impl TextRestorer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pauses after which to add a comma, 600ms by default, and to end the sentence, 1s by
+    /// default.
+    pub fn set_gaps(&mut self, comma_ms: usize, sentence_ms: usize) -> &mut Self {
+        self.comma_gap_ms = comma_ms;
+        self.sentence_gap_ms = sentence_ms;
+        self
+    }
+
+    /// Whether the model ends sentences itself with [`crate::AprilTokenFlags::SENTENCE_END`]
+    /// tokens, which end words with `.`, `!` or `?`. Off by default. Results of such models
+    /// without one, such as partial results, then get no sentence ends added.
+    pub fn set_model_ends_sentences(&mut self, ends_sentences: bool) -> &mut Self {
+        self.model_ends_sentences = ends_sentences;
+        self
+    }
+
+    /// Always case these words as given, as in "iPhone" or "NASA".
+    pub fn add_casing(&mut self, words: &[&str]) -> &mut Self {
+        for word in words {
+            self.fixed.insert(word.to_lowercase(), word.to_string());
+        }
+        self
+    }
+
+    /// Learn the casing of words from cased and punctuated `text`, such as documents of the
+    /// domain. Words starting sentences don't count, as they are capitalized anyway.
+    pub fn learn(&mut self, text: &str) -> &mut Self {
+        let mut sentence_start = true;
+        for word in text.split_whitespace() {
+            let core = word.trim_matches(|c: char| !c.is_alphanumeric());
+            if !core.is_empty() && !sentence_start {
+                *self
+                    .learned
+                    .entry(core.to_lowercase())
+                    .or_default()
+                    .entry(core.to_owned())
+                    .or_default() += 1;
+            }
+            if !core.is_empty() || word.ends_with(SENTENCE_ENDS) {
+                sentence_start = word.ends_with(SENTENCE_ENDS);
+            }
+        }
+        self
+    }
+
+    pub fn restore(&self, tokens: &AprilTokens) -> Restored {
+        self.restore_words(&tokens.words())
+    }
+
+    /// Restore words, as given by [`AprilTokens::words`].
+    pub fn restore_words(&self, words: &[AprilWord]) -> Restored {
+        let mut restored = Restored {
+            words: Vec::with_capacity(words.len()),
+            annotations: Vec::new(),
+        };
+        let mut sentence_start = true;
+        for (i, word) in words.iter().enumerate() {
+            let mut text = self.case(&word.text, sentence_start);
+            if text != word.text {
+                restored.annotations.push(Annotation {
+                    word: i,
+                    change: Change::Cased {
+                        original: word.text.clone(),
+                    },
+                });
+            }
+
+            if !text.ends_with(|c: char| c.is_ascii_punctuation() && c != '\'') {
+                let mark = match words.get(i + 1) {
+                    Some(next) => {
+                        let gap_ms = next.start_ms.saturating_sub(word.end_ms);
+                        let reason = PunctuationReason::Silence { gap_ms };
+                        if gap_ms >= self.sentence_gap_ms && !self.model_ends_sentences {
+                            Some(('.', reason))
+                        } else if gap_ms >= self.comma_gap_ms {
+                            Some((',', reason))
+                        } else {
+                            None
+                        }
+                    }
+                    None if !self.model_ends_sentences => {
+                        Some(('.', PunctuationReason::EndOfResult))
+                    }
+                    None => None,
+                };
+                if let Some((mark, reason)) = mark {
+                    text.push(mark);
+                    restored.annotations.push(Annotation {
+                        word: i,
+                        change: Change::Punctuated { mark, reason },
+                    });
+                }
+            }
+
+            sentence_start = text.ends_with(SENTENCE_ENDS);
+            restored.words.push(AprilWord {
+                text,
+                ..word.clone()
+            });
+        }
+        restored
+    }
+
+    /// Case `word`, keeping any punctuation around it.
+    fn case(&self, word: &str, sentence_start: bool) -> String {
+        let has_upper = word.chars().any(char::is_uppercase);
+        let has_lower = word.chars().any(char::is_lowercase);
+        if has_upper && has_lower {
+            return word.to_owned();
+        }
+        let lower = word.to_lowercase();
+        let core = lower.trim_matches(|c: char| !c.is_alphanumeric());
+        let cased = if let Some(fixed) = self.fixed.get(core) {
+            fixed.clone()
+        } else if let Some(forms) = self.learned.get(core) {
+            let (form, _) = forms
+                .iter()
+                .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))
+                .expect("learned words have at least one form");
+            form.clone()
+        } else if core == "i" || core.starts_with("i'") || CAPITALIZED.contains(&core) {
+            capitalize(core)
+        } else {
+            core.to_owned()
+        };
+        // forms with capitals, as in "iPhone", stay as they are at sentence starts too
+        let cased = if sentence_start && !cased.chars().any(char::is_uppercase) {
+            capitalize(&cased)
+        } else {
+            cased
+        };
+        lower.replacen(core, &cased, 1)
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[(&str, usize)]) -> Vec<AprilWord> {
+        words
+            .iter()
+            .map(|&(text, start_ms)| AprilWord {
+                text: text.to_owned(),
+                logprob: 0.0,
+                start_ms,
+                end_ms: start_ms + 50,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn restores_lowercase_words() {
+        let words = words(&[
+            ("well", 0),
+            ("i", 700),
+            ("met", 800),
+            ("nasa", 900),
+            ("on", 1000),
+            ("monday", 1100),
+            ("the", 2200),
+            ("iphone", 2300),
+            ("broke", 2400),
+        ]);
+        let mut restorer = TextRestorer::new();
+        restorer
+            .add_casing(&["NASA"])
+            .learn("The new iPhone. An iPhone or two, and an IPHONE!");
+        let restored = restorer.restore_words(&words);
+        assert_eq!(
+            restored.to_string(),
+            "Well, I met NASA on Monday. The iPhone broke."
+        );
+        let cased = |word, original: &str| Annotation {
+            word,
+            change: Change::Cased {
+                original: original.to_owned(),
+            },
+        };
+        let punctuated = |word, mark, reason| Annotation {
+            word,
+            change: Change::Punctuated { mark, reason },
+        };
+        assert_eq!(
+            restored.annotations,
+            [
+                cased(0, "well"),
+                punctuated(0, ',', PunctuationReason::Silence { gap_ms: 650 }),
+                cased(1, "i"),
+                cased(3, "nasa"),
+                cased(5, "monday"),
+                punctuated(5, '.', PunctuationReason::Silence { gap_ms: 1050 }),
+                cased(6, "the"),
+                cased(7, "iphone"),
+                punctuated(8, '.', PunctuationReason::EndOfResult),
+            ]
+        );
+        // words the model cased itself are kept
+        assert_eq!(restorer.restore_words(&words[3..4]).words[0].text, "NASA.");
+    }
+
+    #[test]
+    fn leaves_sentence_ends_to_models_that_emit_them() {
+        let words = words(&[("yes.", 0), ("so", 2000), ("i", 2100), ("left", 2200)]);
+        let mut restorer = TextRestorer::new();
+        restorer.set_model_ends_sentences(true);
+        // neither the long pause nor the end of the result end a sentence
+        assert_eq!(restorer.restore_words(&words).to_string(), "Yes. So I left");
+        restorer.set_model_ends_sentences(false);
+        assert_eq!(
+            restorer.restore_words(&words).to_string(),
+            "Yes. So I left."
+        );
+    }
+}
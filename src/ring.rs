@@ -0,0 +1,337 @@
+use crate::recognizer::RecognizerSession;
+use std::sync::atomic::{AtomicBool, AtomicI16, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a blocked producer or an idle consumer sleeps between checks of the ring.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// What [`RingProducer::push`] does with audio that doesn't fit in the ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the consumer to make room
+    Block,
+    /// Discard the oldest buffered audio to make room, keeping latency bounded
+    DropOldest,
+    /// Discard the audio that doesn't fit
+    DropNewest,
+}
+
+/// Counters of a ring, shared by both ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RingStats {
+    /// Samples accepted by [`RingProducer::push`]
+    pub pushed: u64,
+    /// Samples discarded by [`OverflowPolicy::DropOldest`] or [`OverflowPolicy::DropNewest`]
+    pub dropped: u64,
+    /// Calls to [`RingProducer::push`] that found the ring full
+    pub overruns: u64,
+    /// Times [`RingConsumer::drive`] had to wait for audio
+    pub underruns: u64,
+}
+
+/// Create a ring holding up to `capacity` samples, for one thread producing audio and another
+/// one feeding it to a session with [`RingConsumer::drive`].
+///
+/// Pushing and popping never lock. Only a producer blocked by [`OverflowPolicy::Block`] and
+/// an idle consumer wait, by polling.
+///
+/// ```
+/// use april_asr_rs::{audio_ring, AprilConfigFlags, OverflowPolicy, Recognizer, ScriptedRecognizer};
+///
+/// let recognizer = ScriptedRecognizer::new(16000).final_on_flush("DONE.");
+/// let (mut producer, mut consumer) = audio_ring(16000, OverflowPolicy::Block);
+/// let mut session = recognizer
+///     .new_session(AprilConfigFlags::empty(), Box::new(|result, tokens| println!("{}: {}", result, tokens)))
+///     .unwrap();
+/// std::thread::scope(|s| {
+///     s.spawn(|| consumer.drive(&mut session, 1600));
+///     for _ in 0..10 {
+///         producer.push(&[0; 1600]);
+///     }
+///     // the consumer feeds what is left and flushes once the producer is gone
+///     drop(producer);
+/// });
+/// assert_eq!(session.samples_fed(), 16000);
+/// ```
+pub fn audio_ring(capacity: usize, policy: OverflowPolicy) -> (RingProducer, RingConsumer) {
+    assert!(
+        capacity > 0,
+        "an audio ring needs room for at least one sample"
+    );
+    let shared = Arc::new(Shared {
+        buf: (0..capacity).map(|_| AtomicI16::new(0)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        producer_gone: AtomicBool::new(false),
+        consumer_gone: AtomicBool::new(false),
+        pushed: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+        overruns: AtomicU64::new(0),
+        underruns: AtomicU64::new(0),
+    });
+    (
+        RingProducer {
+            shared: shared.clone(),
+            policy,
+        },
+        RingConsumer { shared },
+    )
+}
+
+struct Shared {
+    // atomic slots make a read racing with a write well-defined, the loser just retries
+    buf: Box<[AtomicI16]>,
+    /// Position of the oldest sample. Advanced by the consumer, and by the producer when
+    /// dropping the oldest audio, so always with a compare-exchange.
+    head: AtomicUsize,
+    /// Position after the newest sample, only advanced by the producer
+    tail: AtomicUsize,
+    producer_gone: AtomicBool,
+    consumer_gone: AtomicBool,
+    pushed: AtomicU64,
+    dropped: AtomicU64,
+    overruns: AtomicU64,
+    underruns: AtomicU64,
+}
+
+impl Shared {
+    fn stats(&self) -> RingStats {
+        RingStats {
+            pushed: self.pushed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+        }
+    }
+
+    fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        self.tail.load(Ordering::Acquire).wrapping_sub(head)
+    }
+}
+
+/// The writing end of an [`audio_ring`].
+pub struct RingProducer {
+    shared: Arc<Shared>,
+    policy: OverflowPolicy,
+}
+
+impl RingProducer {
+    /// Append `samples`, applying the overflow policy if they don't fit. Returns how many
+    /// samples were accepted, which is less than all of them when audio is dropped or the
+    /// consumer is gone.
+    pub fn push(&mut self, mut samples: &[i16]) -> usize {
+        let shared = &*self.shared;
+        let capacity = shared.buf.len();
+        let mut accepted = 0;
+        let mut overrun = false;
+        while !samples.is_empty() && !shared.consumer_gone.load(Ordering::Acquire) {
+            let tail = shared.tail.load(Ordering::Relaxed);
+            let head = shared.head.load(Ordering::Acquire);
+            let free = capacity - tail.wrapping_sub(head);
+            if free < samples.len() {
+                overrun = true;
+            }
+
+            let count = match self.policy {
+                OverflowPolicy::Block => {
+                    if free == 0 {
+                        std::thread::sleep(POLL_INTERVAL);
+                        continue;
+                    }
+                    free.min(samples.len())
+                }
+                OverflowPolicy::DropNewest => {
+                    let count = free.min(samples.len());
+                    shared
+                        .dropped
+                        .fetch_add((samples.len() - count) as u64, Ordering::Relaxed);
+                    samples = &samples[..count];
+                    count
+                }
+                OverflowPolicy::DropOldest => {
+                    if samples.len() > capacity {
+                        // only the newest `capacity` samples can be kept anyway
+                        let skipped = samples.len() - capacity;
+                        shared.dropped.fetch_add(skipped as u64, Ordering::Relaxed);
+                        samples = &samples[skipped..];
+                    }
+                    if free < samples.len() {
+                        let needed = samples.len() - free;
+                        let moved = shared.head.compare_exchange(
+                            head,
+                            head.wrapping_add(needed),
+                            Ordering::AcqRel,
+                            Ordering::Acquire,
+                        );
+                        if moved.is_err() {
+                            // the consumer took some audio meanwhile, so less may be needed
+                            continue;
+                        }
+                        shared.dropped.fetch_add(needed as u64, Ordering::Relaxed);
+                    }
+                    samples.len()
+                }
+            };
+
+            for (i, &sample) in samples[..count].iter().enumerate() {
+                shared.buf[tail.wrapping_add(i) % capacity].store(sample, Ordering::Relaxed);
+            }
+            shared
+                .tail
+                .store(tail.wrapping_add(count), Ordering::Release);
+            accepted += count;
+            samples = &samples[count..];
+        }
+        if overrun {
+            shared.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        shared.pushed.fetch_add(accepted as u64, Ordering::Relaxed);
+        accepted
+    }
+
+    /// Number of samples buffered and not taken by the consumer yet.
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.buf.len()
+    }
+
+    pub fn stats(&self) -> RingStats {
+        self.shared.stats()
+    }
+}
+
+impl Drop for RingProducer {
+    fn drop(&mut self) {
+        self.shared.producer_gone.store(true, Ordering::Release);
+    }
+}
+
+/// The reading end of an [`audio_ring`].
+pub struct RingConsumer {
+    shared: Arc<Shared>,
+}
+
+impl RingConsumer {
+    /// Move up to `max` of the oldest samples into `out`, returning how many were moved.
+    pub fn pop_into(&mut self, out: &mut Vec<i16>, max: usize) -> usize {
+        let shared = &*self.shared;
+        let capacity = shared.buf.len();
+        let start = out.len();
+        loop {
+            let head = shared.head.load(Ordering::Acquire);
+            let tail = shared.tail.load(Ordering::Acquire);
+            let count = tail.wrapping_sub(head).min(max);
+            out.extend(
+                (0..count)
+                    .map(|i| shared.buf[head.wrapping_add(i) % capacity].load(Ordering::Relaxed)),
+            );
+            let taken = shared.head.compare_exchange(
+                head,
+                head.wrapping_add(count),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            );
+            if taken.is_ok() {
+                return count;
+            }
+            // the producer dropped the oldest audio while it was copied, which may have been
+            // overwritten, so copy again
+            out.truncate(start);
+        }
+    }
+
+    /// Whether the producer is gone, so no more audio will arrive once the ring is empty.
+    pub fn is_closed(&self) -> bool {
+        self.shared.producer_gone.load(Ordering::Acquire)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> RingStats {
+        self.shared.stats()
+    }
+
+    /// Feed the audio to `session` in chunks of `chunk_samples`, until the producer is gone.
+    /// The last partial chunk is fed then, and the session is flushed.
+    ///
+    /// Feeding full chunks avoids calling april with tiny slices of audio when the producer
+    /// pushes small buffers. 100ms of audio is a good size. [`crate::AprilSession`] can't move
+    /// between threads, so create it on the thread driving it.
+    pub fn drive(&mut self, session: &mut impl RecognizerSession, chunk_samples: usize) {
+        let chunk_samples = chunk_samples.max(1);
+        let mut chunk = Vec::with_capacity(chunk_samples);
+        loop {
+            // read before popping, so that audio pushed right before closing isn't missed
+            let closed = self.is_closed();
+            let wanted = chunk_samples - chunk.len();
+            self.pop_into(&mut chunk, wanted);
+            if chunk.len() == chunk_samples {
+                session.feed_pcm16(&mut chunk);
+                chunk.clear();
+            } else if closed {
+                if !chunk.is_empty() {
+                    session.feed_pcm16(&mut chunk);
+                }
+                session.flush();
+                return;
+            } else {
+                self.shared.underruns.fetch_add(1, Ordering::Relaxed);
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+impl Drop for RingConsumer {
+    fn drop(&mut self) {
+        self.shared.consumer_gone.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflow_policies() {
+        let (mut producer, mut consumer) = audio_ring(4, OverflowPolicy::DropNewest);
+        assert_eq!(producer.push(&[1, 2, 3]), 3);
+        assert_eq!(producer.push(&[4, 5, 6]), 1);
+        let mut out = Vec::new();
+        assert_eq!(consumer.pop_into(&mut out, 10), 4);
+        assert_eq!(out, [1, 2, 3, 4]);
+        let stats = producer.stats();
+        assert_eq!((stats.pushed, stats.dropped, stats.overruns), (4, 2, 1));
+
+        let (mut producer, mut consumer) = audio_ring(4, OverflowPolicy::DropOldest);
+        assert_eq!(producer.push(&[1, 2, 3]), 3);
+        assert_eq!(producer.push(&[4, 5, 6]), 3);
+        assert_eq!(producer.push(&[7, 8, 9, 10, 11]), 4);
+        let mut out = Vec::new();
+        consumer.pop_into(&mut out, 10);
+        assert_eq!(out, [8, 9, 10, 11]);
+        let stats = consumer.stats();
+        assert_eq!((stats.pushed, stats.dropped, stats.overruns), (10, 7, 2));
+
+        let (mut producer, consumer) = audio_ring(4, OverflowPolicy::Block);
+        assert_eq!(producer.push(&[1, 2, 3]), 3);
+        drop(consumer);
+        // nobody is left to read it, so audio is refused instead of blocking forever
+        assert_eq!(producer.push(&[4, 5, 6]), 0);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::april_config::AprilConfigFlags;
+use crate::april_result_type::AprilResultType;
+use crate::april_token::{AprilToken, AprilTokenFlags, AprilTokens};
+use crate::error::{Error, Result};
+use crate::recognizer::{Recognizer, RecognizerSession, ResultHandler};
+use std::borrow::Cow;
+
+/// When a [`ScriptedResult`] is emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    /// Once at least this many milliseconds of audio have been fed to the session
+    Audio { ms: usize },
+    /// On the next call to [`RecognizerSession::flush`]
+    Flush,
+}
+
+/// One pre-programmed result of a [`ScriptedRecognizer`].
+#[derive(Debug, Clone)]
+pub struct ScriptedResult {
+    pub trigger: Trigger,
+    pub result: AprilResultType,
+    /// Text of the result, split into one token per word and sentence-ending punctuation.
+    /// Tokens are timed at the audio fed so far, unless their word ends with `@` and a time in
+    /// milliseconds, as in `"HEY@200 APRIL.@400"`.
+    pub text: String,
+}
+
+/// A fake [`Recognizer`] replaying a fixed sequence of results, for testing without a model.
+///
+/// Every session replays the whole script on its own. Results are emitted in order: a result
+/// only fires once every result before it has, so a [`Trigger::Flush`] result holds back
+/// everything after it until the session is flushed.
+///
+/// ```
+/// use april_asr_rs::{
+///     AprilConfigFlags, AprilResultType, Recognizer, RecognizerSession, ScriptedRecognizer,
+/// };
+/// use std::sync::mpsc;
+///
+/// let recognizer = ScriptedRecognizer::new(16000)
+///     .partial_at(500, "HELLO")
+///     .final_on_flush("HELLO WORLD.")
+///     .silence_on_flush();
+/// let (tx, rx) = mpsc::channel();
+/// let mut session = recognizer
+///     .new_session(AprilConfigFlags::empty(), Box::new(move |result, tokens| {
+///         tx.send((result, tokens.to_string())).unwrap()
+///     }))
+///     .unwrap();
+///
+/// session.feed_pcm16(&mut [0; 16000]);
+/// session.flush();
+/// let results: Vec<_> = rx.try_iter().collect();
+/// assert_eq!(results[0], (AprilResultType::RecognitionPartial, " HELLO".to_owned()));
+/// assert_eq!(results[1], (AprilResultType::RecognitionFinal, " HELLO WORLD.".to_owned()));
+/// assert_eq!(results[2], (AprilResultType::Silence, String::new()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScriptedRecognizer {
+    pub name: String,
+    pub description: String,
+    pub language: String,
+    pub sample_rate: usize,
+    pub realtime_speedup: f32,
+    pub script: Vec<ScriptedResult>,
+}
+
+impl ScriptedRecognizer {
+    /// An English recognizer with an empty script.
+    pub fn new(sample_rate: usize) -> Self {
+        Self {
+            name: "scripted".to_owned(),
+            description: "scripted fake recognizer".to_owned(),
+            language: "en".to_owned(),
+            sample_rate,
+            realtime_speedup: 1.0,
+            script: Vec::new(),
+        }
+    }
+
+    pub fn then(mut self, trigger: Trigger, result: AprilResultType, text: &str) -> Self {
+        self.script.push(ScriptedResult {
+            trigger,
+            result,
+            text: text.to_owned(),
+        });
+        self
+    }
+
+    pub fn partial_at(self, ms: usize, text: &str) -> Self {
+        self.then(
+            Trigger::Audio { ms },
+            AprilResultType::RecognitionPartial,
+            text,
+        )
+    }
+
+    pub fn final_at(self, ms: usize, text: &str) -> Self {
+        self.then(
+            Trigger::Audio { ms },
+            AprilResultType::RecognitionFinal,
+            text,
+        )
+    }
+
+    pub fn silence_at(self, ms: usize) -> Self {
+        self.then(Trigger::Audio { ms }, AprilResultType::Silence, "")
+    }
+
+    pub fn final_on_flush(self, text: &str) -> Self {
+        self.then(Trigger::Flush, AprilResultType::RecognitionFinal, text)
+    }
+
+    pub fn silence_on_flush(self) -> Self {
+        self.then(Trigger::Flush, AprilResultType::Silence, "")
+    }
+}
+
+impl Recognizer for ScriptedRecognizer {
+    type Session<'a> = ScriptedSession<'a>;
+
+    fn get_model_name(&self) -> Result<&str> {
+        Ok(&self.name)
+    }
+
+    fn get_model_description(&self) -> Result<&str> {
+        Ok(&self.description)
+    }
+
+    fn get_model_language(&self) -> Result<&str> {
+        Ok(&self.language)
+    }
+
+    fn get_sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// Results are always emitted synchronously, flags only decide whether
+    /// [`RecognizerSession::get_realtime_speedup`] is available.
+    fn new_session(
+        &self,
+        flags: AprilConfigFlags,
+        handler: ResultHandler,
+    ) -> Result<Self::Session<'_>> {
+        Ok(ScriptedSession {
+            recognizer: self,
+            handler,
+            flags,
+            next: 0,
+            samples_fed: 0,
+        })
+    }
+}
+
+pub struct ScriptedSession<'a> {
+    recognizer: &'a ScriptedRecognizer,
+    handler: ResultHandler,
+    flags: AprilConfigFlags,
+    /// Index of the next result of the script to emit
+    next: usize,
+    samples_fed: usize,
+}
+
+impl ScriptedSession<'_> {
+    /// Number of samples fed so far.
+    pub fn samples_fed(&self) -> usize {
+        self.samples_fed
+    }
+
+    fn fed_ms(&self) -> usize {
+        self.samples_fed * 1000 / self.recognizer.sample_rate.max(1)
+    }
+
+    /// Emit results in order for as long as `ready` allows.
+    fn emit_while(&mut self, ready: impl Fn(Trigger, usize) -> bool) {
+        let fed_ms = self.fed_ms();
+        let recognizer = self.recognizer;
+        while let Some(scripted) = recognizer.script.get(self.next) {
+            if !ready(scripted.trigger, fed_ms) {
+                break;
+            }
+            (self.handler)(scripted.result, tokenize(&scripted.text, fed_ms));
+            self.next += 1;
+        }
+    }
+}
+
+impl RecognizerSession for ScriptedSession<'_> {
+    fn feed_pcm16(&mut self, pcm: &mut [i16]) {
+        self.samples_fed += pcm.len();
+        self.emit_while(|trigger, fed_ms| matches!(trigger, Trigger::Audio { ms } if ms <= fed_ms));
+    }
+
+    fn flush(&mut self) {
+        // a flush releases the results waiting on it, and any audio results now due after them
+        self.emit_while(|trigger, fed_ms| match trigger {
+            Trigger::Audio { ms } => ms <= fed_ms,
+            Trigger::Flush => true,
+        });
+    }
+
+    fn get_realtime_speedup(&self) -> Result<f32> {
+        if !self.flags.contains(AprilConfigFlags::ASYNC_RT) {
+            return Err(Error::SessionMode {
+                operation: "get_realtime_speedup",
+                flags: self.flags,
+            });
+        }
+        Ok(self.recognizer.realtime_speedup)
+    }
+}
+
+/// Split `text` into april-style tokens: each word starts with a space and a word boundary,
+/// and `.`, `!` and `?` at the end of a word are split off as sentence ends.
+fn tokenize(text: &str, time_ms: usize) -> AprilTokens<'static> {
+    let mut tokens = Vec::new();
+    for word in text.split_whitespace() {
+        let (word, time_ms) = match word.rsplit_once('@') {
+            Some((word, ms)) => (word, ms.parse().expect("scripted word times are numbers")),
+            None => (word, time_ms),
+        };
+        let stem = word.trim_end_matches(['.', '!', '?']);
+        if !stem.is_empty() {
+            tokens.push(AprilToken::new(
+                Cow::Owned(format!(" {}", stem)),
+                0.0,
+                AprilTokenFlags::WORD_BOUNDARY,
+                time_ms,
+            ));
+        }
+        for end in word[stem.len()..].chars() {
+            tokens.push(AprilToken::new(
+                Cow::Owned(end.to_string()),
+                0.0,
+                AprilTokenFlags::SENTENCE_END,
+                time_ms,
+            ));
+        }
+    }
+    AprilTokens(tokens)
+}
@@ -0,0 +1,469 @@
+use crate::april_token::{AprilTokens, AprilWord};
+use crate::grammar::{edit_distance, fuzzy_errors};
+use crate::hotword::normalize;
+use std::ops::Range;
+
+/// How the spoken form of a [`VocabularyEntry`] is matched against recognized words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The words must be the same, ignoring case and punctuation
+    #[default]
+    Exact,
+    /// Words may have a character wrong, missing or extra, or two in words of 8 letters and
+    /// more
+    Fuzzy,
+    /// The words must sound the same, compared by their Metaphone keys, and be spelled alike,
+    /// with up to a third of the letters wrong, missing or extra. The words may also be split
+    /// differently, as in "cuber netties" for "kubernetes". Spoken forms shorter than
+    /// [`MIN_PHONETIC_LETTERS`] sound like too many words, and are matched exactly instead.
+    Phonetic,
+}
+
+/// Letters a spoken form needs for [`MatchMode::Phonetic`] to apply.
+pub const MIN_PHONETIC_LETTERS: usize = 4;
+
+/// A phrase to replace, and what to replace it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VocabularyEntry {
+    /// The words as the model recognizes them, as in "cube control"
+    pub spoken: String,
+    /// What to write instead, as in "kubectl"
+    pub written: String,
+    pub mode: MatchMode,
+    /// Give `written` the case of the replaced words when they are all uppercase or
+    /// capitalized. Models emitting uppercase text want this off, to keep the spelling of
+    /// `written`.
+    pub preserve_case: bool,
+}
+
+impl VocabularyEntry {
+    pub fn new(spoken: &str, written: &str, mode: MatchMode) -> Self {
+        Self {
+            spoken: spoken.to_owned(),
+            written: written.to_owned(),
+            mode,
+            preserve_case: true,
+        }
+    }
+}
+
+/// One replacement made by [`Vocabulary::apply_words`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replacement {
+    /// Index of the replacing word in [`Replaced::words`]
+    pub word: usize,
+    /// Index of the [`VocabularyEntry`] that matched
+    pub entry: usize,
+    /// Indices of the replaced words in the input
+    pub original_span: Range<usize>,
+    /// The replaced words
+    pub original: Vec<AprilWord>,
+}
+
+/// Words after replacing vocabulary, and where replacements were made.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replaced {
+    pub words: Vec<AprilWord>,
+    pub replacements: Vec<Replacement>,
+}
+
+impl std::fmt::Display for Replaced {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, word) in self.words.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(&word.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// A dictionary of phrases the model gets wrong, such as product names, and their correct
+/// spelling. Meant for final results.
+///
+/// At each word, the entry covering the most words wins, then the one with the fewest errors,
+/// then the one added first. Replaced words are merged into one word, spanning the time of all
+/// of them with their logprobs summed.
+///
+/// ```
+/// use april_asr_rs::{MatchMode, Vocabulary, VocabularyEntry};
+///
+/// let mut vocabulary = Vocabulary::new();
+/// vocabulary
+///     .add(VocabularyEntry::new("cube control", "kubectl", MatchMode::Exact))
+///     .add(VocabularyEntry::new("kubernetes", "Kubernetes", MatchMode::Phonetic));
+/// let replaced = vocabulary.apply_text("run cube control on cuber netties.");
+/// assert_eq!(replaced.to_string(), "run kubectl on Kubernetes.");
+/// assert_eq!(replaced.replacements[1].original_span, 4..6);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Vocabulary {
+    entries: Vec<CompiledEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledEntry {
+    entry: VocabularyEntry,
+    /// How the entry is matched, which is exact for spoken forms too short to match phonetically
+    mode: MatchMode,
+    /// Normalized words of the spoken form
+    words: Vec<String>,
+    /// The spoken form without spaces
+    spelling: String,
+    /// Metaphone key of the spoken form, without spaces
+    key: String,
+}
+
+impl Vocabulary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an entry. Entries without any word in their spoken form are ignored.
+    pub fn add(&mut self, entry: VocabularyEntry) -> &mut Self {
+        let words: Vec<String> = entry
+            .spoken
+            .split_whitespace()
+            .filter_map(normalize)
+            .collect();
+        if !words.is_empty() {
+            let spelling = words.concat();
+            let mode = match entry.mode {
+                MatchMode::Phonetic if spelling.chars().count() < MIN_PHONETIC_LETTERS => {
+                    MatchMode::Exact
+                }
+                mode => mode,
+            };
+            let key = metaphone(&spelling);
+            self.entries.push(CompiledEntry {
+                entry,
+                mode,
+                words,
+                spelling,
+                key,
+            });
+        }
+        self
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &VocabularyEntry> {
+        self.entries.iter().map(|compiled| &compiled.entry)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn apply(&self, tokens: &AprilTokens) -> Replaced {
+        self.apply_words(&tokens.words())
+    }
+
+    /// Replace vocabulary in words, as given by [`AprilTokens::words`].
+    pub fn apply_words(&self, words: &[AprilWord]) -> Replaced {
+        let spoken: Vec<Option<String>> = words.iter().map(|word| normalize(&word.text)).collect();
+        let mut replaced = Replaced {
+            words: Vec::with_capacity(words.len()),
+            replacements: Vec::new(),
+        };
+        let mut i = 0;
+        while i < words.len() {
+            // a span stops at the first word ending in punctuation
+            let mut span = Vec::new();
+            for (word, spoken) in words[i..].iter().zip(&spoken[i..]) {
+                let Some(spoken) = spoken else { break };
+                span.push(spoken.as_str());
+                if word
+                    .text
+                    .ends_with(|c: char| !c.is_alphanumeric() && c != '\'')
+                {
+                    break;
+                }
+            }
+
+            let best = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(index, entry)| {
+                    let (len, errors) = entry.matches(&span)?;
+                    Some((index, len, errors))
+                })
+                .min_by_key(|(index, len, errors)| (usize::MAX - len, *errors, *index));
+            let Some((index, len, _)) = best else {
+                replaced.words.push(words[i].clone());
+                i += 1;
+                continue;
+            };
+
+            let entry = &self.entries[index].entry;
+            let original = &words[i..i + len];
+            let last = &original[len - 1];
+            let core_end = last
+                .text
+                .trim_end_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+                .len();
+            let mut text = if entry.preserve_case {
+                match_case(&entry.written, original)
+            } else {
+                entry.written.clone()
+            };
+            // keep punctuation after the phrase, as in "kubernetes."
+            text.push_str(&last.text[core_end..]);
+            replaced.replacements.push(Replacement {
+                word: replaced.words.len(),
+                entry: index,
+                original_span: i..i + len,
+                original: original.to_vec(),
+            });
+            replaced.words.push(AprilWord {
+                text,
+                logprob: original.iter().map(|word| word.logprob).sum(),
+                start_ms: original[0].start_ms,
+                end_ms: last.end_ms,
+            });
+            i += len;
+        }
+        replaced
+    }
+
+    /// Replace vocabulary in text split into words at whitespace, mostly useful for testing
+    /// entries.
+    pub fn apply_text(&self, text: &str) -> Replaced {
+        let words: Vec<AprilWord> = text
+            .split_whitespace()
+            .map(|word| AprilWord {
+                text: word.to_owned(),
+                logprob: 0.0,
+                start_ms: 0,
+                end_ms: 0,
+            })
+            .collect();
+        self.apply_words(&words)
+    }
+}
+
+impl CompiledEntry {
+    /// Whether the entry matches the start of `span`, with the number of words matched and the
+    /// errors tolerated.
+    fn matches(&self, span: &[&str]) -> Option<(usize, usize)> {
+        match self.mode {
+            MatchMode::Exact => {
+                let len = self.words.len();
+                (span.len() >= len && span[..len].iter().eq(self.words.iter())).then_some((len, 0))
+            }
+            MatchMode::Fuzzy => {
+                let len = self.words.len();
+                let candidate = span.get(..len)?;
+                let errors = self
+                    .words
+                    .iter()
+                    .zip(candidate)
+                    .map(|(expected, word)| {
+                        if expected == word {
+                            Some(0)
+                        } else {
+                            fuzzy_errors(expected, word)
+                        }
+                    })
+                    .sum::<Option<usize>>()?;
+                Some((len, errors))
+            }
+            MatchMode::Phonetic => {
+                // the same sounds may be split into one more or one less word
+                let lengths = self.words.len().saturating_sub(1).max(1)..=self.words.len() + 1;
+                lengths
+                    .rev()
+                    .filter(|len| *len <= span.len())
+                    .find_map(|len| {
+                        let candidate = span[..len].concat();
+                        if metaphone(&candidate) != self.key {
+                            return None;
+                        }
+                        // sounding the same is not enough for words spelled differently, as
+                        // "key" and "kia"
+                        let errors = edit_distance(&self.spelling, &candidate);
+                        let allowed = self.spelling.chars().count().div_ceil(3);
+                        (errors <= allowed).then_some((len, errors))
+                    })
+            }
+        }
+    }
+}
+
+/// Give `written` the case of `original` if it is all uppercase or capitalized.
+fn match_case(written: &str, original: &[AprilWord]) -> String {
+    let letters = || {
+        original
+            .iter()
+            .flat_map(|word| word.text.chars())
+            .filter(|c| c.is_alphabetic())
+    };
+    if letters().all(char::is_uppercase) && letters().count() > 1 {
+        written.to_uppercase()
+    } else if letters().next().is_some_and(char::is_uppercase) {
+        let mut chars = written.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    } else {
+        written.to_owned()
+    }
+}
+
+/// The Metaphone key of an uppercase word, a rough spelling of how it sounds in English.
+fn metaphone(word: &str) -> String {
+    let letters: Vec<char> = word.chars().filter(char::is_ascii_alphabetic).collect();
+    // adjacent duplicate letters sound as one, except C
+    let mut chars: Vec<char> = Vec::with_capacity(letters.len());
+    for &c in &letters {
+        if chars.last() != Some(&c) || c == 'C' {
+            chars.push(c);
+        }
+    }
+    let mut start = 0;
+    match chars.as_slice() {
+        ['K', 'N', ..] | ['G', 'N', ..] | ['P', 'N', ..] | ['A', 'E', ..] | ['W', 'R', ..] => {
+            start = 1
+        }
+        ['X', ..] => chars[0] = 'S',
+        ['W', 'H', ..] => {
+            chars.remove(1);
+        }
+        _ => {}
+    }
+
+    let is_vowel = |c: Option<&char>| matches!(c, Some('A' | 'E' | 'I' | 'O' | 'U'));
+    let at = |i: usize| chars.get(i);
+    let mut key = String::new();
+    for i in start..chars.len() {
+        let c = chars[i];
+        let prev = i.checked_sub(1).and_then(|i| chars.get(i));
+        let next = at(i + 1);
+        let after = at(i + 2);
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' => {
+                if i == start {
+                    key.push('A');
+                }
+            }
+            'B' => {
+                // silent in a final "mb"
+                if !(prev == Some(&'M') && next.is_none()) {
+                    key.push('B');
+                }
+            }
+            'C' => match (next, after) {
+                (Some('I'), Some('A')) | (Some('H'), _) => {
+                    key.push(if prev == Some(&'S') { 'K' } else { 'X' })
+                }
+                (Some('I' | 'E' | 'Y'), _) => {
+                    if prev != Some(&'S') {
+                        key.push('S')
+                    }
+                }
+                _ => key.push('K'),
+            },
+            'D' => match (next, after) {
+                (Some('G'), Some('E' | 'Y' | 'I')) => key.push('J'),
+                _ => key.push('T'),
+            },
+            'G' => {
+                let silent = (next == Some(&'H') && after.is_some() && !is_vowel(after))
+                    || (next == Some(&'N') && (after.is_none() || chars[i + 2..] == ['E', 'D']));
+                if !silent {
+                    if matches!(next, Some('I' | 'E' | 'Y')) && prev != Some(&'G') {
+                        key.push('J')
+                    } else {
+                        key.push('K')
+                    }
+                }
+            }
+            'H' => {
+                let after_vowel_only = is_vowel(prev) && !is_vowel(next);
+                if !after_vowel_only && !matches!(prev, Some('C' | 'S' | 'P' | 'T' | 'G')) {
+                    key.push('H');
+                }
+            }
+            'K' => {
+                if prev != Some(&'C') {
+                    key.push('K');
+                }
+            }
+            'P' => key.push(if next == Some(&'H') { 'F' } else { 'P' }),
+            'Q' => key.push('K'),
+            'S' => match (next, after) {
+                (Some('H'), _) | (Some('I'), Some('O' | 'A')) => key.push('X'),
+                _ => key.push('S'),
+            },
+            'T' => match (next, after) {
+                (Some('I'), Some('O' | 'A')) => key.push('X'),
+                (Some('H'), _) => key.push('0'),
+                (Some('C'), Some('H')) => {}
+                _ => key.push('T'),
+            },
+            'V' => key.push('F'),
+            'W' | 'Y' => {
+                if is_vowel(next) {
+                    key.push(c);
+                }
+            }
+            'X' => key.push_str("KS"),
+            'Z' => key.push('S'),
+            _ => key.push(c),
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_text() {
+        // capitalized words give capitalized replacements, other words keep the written spelling
+        let mut vocabulary = Vocabulary::new();
+        vocabulary.add(VocabularyEntry::new(
+            "sequel",
+            "postgreSQL",
+            MatchMode::Exact,
+        ));
+        assert_eq!(
+            vocabulary
+                .apply_text("Sequel or sequel, no sequels")
+                .to_string(),
+            "PostgreSQL or postgreSQL, no sequels"
+        );
+    }
+
+    #[test]
+    fn matches_phonetically_only_alike_spellings() {
+        let mut vocabulary = Vocabulary::new();
+        vocabulary
+            .add(VocabularyEntry::new("kia", "Kia", MatchMode::Phonetic))
+            .add(VocabularyEntry::new("fore", "4", MatchMode::Phonetic))
+            .add(VocabularyEntry::new(
+                "cassandra",
+                "Cassandra",
+                MatchMode::Phonetic,
+            ));
+        // "kia" is too short to match phonetically, and "fair" sounds like "fore" but is
+        // spelled too differently
+        assert_eq!(
+            vocabulary
+                .apply_text("let's go to the car key, kia, four or fair")
+                .to_string(),
+            "let's go to the car key, Kia, 4 or fair"
+        );
+        assert_eq!(
+            vocabulary.apply_text("use kasandra").to_string(),
+            "use Cassandra"
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use crate::april_config::AprilConfigFlags;
+use crate::april_result_type::AprilResultType;
+use crate::april_token::AprilTokens;
+use crate::error::{Error, Result};
+use crate::recognizer::{Recognizer, RecognizerSession};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long shutting down an async session waits for the result of its last flush. Async
+/// sessions drop queued audio when freed, so they can't be freed right after flushing.
+///
+/// The worker waits for a final result arriving after its last flush given audio, and for as
+/// many final results as there were such flushes. Final results can't be told apart from the
+/// sentences april ends by itself, so one of those arriving first ends the wait early. A flush
+/// that ends no sentence, such as one of silence only, makes it wait for the whole timeout.
+pub const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A command for a [`SessionWorker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkerCommand {
+    /// Feed audio at the model's sample rate
+    Feed(Vec<i16>),
+    /// End the current sentence
+    Flush,
+    /// Drop the session without flushing it, and continue with a new one
+    Reset,
+    /// Flush the session and stop. Dropping every sender of commands does the same.
+    Shutdown,
+}
+
+/// A result sent by a [`SessionWorker`].
+pub type WorkerResult = (AprilResultType, AprilTokens<'static>);
+
+/// A session running on its own thread, driven by [`WorkerCommand`]s over a channel.
+///
+/// Results are sent over the channel returned by [`SessionWorker::spawn`]. It disconnects once
+/// the worker has stopped, after the results of the final flush.
+///
+/// ```
+/// use april_asr_rs::{AprilConfigFlags, ScriptedRecognizer, SessionWorker};
+/// use std::sync::Arc;
+///
+/// let recognizer = Arc::new(ScriptedRecognizer::new(16000).final_on_flush("DONE."));
+/// let (worker, results) = SessionWorker::spawn(recognizer, AprilConfigFlags::empty()).unwrap();
+/// worker.feed(vec![0; 1600]).unwrap();
+/// worker.shutdown().unwrap();
+/// let texts: Vec<_> = results.iter().map(|(_, tokens)| tokens.to_string()).collect();
+/// assert_eq!(texts, [" DONE."]);
+/// ```
+pub struct SessionWorker {
+    commands: Sender<WorkerCommand>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl SessionWorker {
+    /// Start a thread running a session of `recognizer` with `flags`. Fails if the session
+    /// can't be created.
+    pub fn spawn<R: Recognizer + 'static>(
+        recognizer: Arc<R>,
+        flags: AprilConfigFlags,
+    ) -> Result<(Self, Receiver<WorkerResult>)> {
+        let (commands, command_rx) = mpsc::channel();
+        let (result_tx, results) = mpsc::channel();
+        let (started_tx, started) = mpsc::sync_channel(1);
+        let thread = std::thread::Builder::new()
+            .name("april-session".into())
+            .spawn(move || run(&*recognizer, flags, command_rx, result_tx, started_tx))
+            .expect("failed to spawn session worker thread");
+
+        match started.recv() {
+            Ok(Ok(())) => Ok((
+                Self {
+                    commands,
+                    thread: Some(thread),
+                },
+                results,
+            )),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => match thread.join() {
+                Err(panic) => std::panic::resume_unwind(panic),
+                Ok(result) => result.and(Err(Error::WorkerStopped)),
+            },
+        }
+    }
+
+    /// A sender of commands, for passing to other threads or actors.
+    pub fn commands(&self) -> Sender<WorkerCommand> {
+        self.commands.clone()
+    }
+
+    pub fn feed(&self, pcm: Vec<i16>) -> Result<()> {
+        self.send(WorkerCommand::Feed(pcm))
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.send(WorkerCommand::Flush)
+    }
+
+    pub fn reset(&self) -> Result<()> {
+        self.send(WorkerCommand::Reset)
+    }
+
+    /// Flush the session and wait for the worker to stop. Every result has been sent once this
+    /// returns. Fails if the worker stopped early because a [`WorkerCommand::Reset`] couldn't
+    /// create a new session.
+    pub fn shutdown(mut self) -> Result<()> {
+        self.stop()
+    }
+
+    fn send(&self, command: WorkerCommand) -> Result<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| Error::WorkerStopped)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        let Some(thread) = self.thread.take() else {
+            return Ok(());
+        };
+        // fails if the worker already stopped, which join reports on
+        let _ = self.commands.send(WorkerCommand::Shutdown);
+        match thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for SessionWorker {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            let _ = self.stop();
+        }
+    }
+}
+
+/// Counts the final results of a session, to wait for the ones answering its flushes.
+#[derive(Default)]
+pub(crate) struct Finals {
+    count: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl Finals {
+    pub(crate) fn counted(&self) {
+        *self.lock() += 1;
+        self.changed.notify_all();
+    }
+
+    /// Final results counted so far, to take right before a flush.
+    pub(crate) fn count(&self) -> u64 {
+        *self.lock()
+    }
+
+    /// Wait until more than `seen` and at least `at_least` final results were counted, or until
+    /// `timeout` passed.
+    pub(crate) fn wait_past(&self, seen: u64, at_least: u64, timeout: Duration) {
+        let _ = self
+            .changed
+            .wait_timeout_while(self.lock(), timeout, |count| {
+                *count <= seen || *count < at_least
+            });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, u64> {
+        self.count.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn run<R: Recognizer>(
+    recognizer: &R,
+    flags: AprilConfigFlags,
+    commands: Receiver<WorkerCommand>,
+    results: Sender<WorkerResult>,
+    started: SyncSender<Result<()>>,
+) -> Result<()> {
+    let new_session = |finals: &Arc<Finals>| {
+        let results = results.clone();
+        let finals = finals.clone();
+        recognizer.new_session(
+            flags,
+            Box::new(move |result, tokens| {
+                let _ = results.send((result, tokens.into_owned()));
+                if result == AprilResultType::RecognitionFinal {
+                    finals.counted();
+                }
+            }),
+        )
+    };
+
+    let mut finals = Arc::new(Finals::default());
+    let mut session = match new_session(&finals) {
+        Ok(session) => session,
+        Err(e) => {
+            let _ = started.send(Err(e));
+            return Ok(());
+        }
+    };
+    let _ = started.send(Ok(()));
+
+    // flushes of the current session with audio to finalize, each answered by a final result
+    let mut flushes = 0;
+    // final results counted right before the last of those flushes
+    let mut seen = None;
+    // whether audio was fed since the last flush, as flushing nothing gives no result
+    let mut fed = false;
+    for command in commands.iter() {
+        match command {
+            WorkerCommand::Feed(mut pcm) => {
+                session.feed_pcm16(&mut pcm);
+                fed = true;
+            }
+            WorkerCommand::Flush => {
+                if std::mem::take(&mut fed) {
+                    flushes += 1;
+                    seen = Some(finals.count());
+                }
+                session.flush();
+            }
+            WorkerCommand::Reset => {
+                // free the old session first, so no result of it arrives after the reset
+                drop(session);
+                finals = Arc::new(Finals::default());
+                session = new_session(&finals)?;
+                flushes = 0;
+                seen = None;
+                fed = false;
+            }
+            WorkerCommand::Shutdown => break,
+        }
+    }
+
+    if fed {
+        flushes += 1;
+        seen = Some(finals.count());
+    }
+    session.flush();
+    let is_async = flags.intersects(AprilConfigFlags::ASYNC_RT | AprilConfigFlags::ASYNC_NO_RT);
+    if let Some(seen) = seen.filter(|_| is_async) {
+        finals.wait_past(seen, flushes, SHUTDOWN_FLUSH_TIMEOUT);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recognizer::ResultHandler;
+    use crate::scripted::{ScriptedRecognizer, ScriptedSession};
+    use crate::Result;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Emits the results of a scripted session from another thread, a little later, and drops
+    /// the ones still queued when freed, like an async april session.
+    struct Delayed(ScriptedRecognizer);
+
+    struct DelayedSession<'a> {
+        inner: ScriptedSession<'a>,
+        freed: Arc<AtomicBool>,
+    }
+
+    impl Recognizer for Delayed {
+        type Session<'a> = DelayedSession<'a>;
+
+        fn get_model_name(&self) -> Result<&str> {
+            self.0.get_model_name()
+        }
+
+        fn get_model_description(&self) -> Result<&str> {
+            self.0.get_model_description()
+        }
+
+        fn get_model_language(&self) -> Result<&str> {
+            self.0.get_model_language()
+        }
+
+        fn get_sample_rate(&self) -> usize {
+            self.0.get_sample_rate()
+        }
+
+        fn new_session(
+            &self,
+            flags: AprilConfigFlags,
+            mut handler: ResultHandler,
+        ) -> Result<Self::Session<'_>> {
+            let freed = Arc::new(AtomicBool::new(false));
+            let (queue, queued) = mpsc::channel::<WorkerResult>();
+            let dropping = freed.clone();
+            std::thread::spawn(move || {
+                for (result, tokens) in queued {
+                    std::thread::sleep(Duration::from_millis(20));
+                    if !dropping.load(Ordering::Acquire) {
+                        handler(result, tokens);
+                    }
+                }
+            });
+            let inner = self.0.new_session(
+                flags,
+                Box::new(move |result, tokens| {
+                    let _ = queue.send((result, tokens.into_owned()));
+                }),
+            )?;
+            Ok(DelayedSession { inner, freed })
+        }
+    }
+
+    impl RecognizerSession for DelayedSession<'_> {
+        fn feed_pcm16(&mut self, pcm: &mut [i16]) {
+            self.inner.feed_pcm16(pcm)
+        }
+
+        fn flush(&mut self) {
+            self.inner.flush()
+        }
+
+        fn get_realtime_speedup(&self) -> Result<f32> {
+            self.inner.get_realtime_speedup()
+        }
+    }
+
+    impl Drop for DelayedSession<'_> {
+        fn drop(&mut self) {
+            self.freed.store(true, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn shutdown_waits_past_sentences_ended_by_april() {
+        let recognizer = Delayed(
+            ScriptedRecognizer::new(16000)
+                .final_at(500, "HELLO.")
+                .partial_at(1000, "WORLD")
+                .final_on_flush("WORLD."),
+        );
+        let (worker, results) =
+            SessionWorker::spawn(Arc::new(recognizer), AprilConfigFlags::ASYNC_NO_RT).unwrap();
+        worker.feed(vec![0; 16000]).unwrap();
+        // april ended a sentence by itself, so as many finals as flushes arrived already
+        let (result, tokens) = results.recv().unwrap();
+        assert_eq!(result, AprilResultType::RecognitionFinal);
+        assert_eq!(tokens.to_string(), " HELLO.");
+
+        let start = std::time::Instant::now();
+        worker.shutdown().unwrap();
+        assert!(start.elapsed() < SHUTDOWN_FLUSH_TIMEOUT / 2);
+        let texts: Vec<_> = results
+            .iter()
+            .map(|(_, tokens)| tokens.to_string())
+            .collect();
+        assert_eq!(texts, [" WORLD", " WORLD."]);
+    }
+}
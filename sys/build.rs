@@ -1,8 +1,50 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     let out = PathBuf::from(env::var("OUT_DIR").unwrap());
+    for var in [
+        "APRIL_LIB_DIR",
+        "APRIL_INCLUDE_DIR",
+        "APRIL_STATIC",
+        "ONNXRUNTIME_DIR",
+        "ONNXRUNTIME_LIBS",
+        "ONNXRUNTIME_STATIC",
+    ] {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+
+    if env::var_os("CARGO_FEATURE_DYNAMIC").is_some() {
+        // nothing is linked, libaprilasr is loaded at runtime by `dynamic::load`
+        if env::var_os("CARGO_FEATURE_STUB").is_some() {
+            build_stub_shared(&out);
+        } else if let Some(include_dir) = env::var_os("APRIL_INCLUDE_DIR") {
+            generate_bindings(&out, &[PathBuf::from(include_dir)]);
+        } else if Path::new("april-asr/april_api.h").exists() {
+            generate_bindings(&out, &[PathBuf::from("./april-asr")]);
+        } else {
+            std::fs::copy("src/bindings.rs", out.join("bindings.rs"))
+                .expect("Failed to copy bindings.rs");
+        }
+        return;
+    }
+
+    if env::var_os("CARGO_FEATURE_STUB").is_some() {
+        build_stub(&out);
+        return;
+    }
+
+    if env::var_os("CARGO_FEATURE_SYSTEM").is_some() || env::var_os("APRIL_LIB_DIR").is_some() {
+        link_system(&out);
+        return;
+    }
+
+    if !Path::new("april-asr/CMakeLists.txt").exists() {
+        fail(
+            "the april-asr submodule in sys/april-asr is missing, so april can't be built from source",
+        );
+    }
+
     let whisper_root = out.join("april-asr/");
 
     if !whisper_root.exists() {
@@ -16,31 +58,7 @@ fn main() {
         });
     }
 
-    if env::var("APRIL_DONT_GENERATE_BINDINGS").is_ok() {
-        let _: u64 = std::fs::copy("src/bindings.rs", out.join("bindings.rs"))
-            .expect("Failed to copy bindings.rs");
-    } else {
-        let bindings = bindgen::Builder::default()
-            .header("wrapper.h")
-            .clang_arg("-I./april-asr")
-            .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
-            .generate();
-
-        match bindings {
-            Ok(b) => {
-                let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
-                b.write_to_file(out_path.join("bindings.rs"))
-                    .expect("Couldn't write bindings!");
-            }
-            Err(e) => {
-                println!("cargo:warning=Unable to generate bindings: {}", e);
-                println!("cargo:warning=Using bundled bindings.rs, which may be out of date");
-                // copy src/bindings.rs to OUT_DIR
-                std::fs::copy("src/bindings.rs", out.join("bindings.rs"))
-                    .expect("Unable to copy bindings.rs");
-            }
-        }
-    };
+    generate_bindings(&out, &[PathBuf::from("./april-asr")]);
 
     cmake::Config::new(&whisper_root).build();
 
@@ -51,6 +69,209 @@ fn main() {
     std::fs::copy(input_dir, output_dir).expect("failed to copy to OUT_DIR");
 
     println!("cargo:rustc-link-lib=static=aprilasr");
-    println!("cargo:rustc-link-lib=dylib=onnxruntime");
     println!("cargo:rustc-link-search={}", out_dir.display());
+    link_onnxruntime();
+}
+
+/// Write bindings for `april_api.h` found in `include_dirs`, falling back to the bundled ones.
+fn generate_bindings(out: &Path, include_dirs: &[PathBuf]) {
+    if env::var("APRIL_DONT_GENERATE_BINDINGS").is_ok() {
+        let _: u64 = std::fs::copy("src/bindings.rs", out.join("bindings.rs"))
+            .expect("Failed to copy bindings.rs");
+        return;
+    }
+
+    let bindings = bindgen::Builder::default()
+        .header("wrapper.h")
+        .clang_args(
+            include_dirs
+                .iter()
+                .map(|dir| format!("-I{}", dir.display())),
+        )
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+        .generate();
+
+    match bindings {
+        Ok(b) => {
+            b.write_to_file(out.join("bindings.rs"))
+                .expect("Couldn't write bindings!");
+        }
+        Err(e) => {
+            println!("cargo:warning=Unable to generate bindings: {}", e);
+            println!("cargo:warning=Using bundled bindings.rs, which may be out of date");
+            // copy src/bindings.rs to OUT_DIR
+            std::fs::copy("src/bindings.rs", out.join("bindings.rs"))
+                .expect("Unable to copy bindings.rs");
+        }
+    }
+}
+
+/// Link an already installed libaprilasr, found through `APRIL_LIB_DIR` or pkg-config.
+///
+/// `APRIL_STATIC=1` links it statically, which also requires linking onnxruntime here.
+/// A shared libaprilasr already depends on onnxruntime itself.
+fn link_system(out: &Path) {
+    let link_static = env::var("APRIL_STATIC").is_ok_and(|v| v != "0");
+    let kind = if link_static { "static" } else { "dylib" };
+
+    let include_dirs = if let Some(lib_dir) = env::var_os("APRIL_LIB_DIR") {
+        let lib_dir = PathBuf::from(lib_dir);
+        if !lib_dir.is_dir() {
+            fail(&format!(
+                "APRIL_LIB_DIR is set to {}, which is not a directory",
+                lib_dir.display()
+            ));
+        }
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+        println!("cargo:rustc-link-lib={}=aprilasr", kind);
+        env::var_os("APRIL_INCLUDE_DIR")
+            .map(PathBuf::from)
+            .into_iter()
+            .collect()
+    } else {
+        match probe_pkg_config("aprilasr", link_static) {
+            Ok(include_dirs) => include_dirs,
+            Err(e) => fail(&format!(
+                "libaprilasr was not found through pkg-config: {}",
+                e
+            )),
+        }
+    };
+
+    generate_bindings(out, &include_dirs);
+    if link_static {
+        link_onnxruntime();
+    } else if env::var_os("CARGO_FEATURE_ONNXRUNTIME_STATIC").is_some() {
+        println!(
+            "cargo:warning=`onnxruntime-static` has no effect on a shared libaprilasr, \
+             set APRIL_STATIC=1 to link both statically"
+        );
+    }
+}
+
+/// Link onnxruntime from `ONNXRUNTIME_DIR` (either its `lib` directory, or a release archive
+/// holding one) or, with the `system` feature, from pkg-config, falling back to the default
+/// linker search path.
+///
+/// The `onnxruntime-static` feature (or `ONNXRUNTIME_STATIC=1`) links it statically, along
+/// with the C++ standard library it needs. `ONNXRUNTIME_LIBS` overrides the comma-separated
+/// libraries to link, for custom builds that are renamed or split into several archives.
+fn link_onnxruntime() {
+    let link_static = env::var_os("CARGO_FEATURE_ONNXRUNTIME_STATIC").is_some()
+        || env::var("ONNXRUNTIME_STATIC").is_ok_and(|v| v != "0");
+    let kind = if link_static { "static" } else { "dylib" };
+    let libs = env::var("ONNXRUNTIME_LIBS").unwrap_or_else(|_| "onnxruntime".to_owned());
+
+    if let Some(dir) = env::var_os("ONNXRUNTIME_DIR") {
+        let dir = PathBuf::from(dir);
+        let lib_dir = if dir.join("lib").is_dir() {
+            dir.join("lib")
+        } else {
+            dir
+        };
+        if !lib_dir.is_dir() {
+            fail(&format!(
+                "ONNXRUNTIME_DIR is set to {}, which is not a directory",
+                lib_dir.display()
+            ));
+        }
+        println!("cargo:rustc-link-search=native={}", lib_dir.display());
+    } else if env::var_os("ONNXRUNTIME_LIBS").is_none()
+        && probe_pkg_config("libonnxruntime", link_static).is_ok()
+    {
+        // pkg-config already printed the link flags, including private ones when static
+        return;
+    }
+
+    for lib in libs.split(',').map(str::trim).filter(|lib| !lib.is_empty()) {
+        println!("cargo:rustc-link-lib={}={}", kind, lib);
+    }
+    if link_static {
+        link_cpp_runtime();
+    }
+}
+
+/// Link the C++ standard library, which a static onnxruntime doesn't pull in by itself.
+fn link_cpp_runtime() {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    match (target_os.as_str(), target_env.as_str()) {
+        ("macos" | "ios" | "freebsd" | "openbsd", _) => println!("cargo:rustc-link-lib=c++"),
+        (_, "msvc") => {}
+        _ => println!("cargo:rustc-link-lib=stdc++"),
+    }
+    if target_os != "windows" {
+        println!("cargo:rustc-link-lib=dl");
+        println!("cargo:rustc-link-lib=pthread");
+    }
+}
+
+/// Look up `name` with pkg-config, which prints its link flags, and return its include paths.
+#[cfg(feature = "system")]
+fn probe_pkg_config(name: &str, link_static: bool) -> Result<Vec<PathBuf>, String> {
+    pkg_config::Config::new()
+        .statik(link_static)
+        .probe(name)
+        .map(|library| library.include_paths)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "system"))]
+fn probe_pkg_config(_name: &str, _link_static: bool) -> Result<Vec<PathBuf>, String> {
+    Err("pkg-config support needs the `system` feature".to_owned())
+}
+
+/// Abort the build, listing every way to provide april.
+fn fail(reason: &str) -> ! {
+    panic!(
+        "\n{}.\n\nTo link april, do one of the following:\n\
+         - check out the submodule with `git submodule update --init` to build it from source\n\
+         - install libaprilasr with a pkg-config file, and enable the `system` feature\n\
+         - set APRIL_LIB_DIR to the directory holding libaprilasr (and APRIL_STATIC=1 to link \
+         it statically). APRIL_INCLUDE_DIR can point at april_api.h to regenerate bindings\n\
+         - enable the `stub` feature to link a stand-in library for tests\n\
+         - enable the `dynamic` feature to load libaprilasr at runtime instead of linking it\n\n\
+         onnxruntime is looked up in ONNXRUNTIME_DIR, then through pkg-config if the `system` \
+         feature is enabled, then on the default linker path. It is linked statically with the `onnxruntime-static` feature or \
+         ONNXRUNTIME_STATIC=1, and ONNXRUNTIME_LIBS names the libraries of a custom build.\n",
+        reason
+    )
+}
+
+/// Build the in-repo stub of april_api.h instead of april-asr, see `stub/april_stub.c`.
+/// The stub matches the bundled bindings, so those are used as they are.
+fn build_stub(out: &Path) {
+    println!("cargo:rerun-if-changed=stub/april_stub.c");
+    std::fs::copy("src/bindings.rs", out.join("bindings.rs")).expect("Failed to copy bindings.rs");
+    cc::Build::new()
+        .file("stub/april_stub.c")
+        .warnings(true)
+        .compile("aprilasr");
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        println!("cargo:rustc-link-lib=pthread");
+    }
+}
+
+/// Build the stub as a shared library for the `dynamic` backend, and pass its path to the
+/// crate as `APRIL_STUB_LIBRARY`.
+fn build_stub_shared(out: &Path) {
+    println!("cargo:rerun-if-changed=stub/april_stub.c");
+    std::fs::copy("src/bindings.rs", out.join("bindings.rs")).expect("Failed to copy bindings.rs");
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let library = match target_os.as_str() {
+        "windows" => panic!("the shared stub library is not supported on windows"),
+        "macos" | "ios" => out.join("libaprilasr_stub.dylib"),
+        _ => out.join("libaprilasr_stub.so"),
+    };
+    let status = cc::Build::new()
+        .get_compiler()
+        .to_command()
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(&library)
+        .arg("stub/april_stub.c")
+        .arg("-lpthread")
+        .status()
+        .expect("Failed to run the C compiler");
+    assert!(status.success(), "Failed to build the shared stub library");
+    println!("cargo:rustc-env=APRIL_STUB_LIBRARY={}", library.display());
 }
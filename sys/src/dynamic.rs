@@ -0,0 +1,176 @@
+//! Backend resolving the april API from a shared library at runtime, instead of linking it.
+//!
+//! Every function of the API is exported here with the same signature as the linked bindings,
+//! calling into the library given to [`load`]. Calling one before [`load`] succeeded panics.
+
+use crate::bindings::{AprilASRModel, AprilASRSession, AprilConfig, APRIL_VERSION};
+use libloading::Library;
+use std::ffi::{c_char, c_int, c_short, OsStr};
+use std::fmt::Formatter;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+/// The version of april_api.h the functions below are written against.
+pub const SUPPORTED_VERSION: u32 = 1;
+
+// bindings regenerated from another april_api.h may not match the signatures below
+const _: () = assert!(
+    APRIL_VERSION == SUPPORTED_VERSION,
+    "the bindings are for an april API version the dynamic backend doesn't support"
+);
+
+static API: OnceLock<Api> = OnceLock::new();
+static LOADING: Mutex<()> = Mutex::new(());
+
+/// Why [`load`] failed.
+///
+/// There is no variant for a library of the wrong version: april can't report the version
+/// of a built library, so [`load`] doesn't check it.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The shared library could not be opened
+    Library {
+        path: PathBuf,
+        source: libloading::Error,
+    },
+    /// The library doesn't export a function of the april API
+    Symbol {
+        name: &'static str,
+        source: libloading::Error,
+    },
+    /// Another library was already loaded, which can't be replaced while it may be in use
+    AlreadyLoaded { path: PathBuf },
+    /// An april function was needed before a library was loaded
+    NotLoaded,
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Library { path, source } => {
+                write!(f, "failed to load {}: {}", path.display(), source)
+            }
+            LoadError::Symbol { name, source } => {
+                write!(f, "april library doesn't export {}: {}", name, source)
+            }
+            LoadError::AlreadyLoaded { path } => {
+                write!(f, "april library already loaded from {}", path.display())
+            }
+            LoadError::NotLoaded => f.write_str("no april library loaded"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Library { source, .. } | LoadError::Symbol { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Load the april library at `path`, resolve the whole API and initialize it with
+/// [`APRIL_VERSION`].
+///
+/// No version check is done at runtime: april can't report the version of a built library,
+/// so a library built from another april_api.h is loaded all the same, and only
+/// `aam_api_init` itself may notice. The bindings' own version is checked at compile time.
+///
+/// Loading the same path again does nothing. The library stays loaded until the process exits.
+pub fn load(path: impl AsRef<OsStr>) -> Result<(), LoadError> {
+    let path = PathBuf::from(path.as_ref());
+    let _loading = LOADING.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(api) = API.get() {
+        return if api.path == path {
+            Ok(())
+        } else {
+            Err(LoadError::AlreadyLoaded {
+                path: api.path.clone(),
+            })
+        };
+    }
+
+    // SAFETY: loading april runs no initialization code beyond its static constructors
+    let library = unsafe { Library::new(&path) }.map_err(|source| LoadError::Library {
+        path: path.clone(),
+        source,
+    })?;
+    // SAFETY: the signatures match april_api.h of SUPPORTED_VERSION, the version of the
+    // bindings. That the library was built from the same header is up to the caller
+    let api = unsafe { Api::resolve(path, library) }?;
+    unsafe { (api.aam_api_init)(APRIL_VERSION as c_int) };
+    let _ = API.set(api);
+    Ok(())
+}
+
+/// Whether [`load`] succeeded, so that the april functions can be called.
+pub fn is_loaded() -> bool {
+    API.get().is_some()
+}
+
+fn api() -> &'static Api {
+    API.get()
+        .expect("april function called before april_asr_rs_sys::load loaded the library")
+}
+
+macro_rules! april_api {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)?;)*) => {
+        struct Api {
+            path: PathBuf,
+            // keeps the functions below valid
+            _library: Library,
+            $($name: unsafe extern "C" fn($($ty),*) $(-> $ret)?,)*
+        }
+
+        impl Api {
+            unsafe fn resolve(path: PathBuf, library: Library) -> Result<Self, LoadError> {
+                $(
+                    let $name = *library
+                        .get::<unsafe extern "C" fn($($ty),*) $(-> $ret)?>(
+                            concat!(stringify!($name), "\0").as_bytes(),
+                        )
+                        .map_err(|source| LoadError::Symbol {
+                            name: stringify!($name),
+                            source,
+                        })?;
+                )*
+                Ok(Self {
+                    path,
+                    _library: library,
+                    $($name,)*
+                })
+            }
+        }
+
+        $(
+            /// Calls the function of the library given to [`load`].
+            ///
+            /// # Panics
+            ///
+            /// If no library was loaded.
+            ///
+            /// # Safety
+            ///
+            /// Same as the function in april_api.h.
+            pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                (api().$name)($($arg),*)
+            }
+        )*
+    };
+}
+
+april_api! {
+    fn aam_api_init(version: c_int);
+    fn aam_create_model(model_path: *const c_char) -> AprilASRModel;
+    fn aam_get_name(model: AprilASRModel) -> *const c_char;
+    fn aam_get_description(model: AprilASRModel) -> *const c_char;
+    fn aam_get_language(model: AprilASRModel) -> *const c_char;
+    fn aam_get_sample_rate(model: AprilASRModel) -> usize;
+    fn aam_free(model: AprilASRModel);
+    fn aas_create_session(model: AprilASRModel, config: AprilConfig) -> AprilASRSession;
+    fn aas_feed_pcm16(session: AprilASRSession, pcm16: *mut c_short, short_count: usize);
+    fn aas_flush(session: AprilASRSession);
+    fn aas_realtime_get_speedup(session: AprilASRSession) -> f32;
+    fn aas_free(session: AprilASRSession);
+}
@@ -2,4 +2,28 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+#[cfg(not(feature = "dynamic"))]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+// With `dynamic`, the extern declarations of the bindings are never called or linked, the
+// functions of the same name in `dynamic` shadow them
+#[cfg(feature = "dynamic")]
+#[allow(dead_code)]
+mod bindings {
+    include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+}
+#[cfg(feature = "dynamic")]
+mod dynamic;
+
+#[cfg(feature = "dynamic")]
+pub use bindings::*;
+#[cfg(feature = "dynamic")]
+pub use dynamic::{
+    aam_api_init, aam_create_model, aam_free, aam_get_description, aam_get_language, aam_get_name,
+    aam_get_sample_rate, aas_create_session, aas_feed_pcm16, aas_flush, aas_free,
+    aas_realtime_get_speedup, is_loaded, load, LoadError, SUPPORTED_VERSION,
+};
+
+/// Path of the stub built as a shared library, to load with the `dynamic` backend in tests.
+#[cfg(all(feature = "stub", feature = "dynamic"))]
+pub const STUB_LIBRARY: &str = env!("APRIL_STUB_LIBRARY");
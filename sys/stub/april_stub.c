@@ -0,0 +1,355 @@
+/*
+ * Stand-in for the april-asr library, built instead of it with the `stub` feature.
+ *
+ * It implements the april_api.h ABI without any model: audio is split into 500ms blocks,
+ * every block with sound in it is "recognized" as the next number word, and a silent block
+ * (or a flush) ends the current sentence. This makes results depend only on the audio fed,
+ * so the Rust wrapper can be tested end-to-end without onnxruntime or a .april file:
+ *
+ *   - each loud block emits APRIL_RESULT_RECOGNITION_PARTIAL with all words of the sentence
+ *   - the first silent block after words emits APRIL_RESULT_RECOGNITION_FINAL with the words
+ *     and a "." sentence end, followed by APRIL_RESULT_SILENCE
+ *   - aas_flush treats the buffered partial block as a full one, then ends the sentence
+ *
+ * With an async flag set, audio is queued and results are delivered from a background
+ * thread. With APRIL_CONFIG_FLAG_ASYNC_NO_RT_BIT, more than MAX_QUEUED_SAMPLES of queued
+ * audio is dropped and reported as APRIL_RESULT_ERROR_CANT_KEEP_UP.
+ *
+ * Models load from any readable file, so a missing path still fails like the real library.
+ */
+
+#include <pthread.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define APRIL_VERSION 1
+
+typedef struct AprilASRModel_i *AprilASRModel;
+typedef struct AprilASRSession_i *AprilASRSession;
+
+typedef struct AprilSpeakerID {
+    uint8_t data[16];
+} AprilSpeakerID;
+
+typedef enum AprilResultType {
+    APRIL_RESULT_UNKNOWN = 0,
+    APRIL_RESULT_RECOGNITION_PARTIAL = 1,
+    APRIL_RESULT_RECOGNITION_FINAL = 2,
+    APRIL_RESULT_ERROR_CANT_KEEP_UP = 3,
+    APRIL_RESULT_SILENCE = 4,
+} AprilResultType;
+
+typedef enum AprilTokenFlagBits {
+    APRIL_TOKEN_FLAG_WORD_BOUNDARY_BIT = 1,
+    APRIL_TOKEN_FLAG_SENTENCE_END_BIT = 2,
+} AprilTokenFlagBits;
+
+typedef struct AprilToken {
+    const char *token;
+    float logprob;
+    AprilTokenFlagBits flags;
+    size_t time_ms;
+    void *reserved;
+} AprilToken;
+
+typedef void (*AprilRecognitionResultHandler)(void *, AprilResultType, size_t, const AprilToken *);
+
+typedef enum AprilConfigFlagBits {
+    APRIL_CONFIG_FLAG_ZERO_BIT = 0,
+    APRIL_CONFIG_FLAG_ASYNC_RT_BIT = 1,
+    APRIL_CONFIG_FLAG_ASYNC_NO_RT_BIT = 2,
+} AprilConfigFlagBits;
+
+typedef struct AprilConfig {
+    AprilSpeakerID speaker;
+    AprilRecognitionResultHandler handler;
+    void *userdata;
+    AprilConfigFlagBits flags;
+} AprilConfig;
+
+#define SAMPLE_RATE 16000
+#define BLOCK_SAMPLES (SAMPLE_RATE / 2)
+#define SILENCE_THRESHOLD 64
+#define MAX_WORDS 64
+#define MAX_QUEUED_SAMPLES (SAMPLE_RATE * 30)
+
+static const char *const WORDS[] = {
+    " ONE", " TWO", " THREE", " FOUR", " FIVE", " SIX", " SEVEN", " EIGHT", " NINE", " TEN",
+};
+
+struct AprilASRModel_i {
+    int unused;
+};
+
+struct AprilASRSession_i {
+    AprilConfig config;
+
+    /* recognition state, only touched by whoever runs recognition */
+    int16_t block[BLOCK_SAMPLES];
+    size_t block_len;
+    size_t samples_processed;
+    AprilToken tokens[MAX_WORDS + 1];
+    size_t num_words;
+
+    /* async queue, guarded by lock */
+    bool async;
+    pthread_t worker;
+    pthread_mutex_t lock;
+    pthread_cond_t changed;
+    int16_t *queue;
+    size_t queue_len;
+    size_t queue_cap;
+    bool flush_requested;
+    bool cant_keep_up;
+    bool stopping;
+};
+
+void aam_api_init(int version) {
+    if (version != APRIL_VERSION) {
+        fprintf(stderr, "april stub: API version mismatch, got %d, expected %d\n", version,
+                APRIL_VERSION);
+        abort();
+    }
+}
+
+AprilASRModel aam_create_model(const char *model_path) {
+    FILE *file = fopen(model_path, "rb");
+    if (file == NULL) {
+        return NULL;
+    }
+    fclose(file);
+    return calloc(1, sizeof(struct AprilASRModel_i));
+}
+
+const char *aam_get_name(AprilASRModel model) {
+    (void)model;
+    return "stub";
+}
+
+const char *aam_get_description(AprilASRModel model) {
+    (void)model;
+    return "Deterministic stub of the april-asr library";
+}
+
+const char *aam_get_language(AprilASRModel model) {
+    (void)model;
+    return "en";
+}
+
+size_t aam_get_sample_rate(AprilASRModel model) {
+    (void)model;
+    return SAMPLE_RATE;
+}
+
+void aam_free(AprilASRModel model) {
+    free(model);
+}
+
+static void emit(AprilASRSession session, AprilResultType result, size_t num_tokens) {
+    if (session->config.handler != NULL) {
+        session->config.handler(session->config.userdata, result, num_tokens,
+                                num_tokens > 0 ? session->tokens : NULL);
+    }
+}
+
+static void end_sentence(AprilASRSession session) {
+    if (session->num_words == 0) {
+        return;
+    }
+    AprilToken *end = &session->tokens[session->num_words];
+    end->token = ".";
+    end->logprob = 0.0f;
+    end->flags = APRIL_TOKEN_FLAG_SENTENCE_END_BIT;
+    end->time_ms = session->samples_processed * 1000 / SAMPLE_RATE;
+    end->reserved = NULL;
+    emit(session, APRIL_RESULT_RECOGNITION_FINAL, session->num_words + 1);
+    session->num_words = 0;
+    emit(session, APRIL_RESULT_SILENCE, 0);
+}
+
+static void process_block(AprilASRSession session) {
+    bool loud = false;
+    for (size_t i = 0; i < session->block_len; i++) {
+        int sample = session->block[i];
+        if (sample > SILENCE_THRESHOLD || sample < -SILENCE_THRESHOLD) {
+            loud = true;
+            break;
+        }
+    }
+
+    size_t start_ms = session->samples_processed * 1000 / SAMPLE_RATE;
+    session->samples_processed += session->block_len;
+    session->block_len = 0;
+
+    if (!loud) {
+        end_sentence(session);
+    } else if (session->num_words < MAX_WORDS) {
+        AprilToken *token = &session->tokens[session->num_words];
+        token->token = WORDS[session->num_words % (sizeof(WORDS) / sizeof(WORDS[0]))];
+        token->logprob = -0.5f;
+        token->flags = APRIL_TOKEN_FLAG_WORD_BOUNDARY_BIT;
+        token->time_ms = start_ms;
+        token->reserved = NULL;
+        session->num_words++;
+        emit(session, APRIL_RESULT_RECOGNITION_PARTIAL, session->num_words);
+    }
+}
+
+static void recognize(AprilASRSession session, const int16_t *samples, size_t num_samples) {
+    while (num_samples > 0) {
+        size_t take = BLOCK_SAMPLES - session->block_len;
+        if (take > num_samples) {
+            take = num_samples;
+        }
+        memcpy(session->block + session->block_len, samples, take * sizeof(int16_t));
+        session->block_len += take;
+        samples += take;
+        num_samples -= take;
+        if (session->block_len == BLOCK_SAMPLES) {
+            process_block(session);
+        }
+    }
+}
+
+static void recognize_flush(AprilASRSession session) {
+    if (session->block_len > 0) {
+        process_block(session);
+    }
+    end_sentence(session);
+}
+
+static void *worker_main(void *arg) {
+    AprilASRSession session = arg;
+    int16_t *batch = NULL;
+
+    pthread_mutex_lock(&session->lock);
+    for (;;) {
+        while (!session->stopping && session->queue_len == 0 && !session->flush_requested &&
+               !session->cant_keep_up) {
+            pthread_cond_wait(&session->changed, &session->lock);
+        }
+        if (session->stopping) {
+            break;
+        }
+
+        /* take the queued work, and run recognition without holding the lock */
+        size_t len = session->queue_len;
+        bool flush = session->flush_requested;
+        bool cant_keep_up = session->cant_keep_up;
+        free(batch);
+        batch = session->queue;
+        session->queue = NULL;
+        session->queue_len = 0;
+        session->queue_cap = 0;
+        session->flush_requested = false;
+        session->cant_keep_up = false;
+        pthread_mutex_unlock(&session->lock);
+
+        if (cant_keep_up) {
+            emit(session, APRIL_RESULT_ERROR_CANT_KEEP_UP, 0);
+        }
+        recognize(session, batch, len);
+        if (flush) {
+            recognize_flush(session);
+        }
+
+        pthread_mutex_lock(&session->lock);
+    }
+    pthread_mutex_unlock(&session->lock);
+    free(batch);
+    return NULL;
+}
+
+AprilASRSession aas_create_session(AprilASRModel model, AprilConfig config) {
+    if (model == NULL) {
+        return NULL;
+    }
+    AprilASRSession session = calloc(1, sizeof(struct AprilASRSession_i));
+    if (session == NULL) {
+        return NULL;
+    }
+    session->config = config;
+    session->async = (config.flags & (APRIL_CONFIG_FLAG_ASYNC_RT_BIT |
+                                      APRIL_CONFIG_FLAG_ASYNC_NO_RT_BIT)) != 0;
+    if (session->async) {
+        pthread_mutex_init(&session->lock, NULL);
+        pthread_cond_init(&session->changed, NULL);
+        if (pthread_create(&session->worker, NULL, worker_main, session) != 0) {
+            pthread_cond_destroy(&session->changed);
+            pthread_mutex_destroy(&session->lock);
+            free(session);
+            return NULL;
+        }
+    }
+    return session;
+}
+
+void aas_feed_pcm16(AprilASRSession session, short *pcm16, size_t short_count) {
+    if (!session->async) {
+        recognize(session, pcm16, short_count);
+        return;
+    }
+
+    pthread_mutex_lock(&session->lock);
+    bool overflow = (session->config.flags & APRIL_CONFIG_FLAG_ASYNC_NO_RT_BIT) != 0 &&
+                    session->queue_len + short_count > MAX_QUEUED_SAMPLES;
+    if (overflow) {
+        /* the worker reports it, so callbacks only ever come from one thread */
+        session->queue_len = 0;
+        session->cant_keep_up = true;
+        pthread_cond_signal(&session->changed);
+    } else {
+        if (session->queue_len + short_count > session->queue_cap) {
+            size_t cap = (session->queue_len + short_count) * 2;
+            int16_t *queue = realloc(session->queue, cap * sizeof(int16_t));
+            if (queue == NULL) {
+                pthread_mutex_unlock(&session->lock);
+                return;
+            }
+            session->queue = queue;
+            session->queue_cap = cap;
+        }
+        memcpy(session->queue + session->queue_len, pcm16, short_count * sizeof(int16_t));
+        session->queue_len += short_count;
+        pthread_cond_signal(&session->changed);
+    }
+    pthread_mutex_unlock(&session->lock);
+}
+
+void aas_flush(AprilASRSession session) {
+    if (!session->async) {
+        recognize_flush(session);
+        return;
+    }
+    pthread_mutex_lock(&session->lock);
+    session->flush_requested = true;
+    pthread_cond_signal(&session->changed);
+    pthread_mutex_unlock(&session->lock);
+}
+
+float aas_realtime_get_speedup(AprilASRSession session) {
+    (void)session;
+    return 1.0f;
+}
+
+void aas_free(AprilASRSession session) {
+    if (session == NULL) {
+        return;
+    }
+    if (session->async) {
+        /* queued audio is discarded, and no callback runs after this returns */
+        pthread_mutex_lock(&session->lock);
+        session->stopping = true;
+        pthread_cond_signal(&session->changed);
+        pthread_mutex_unlock(&session->lock);
+        pthread_join(session->worker, NULL);
+        pthread_cond_destroy(&session->changed);
+        pthread_mutex_destroy(&session->lock);
+        free(session->queue);
+    }
+    free(session);
+}
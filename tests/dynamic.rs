@@ -0,0 +1,60 @@
+//! Tests of the `dynamic` backend, loading the stub april library built as a shared library.
+//!
+//! Run with `cargo test --features stub,dynamic`. Loading is process-wide, so everything
+//! runs in order in a single test.
+#![cfg(all(feature = "stub", feature = "dynamic"))]
+
+use april_asr_rs::{AprilConfigFlags, AprilModel, AprilResultType, Error, LoadError, Recognizer};
+use std::sync::mpsc;
+
+const MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+
+#[test]
+fn load_stub_at_runtime() {
+    assert!(matches!(
+        AprilModel::new(MODEL_PATH),
+        Err(Error::Library(LoadError::NotLoaded))
+    ));
+
+    assert!(matches!(
+        april_asr_rs::load_library("/nonexistent/libaprilasr.so"),
+        Err(Error::Library(LoadError::Library { .. }))
+    ));
+    // a library without the april API is rejected, and doesn't count as loaded
+    assert!(matches!(
+        april_asr_rs::load_library("libc.so.6"),
+        Err(Error::Library(LoadError::Symbol {
+            name: "aam_api_init",
+            ..
+        }))
+    ));
+    assert!(AprilModel::new(MODEL_PATH).is_err());
+
+    april_asr_rs::load_library(april_asr_rs_sys::STUB_LIBRARY).unwrap();
+    april_asr_rs::load_library(april_asr_rs_sys::STUB_LIBRARY).unwrap();
+    assert!(matches!(
+        april_asr_rs::load_library("libc.so.6"),
+        Err(Error::Library(LoadError::AlreadyLoaded { .. }))
+    ));
+
+    let model = AprilModel::new(MODEL_PATH).unwrap();
+    assert_eq!(model.get_model_name().unwrap(), "stub");
+    let (tx, rx) = mpsc::channel();
+    let mut session = Recognizer::new_session(
+        &model,
+        AprilConfigFlags::empty(),
+        Box::new(move |result, tokens| tx.send((result, tokens.to_string())).unwrap()),
+    )
+    .unwrap();
+    session.feed_pcm16(&mut vec![1000; 8000]);
+    session.flush();
+    let results: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        results,
+        [
+            (AprilResultType::RecognitionPartial, " ONE".to_owned()),
+            (AprilResultType::RecognitionFinal, " ONE.".to_owned()),
+            (AprilResultType::Silence, String::new()),
+        ]
+    );
+}
@@ -0,0 +1,633 @@
+//! End-to-end tests of the FFI glue against the stub april library.
+//!
+//! Run with `cargo test --features stub`. The stub recognizes every 500ms block of audio with
+//! sound in it as the next number word, and ends the sentence on silence or a flush. With
+//! `dynamic` as well, `tests/dynamic.rs` runs instead.
+#![cfg(all(feature = "stub", not(feature = "dynamic")))]
+
+use april_asr_rs::{
+    audio_ring, AprilConfig, AprilConfigFlags, AprilModel, AprilResultType, AprilTokenFlags, Error,
+    Grammar, HotwordSpotter, InverseNormalizer, KeepUpPolicy, KeepUpSession, KeepUpStats,
+    MatchMode, OverflowPolicy, Recognizer, RecognizerSession, RecordedEvent, Recorder, Recording,
+    ResultDiff, ScriptedRecognizer, SessionWorker, SlotValue, TextRestorer, Vocabulary,
+    VocabularyEntry, WorkerCommand, SHUTDOWN_FLUSH_TIMEOUT,
+};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The stub loads any readable file as a model.
+const MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+const BLOCK: usize = 8000;
+
+fn model() -> AprilModel {
+    AprilModel::new(MODEL_PATH).expect("failed to load stub model")
+}
+
+/// User data counting how often it is dropped.
+struct DropCounter {
+    results: Mutex<Sender<(AprilResultType, String)>>,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn config(
+    flags: AprilConfigFlags,
+) -> (
+    AprilConfig<DropCounter>,
+    Receiver<(AprilResultType, String)>,
+    Arc<AtomicUsize>,
+) {
+    let (tx, rx) = mpsc::channel();
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut config = AprilConfig::default();
+    config.set_flags(flags);
+    config.set_handler_fn(
+        |data: &DropCounter, result, tokens| {
+            let _ = data
+                .results
+                .lock()
+                .unwrap()
+                .send((result, tokens.to_string()));
+        },
+        DropCounter {
+            results: Mutex::new(tx),
+            drops: drops.clone(),
+        },
+    );
+    (config, rx, drops)
+}
+
+fn speech(blocks: usize) -> Vec<i16> {
+    vec![1000; blocks * BLOCK]
+}
+
+fn silence(blocks: usize) -> Vec<i16> {
+    vec![0; blocks * BLOCK]
+}
+
+#[test]
+fn model_metadata() {
+    let model = model();
+    assert_eq!(model.get_model_name().unwrap(), "stub");
+    assert_eq!(model.get_model_language().unwrap(), "en");
+    assert_eq!(model.get_sample_rate(), 16000);
+}
+
+#[test]
+fn missing_model_fails() {
+    match AprilModel::new("/nonexistent/model.april") {
+        Err(Error::ModelFile { path, source }) => {
+            assert_eq!(path, Path::new("/nonexistent/model.april"));
+            assert_eq!(source.kind(), io::ErrorKind::NotFound);
+        }
+        _ => panic!("expected a model file error"),
+    }
+    assert!(matches!(
+        AprilModel::new(env!("CARGO_MANIFEST_DIR")),
+        Err(Error::ModelFile { .. })
+    ));
+}
+
+#[test]
+fn sync_session_calls_handler_through_trampoline() {
+    let model = model();
+    let (config, results, drops) = config(AprilConfigFlags::empty());
+    let mut session = model.create_session(config).unwrap();
+
+    session.feed_pcm16(&mut speech(2));
+    session.feed_pcm16(&mut silence(1));
+    assert_eq!(
+        results.try_iter().collect::<Vec<_>>(),
+        [
+            (AprilResultType::RecognitionPartial, " ONE".to_owned()),
+            (AprilResultType::RecognitionPartial, " ONE TWO".to_owned()),
+            (AprilResultType::RecognitionFinal, " ONE TWO.".to_owned()),
+            (AprilResultType::Silence, String::new()),
+        ]
+    );
+
+    // a flush finishes the buffered partial block
+    session.feed_pcm16(&mut speech(1)[..100]);
+    session.flush();
+    assert_eq!(
+        results.try_iter().collect::<Vec<_>>(),
+        [
+            (AprilResultType::RecognitionPartial, " ONE".to_owned()),
+            (AprilResultType::RecognitionFinal, " ONE.".to_owned()),
+            (AprilResultType::Silence, String::new()),
+        ]
+    );
+
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+    drop(session);
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn tokens_carry_flags_and_times() {
+    let model = model();
+    let (tx, rx) = mpsc::channel();
+    let mut config = AprilConfig::default();
+    config.set_handler_fn(
+        |tx: &Mutex<Sender<_>>, result, tokens| {
+            if result == AprilResultType::RecognitionFinal {
+                let _ = tx.lock().unwrap().send(tokens.words());
+                let flags: Vec<u32> = tokens.0.iter().map(|t| t.flag_bits.bits()).collect();
+                assert_eq!(
+                    flags,
+                    [
+                        AprilTokenFlags::WORD_BOUNDARY.bits(),
+                        AprilTokenFlags::WORD_BOUNDARY.bits(),
+                        AprilTokenFlags::SENTENCE_END.bits()
+                    ]
+                );
+            }
+        },
+        Mutex::new(tx),
+    );
+    let mut session = model.create_session(config).unwrap();
+    session.feed_pcm16(&mut speech(2));
+    session.flush();
+
+    let words = rx.try_recv().unwrap();
+    let times: Vec<_> = words
+        .iter()
+        .map(|w| (w.text.as_str(), w.start_ms))
+        .collect();
+    assert_eq!(times, [("ONE", 0), ("TWO.", 500)]);
+}
+
+#[test]
+fn cleared_handler_frees_user_data() {
+    let (mut config, _results, drops) = config(AprilConfigFlags::empty());
+    config.clear_handler_fn();
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+    // sessions without a handler still run
+    let model = model();
+    let mut session = model.create_session(config).unwrap();
+    session.feed_pcm16(&mut speech(1));
+    session.flush();
+}
+
+#[test]
+fn async_session_delivers_from_background_thread() {
+    let model = model();
+    for flags in [AprilConfigFlags::ASYNC_RT, AprilConfigFlags::ASYNC_NO_RT] {
+        let (config, results, drops) = config(flags);
+        let mut session = model.create_session(config).unwrap();
+        assert_eq!(
+            session.get_realtime_speedup().is_ok(),
+            flags == AprilConfigFlags::ASYNC_RT
+        );
+        session.feed_pcm16(&mut speech(1));
+        session.flush();
+
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            received.push(
+                results
+                    .recv_timeout(Duration::from_secs(5))
+                    .expect("async results never arrived"),
+            );
+        }
+        assert_eq!(
+            received[1],
+            (AprilResultType::RecognitionFinal, " ONE.".to_owned())
+        );
+
+        drop(session);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[test]
+fn recognizer_trait_uses_ffi() {
+    let model = model();
+    let (tx, rx) = mpsc::channel();
+    let mut session = Recognizer::new_session(
+        &model,
+        AprilConfigFlags::empty(),
+        Box::new(move |result, tokens| {
+            let _ = tx.send((result, tokens.to_string()));
+        }),
+    )
+    .unwrap();
+    RecognizerSession::feed_pcm16(&mut session, &mut speech(1));
+    RecognizerSession::flush(&mut session);
+    assert_eq!(rx.try_iter().count(), 3);
+    assert!(matches!(
+        RecognizerSession::get_realtime_speedup(&session),
+        Err(Error::SessionMode { .. })
+    ));
+}
+
+#[test]
+fn recorded_session_replays() {
+    let model = model();
+    let path = std::env::temp_dir().join(format!("april-stub-{}.rec", std::process::id()));
+    let recorder = Recorder::create(&path, &model).unwrap();
+    let (mut config, results, _) = config(AprilConfigFlags::empty());
+    config.set_recorder(recorder.clone());
+    let mut session = model.create_session(config).unwrap();
+    session.feed_pcm16(&mut speech(2));
+    session.feed_pcm16(&mut silence(1));
+    session.feed_pcm16(&mut speech(1));
+    session.flush();
+    drop(session);
+    recorder.finish().unwrap();
+    let live: Vec<_> = results.try_iter().collect();
+
+    let recording = Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(recording.model.name, "stub");
+    assert_eq!(recording.sessions(), [0]);
+    assert!(matches!(
+        recording.events[0],
+        (0, RecordedEvent::Session(_))
+    ));
+    let audio_chunks = recording
+        .session_events(0)
+        .filter(|event| matches!(event, RecordedEvent::Audio(_)))
+        .count();
+    assert_eq!(audio_chunks, 3);
+    let recorded: Vec<_> = recording
+        .results()
+        .into_iter()
+        .map(|r| (r.result, r.tokens.to_string()))
+        .collect();
+    assert_eq!(recorded, live);
+
+    let replayed = recording.replay(&model).unwrap();
+    assert!(recording.diff(&replayed).is_empty());
+
+    // a model hearing something else differs from the first result on
+    let other = ScriptedRecognizer::new(16000).partial_at(500, "HELLO");
+    let diffs = recording.diff(&recording.replay(&other).unwrap());
+    assert!(matches!(
+        diffs[0],
+        ResultDiff::Changed {
+            recorded_index: 0,
+            replayed_index: 0,
+            ..
+        }
+    ));
+    assert!(matches!(
+        diffs[1],
+        ResultDiff::Missing {
+            recorded_index: 1,
+            ..
+        }
+    ));
+    assert!(matches!(
+        recording.replay(&ScriptedRecognizer::new(8000)),
+        Err(Error::SampleRate {
+            expected: 8000,
+            actual: 16000
+        })
+    ));
+}
+
+#[test]
+fn session_metrics() {
+    let model = model();
+    let (config, _results, _) = config(AprilConfigFlags::empty());
+    let mut session = model.create_session(config).unwrap();
+    let handle = session.metrics_handle();
+    session.feed_pcm16(&mut speech(2));
+    session.feed_pcm16(&mut silence(1));
+    session.feed_pcm16(&mut speech(1));
+    session.flush();
+
+    let metrics = session.metrics();
+    assert_eq!(metrics.audio_seconds, 2.0);
+    assert!(metrics.realtime_factor().is_some());
+    assert_eq!(metrics.partial_results, 3);
+    assert_eq!(metrics.final_results, 2);
+    assert_eq!(metrics.cant_keep_up, 0);
+    // both chunks of speech are covered by their partial, the silence by the final it ends
+    assert_eq!(metrics.first_partial_latency.count, 3);
+    assert_eq!(metrics.partial_to_final_latency.count, 2);
+
+    assert!(handle.is_active());
+    drop(session);
+    assert!(!handle.is_active());
+    assert_eq!(handle.snapshot(), metrics);
+}
+
+/// Start a no-RT session under `policy` and make it fall behind, by feeding more audio at once
+/// than the stub queues.
+fn fall_behind(
+    model: &AprilModel,
+    policy: KeepUpPolicy,
+) -> (KeepUpSession<'_, AprilModel>, KeepUpStats) {
+    let (tx, rx) = mpsc::channel();
+    let stats = KeepUpStats::new();
+    let mut session = KeepUpSession::new(
+        model,
+        AprilConfigFlags::ASYNC_NO_RT,
+        policy,
+        Box::new(move |result, _| {
+            let _ = tx.send(result);
+        }),
+        stats.clone(),
+    )
+    .unwrap();
+    session.feed_pcm16(&mut speech(62)).unwrap();
+    loop {
+        let result = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("stub never reported falling behind");
+        if result == AprilResultType::ErrorCantKeepUp {
+            break;
+        }
+    }
+    assert_eq!(stats.counts().cant_keep_up, 1);
+    (session, stats)
+}
+
+#[test]
+fn keep_up_policies() {
+    let model = model();
+
+    let (mut session, stats) = fall_behind(&model, KeepUpPolicy::Fail);
+    assert!(matches!(
+        session.feed_pcm16(&mut speech(1)),
+        Err(Error::CantKeepUp)
+    ));
+    session.feed_pcm16(&mut speech(1)).unwrap();
+    assert_eq!(stats.counts().failures, 1);
+
+    let (mut session, stats) = fall_behind(&model, KeepUpPolicy::DropAndResync);
+    session.feed_pcm16(&mut speech(1)).unwrap();
+    assert_eq!(stats.counts().resyncs, 1);
+
+    let (mut session, stats) = fall_behind(&model, KeepUpPolicy::SwitchToRealtime);
+    session.flush().unwrap();
+    assert_eq!(session.flags(), AprilConfigFlags::ASYNC_RT);
+    assert!(session.session().get_realtime_speedup().is_ok());
+    assert_eq!(stats.counts().switched_to_realtime, 1);
+
+    let (mut session, stats) = fall_behind(&model, KeepUpPolicy::Backpressure);
+    let start = Instant::now();
+    // two 100ms chunks, paced to realtime
+    session.feed_pcm16(&mut speech(1)[..1600]).unwrap();
+    session.feed_pcm16(&mut speech(1)[..1600]).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(150));
+    assert!(stats.counts().paced_seconds > 0.0);
+}
+
+#[test]
+fn audio_ring_drives_session() {
+    let model = model();
+    let (mut producer, mut consumer) = audio_ring(BLOCK, OverflowPolicy::Block);
+
+    let (metrics, results) = std::thread::scope(|s| {
+        let worker = s.spawn(|| {
+            let (config, results, _) = config(AprilConfigFlags::empty());
+            let mut session = model.create_session(config).unwrap();
+            consumer.drive(&mut session, BLOCK / 5);
+            (session.metrics(), results.try_iter().collect::<Vec<_>>())
+        });
+        // small pushes, more than the ring holds in total
+        for chunk in speech(3).chunks(100) {
+            assert_eq!(producer.push(chunk), chunk.len());
+        }
+        drop(producer);
+        worker.join().unwrap()
+    });
+
+    let stats = consumer.stats();
+    assert_eq!(stats.pushed, 3 * BLOCK as u64);
+    assert_eq!(stats.dropped, 0);
+    assert!((metrics.audio_seconds - 1.5).abs() < 1e-9);
+    assert!(results.contains(&(
+        AprilResultType::RecognitionFinal,
+        " ONE TWO THREE.".to_owned()
+    )));
+}
+
+#[test]
+fn session_worker_delivers_final_flush() {
+    let model = Arc::new(model());
+
+    let (worker, results) =
+        SessionWorker::spawn(model.clone(), AprilConfigFlags::ASYNC_NO_RT).unwrap();
+    worker.feed(speech(1)).unwrap();
+    worker.reset().unwrap();
+    let commands = worker.commands();
+    std::thread::spawn(move || commands.send(WorkerCommand::Feed(speech(2))).unwrap())
+        .join()
+        .unwrap();
+    worker.shutdown().unwrap();
+    // the channel disconnects once the worker is gone, so this ends
+    let results: Vec<_> = results
+        .iter()
+        .map(|(result, tokens)| (result, tokens.to_string()))
+        .collect();
+    assert_eq!(
+        results.last(),
+        Some(&(AprilResultType::Silence, String::new()))
+    );
+    // the reset session starts counting again
+    assert!(results.contains(&(AprilResultType::RecognitionFinal, " ONE TWO.".to_owned())));
+
+    let (worker, _results) = SessionWorker::spawn(model, AprilConfigFlags::empty()).unwrap();
+    let commands = worker.commands();
+    worker.shutdown().unwrap();
+    assert!(commands.send(WorkerCommand::Flush).is_err());
+}
+
+#[test]
+fn session_worker_waits_for_its_own_flush() {
+    let model = Arc::new(model());
+
+    // a flush answered before shutting down leaves nothing to wait for
+    let (worker, results) =
+        SessionWorker::spawn(model.clone(), AprilConfigFlags::ASYNC_NO_RT).unwrap();
+    worker.feed(speech(1)).unwrap();
+    worker.flush().unwrap();
+    while results.recv().unwrap().0 != AprilResultType::RecognitionFinal {}
+    let start = Instant::now();
+    worker.shutdown().unwrap();
+    assert!(start.elapsed() < SHUTDOWN_FLUSH_TIMEOUT / 2);
+
+    // the result of an earlier flush doesn't end the wait for the last one
+    let (worker, results) = SessionWorker::spawn(model, AprilConfigFlags::ASYNC_NO_RT).unwrap();
+    worker.feed(speech(2)).unwrap();
+    worker.flush().unwrap();
+    worker.feed(speech(1)).unwrap();
+    worker.shutdown().unwrap();
+    let results: Vec<_> = results.iter().map(|(result, _)| result).collect();
+    let last_partial = results
+        .iter()
+        .rposition(|&result| result == AprilResultType::RecognitionPartial);
+    let last_final = results
+        .iter()
+        .rposition(|&result| result == AprilResultType::RecognitionFinal);
+    assert!(last_final > last_partial, "{:?}", results);
+}
+
+#[test]
+fn hotwords_fire_once_per_occurrence() {
+    let model = model();
+    let (tx, events) = mpsc::channel();
+    let mut spotter = HotwordSpotter::new();
+    // every stub word has a logprob of -0.5
+    spotter
+        .add("two three", -1.0)
+        .add("One Two Three Four", -1.5)
+        .add("five", -1.0);
+    let mut session = model
+        .new_session(
+            AprilConfigFlags::empty(),
+            spotter.handler(move |event| tx.send(event).unwrap()),
+        )
+        .unwrap();
+    session.feed_pcm16(&mut speech(4));
+    session.flush();
+    session.feed_pcm16(&mut speech(3));
+    session.flush();
+
+    let events: Vec<_> = events.try_iter().collect();
+    let spotted: Vec<_> = events
+        .iter()
+        .map(|event| {
+            (
+                event.phrase.as_str(),
+                event.result,
+                event.start_ms,
+                event.end_ms,
+            )
+        })
+        .collect();
+    // partials repeating an occurrence don't report it again, and the long phrase is too
+    // unlikely
+    assert_eq!(
+        spotted,
+        [
+            ("two three", AprilResultType::RecognitionPartial, 500, 1000),
+            ("two three", AprilResultType::RecognitionPartial, 2500, 3000),
+        ]
+    );
+    assert_eq!(events[0].logprob, -1.0);
+}
+
+#[test]
+fn grammar_matches_tokens() {
+    let mut grammar = Grammar::new();
+    grammar
+        .add_intent("count", "one two <next:number>")
+        .unwrap()
+        .add_intent("say", "say <text>")
+        .unwrap();
+
+    let model = model();
+    let (tx, results) = mpsc::channel();
+    let mut session = model
+        .new_session(
+            AprilConfigFlags::empty(),
+            Box::new(move |result, tokens| {
+                if result == AprilResultType::RecognitionFinal {
+                    tx.send(tokens.into_owned()).unwrap();
+                }
+            }),
+        )
+        .unwrap();
+    session.feed_pcm16(&mut speech(3));
+    session.flush();
+    let matched = grammar.match_tokens(&results.recv().unwrap()).unwrap();
+    assert_eq!(matched.intent, "count");
+    assert_eq!(matched.slots, [("next".to_owned(), SlotValue::Number(3))]);
+}
+
+#[test]
+fn inverse_normalization_for_model() {
+    let itn = InverseNormalizer::for_model(&model()).unwrap().unwrap();
+    assert_eq!(itn.normalize_text("TWENTY FIVE DOLLARS."), "$25.");
+}
+
+#[test]
+fn text_restoration() {
+    let model = model();
+    let (tx, results) = mpsc::channel();
+    let mut session = model
+        .new_session(
+            AprilConfigFlags::empty(),
+            Box::new(move |result, tokens| {
+                if result == AprilResultType::RecognitionFinal {
+                    tx.send(tokens.into_owned()).unwrap();
+                }
+            }),
+        )
+        .unwrap();
+    session.feed_pcm16(&mut speech(3));
+    session.flush();
+
+    let mut restorer = TextRestorer::new();
+    // the stub ends sentences itself, and its words are 500ms apart
+    restorer.set_model_ends_sentences(true);
+    let restored = restorer.restore(&results.recv().unwrap());
+    assert_eq!(restored.to_string(), "One two three.");
+    assert_eq!(restored.annotations.len(), 3);
+    session.feed_pcm16(&mut speech(2));
+    session.flush();
+    restorer.set_gaps(400, 450);
+    let restored = restorer.restore(&results.recv().unwrap());
+    assert_eq!(restored.to_string(), "One, two.");
+}
+
+#[test]
+fn vocabulary_replacement() {
+    let model = model();
+    let (tx, results) = mpsc::channel();
+    let mut session = model
+        .new_session(
+            AprilConfigFlags::empty(),
+            Box::new(move |result, tokens| {
+                if result == AprilResultType::RecognitionFinal {
+                    tx.send(tokens.into_owned()).unwrap();
+                }
+            }),
+        )
+        .unwrap();
+    session.feed_pcm16(&mut speech(4));
+    session.flush();
+    let tokens = results.recv().unwrap();
+
+    let mut vocabulary = Vocabulary::new();
+    vocabulary
+        .add(VocabularyEntry::new("one two", "April", MatchMode::Exact))
+        .add(VocabularyEntry::new("one", "1", MatchMode::Exact))
+        .add(VocabularyEntry::new("thre", "three", MatchMode::Fuzzy))
+        .add(VocabularyEntry {
+            preserve_case: false,
+            ..VocabularyEntry::new("fore", "4", MatchMode::Phonetic)
+        });
+    let replaced = vocabulary.apply(&tokens);
+    // the longest entry wins, uppercase words give uppercase replacements, and punctuation stays
+    assert_eq!(replaced.to_string(), "APRIL THREE 4.");
+    assert_eq!(replaced.replacements.len(), 3);
+    let april = &replaced.replacements[0];
+    assert_eq!((april.word, april.entry), (0, 0));
+    assert_eq!(april.original_span, 0..2);
+    let original: Vec<&str> = april
+        .original
+        .iter()
+        .map(|word| word.text.as_str())
+        .collect();
+    assert_eq!(original, ["ONE", "TWO"]);
+    assert_eq!(replaced.words[0].start_ms, april.original[0].start_ms);
+    assert_eq!(replaced.words[0].end_ms, april.original[1].end_ms);
+    assert_eq!(replaced.words[0].logprob, -1.0);
+    assert_eq!(replaced.replacements[2].original_span, 3..4);
+}